@@ -0,0 +1,10 @@
+use std::env;
+
+/// Follow the libmodbus version modbus-sys found, see modbus-sys/build.rs
+pub fn main() {
+    println!("cargo:rustc-check-cfg=cfg(libmodbus_3_0)");
+    let version = env::var("DEP_MODBUS_VERSION").unwrap_or_default();
+    if version.starts_with("3.0.") {
+        println!("cargo:rustc-cfg=libmodbus_3_0");
+    }
+}
@@ -1,40 +1,255 @@
-
 extern crate pkg_config;
-extern crate gcc;
+extern crate cc;
 
 use std::env;
 use std::fs;
+use std::io::Write;
 use std::path::{PathBuf, Path};
 use std::process::Command;
-use std::io::ErrorKind;
 
+/// Headers the sources include, checked by configure when building libmodbus with autotools
+const UNIX_HEADERS: &'static [&'static str] = &[
+    "HAVE_ARPA_INET_H", "HAVE_ERRNO_H", "HAVE_FCNTL_H", "HAVE_INTTYPES_H", "HAVE_LIMITS_H",
+    "HAVE_NETDB_H", "HAVE_NETINET_IN_H", "HAVE_NETINET_TCP_H", "HAVE_STDINT_H", "HAVE_STDLIB_H",
+    "HAVE_STRING_H", "HAVE_SYS_IOCTL_H", "HAVE_SYS_SOCKET_H", "HAVE_SYS_STAT_H", "HAVE_SYS_TIME_H",
+    "HAVE_SYS_TYPES_H", "HAVE_TERMIOS_H", "HAVE_TIME_H", "HAVE_UNISTD_H",
+];
+
+/// Functions available everywhere but on Windows
+const UNIX_FUNCTIONS: &'static [&'static str] = &[
+    "HAVE_FORK", "HAVE_GETADDRINFO", "HAVE_GETTIMEOFDAY", "HAVE_INET_NTOA", "HAVE_MEMSET",
+    "HAVE_SELECT", "HAVE_SOCKET", "HAVE_STRERROR",
+];
 
 pub fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=CARGO_FEATURE_VENDORED");
+    println!("cargo:rerun-if-env-changed=LIBMODBUS_VERSION");
 
-    let target = env::var("TARGET").unwrap();
+    let (version, include_paths) = if env::var_os("CARGO_FEATURE_VENDORED").is_some() {
+        build_vendored()
+    } else {
+        match pkg_config::find_library("libmodbus") {
+            Ok(lib) => {
+                for path in lib.include_paths.iter() {
+                    println!("cargo:include={}", path.display());
+                }
+                let version = if lib.version.is_empty() {
+                    installed_version(&lib.include_paths)
+                } else {
+                    lib.version
+                };
+                (version, lib.include_paths)
+            }
+            Err(_) => {
+                // Installed without a .pc file maybe, let the linker search its default paths
+                println!("cargo:warning=Couldn't find libmodbus from pkgconfig, enable the \
+                          `vendored` feature to build it from source");
+                println!("cargo:rustc-link-lib=modbus");
+                (installed_version(&[]), Vec::new())
+            }
+        }
+    };
 
-    
-    match pkg_config::find_library("libmodbus") {
-        Ok(lib) => {
-            for path in lib.include_paths.iter() {
-                println!("cargo:include={}", path.display());
+    select_bindings(&version);
+    if !include_paths.is_empty() {
+        compile_layout_check(&version, &include_paths);
+    }
+}
+
+/// The version of an installed libmodbus: LIBMODBUS_VERSION, or LIBMODBUS_VERSION_STRING from
+/// the modbus-version.h found in include_paths or the default include directories
+fn installed_version(include_paths: &[PathBuf]) -> String {
+    if let Ok(version) = env::var("LIBMODBUS_VERSION") {
+        return version
+    }
+
+    let mut dirs = include_paths.to_vec();
+    dirs.push(PathBuf::from("/usr/local/include"));
+    dirs.push(PathBuf::from("/usr/include"));
+    for dir in &dirs {
+        for header in &[dir.join("modbus-version.h"), dir.join("modbus/modbus-version.h")] {
+            let contents = match fs::read_to_string(header) {
+                Ok(contents) => contents,
+                Err(_) => continue,
+            };
+            let version = contents.lines()
+                                  .filter_map(|line| {
+                                      line.trim().splitn(2, "#define LIBMODBUS_VERSION_STRING")
+                                          .nth(1)
+                                  })
+                                  .filter_map(|rest| rest.split('"').nth(1))
+                                  .next();
+            match version {
+                Some(version) => return version.to_string(),
+                None => panic!("LIBMODBUS_VERSION_STRING not found in {}", header.display()),
             }
-            return
         }
-        Err(e) => println!("Couldn't find libmodbus from \
-                            pkgconfig ({:?}), compiling it from source...", e),
+    }
+    panic!("Couldn't find the libmodbus version: modbus-version.h is not in {:?}, set \
+            LIBMODBUS_VERSION (e.g. 3.1.6) or enable the `vendored` feature", dirs);
+}
+
+fn parse_version(version: &str) -> (u32, u32, u32) {
+    let mut parts = version.trim().split('.').map(|part| part.parse::<u32>().ok());
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Some(major)), Some(Some(minor)), Some(Some(micro))) => (major, minor, micro),
+        _ => panic!("Couldn't parse libmodbus version {:?}", version),
+    }
+}
+
+/// Pick the bindings matching the libmodbus version and tell dependents the version through
+/// DEP_MODBUS_VERSION
+fn select_bindings(version: &str) {
+    let (major, minor, micro) = parse_version(version);
+    if (major, minor) < (3, 0) {
+        panic!("libmodbus {} is too old, 3.0 or later is needed", version);
+    }
+    if (major, minor) == (3, 0) {
+        println!("cargo:rustc-cfg=libmodbus_3_0");
+    } else if (major, minor, micro) < (3, 1, 4) {
+        println!("cargo:warning=libmodbus {} is a development release, the bindings follow the \
+                  3.1.4 API", version);
+    }
+    println!("cargo:rustc-check-cfg=cfg(libmodbus_3_0)");
+    println!("cargo:rustc-check-cfg=cfg(modbus_sys_layout_check)");
+    println!("cargo:version={}.{}.{}", major, minor, micro);
+}
+
+/// Compile sizeof(modbus_mapping_t) and its field offsets from modbus.h, for the layout test to
+/// compare with the Rust struct
+fn compile_layout_check(version: &str, include_paths: &[PathBuf]) {
+    let fields = if parse_version(version) < (3, 1, 0) {
+        vec!["nb_bits", "nb_input_bits", "nb_input_registers", "nb_registers",
+             "tab_bits", "tab_input_bits", "tab_input_registers", "tab_registers"]
+    } else {
+        vec!["nb_bits", "start_bits", "nb_input_bits", "start_input_bits",
+             "nb_input_registers", "start_input_registers", "nb_registers", "start_registers",
+             "tab_bits", "tab_input_bits", "tab_input_registers", "tab_registers"]
+    };
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let path = out_dir.join("layout.c");
+    let mut c = fs::File::create(&path).unwrap();
+    writeln!(c, "#include <stddef.h>").unwrap();
+    writeln!(c, "#include <modbus.h>").unwrap();
+    writeln!(c, "const size_t modbus_sys_mapping_layout[] = {{").unwrap();
+    writeln!(c, "    sizeof(modbus_mapping_t),").unwrap();
+    for field in fields {
+        writeln!(c, "    offsetof(modbus_mapping_t, {}),", field).unwrap();
+    }
+    writeln!(c, "}};").unwrap();
+
+    let mut build = cc::Build::new();
+    build.file(&path);
+    for include in include_paths {
+        build.include(include);
+    }
+    build.compile("modbus_sys_layout");
+    println!("cargo:rustc-cfg=modbus_sys_layout_check");
+}
+
+/// Compile the libmodbus submodule into a static library
+fn build_vendored() -> (String, Vec<PathBuf>) {
+    let target = env::var("TARGET").unwrap();
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join("libmodbus");
+    let src = root.join("src");
+
+    if !src.join("modbus.c").exists() {
+        let _ = Command::new("git").args(&["submodule", "update", "--init", "libmodbus"])
+                                   .current_dir(env::var("CARGO_MANIFEST_DIR").unwrap())
+                                   .status();
+    }
+    if !src.join("modbus.c").exists() {
+        panic!("libmodbus sources not found in {}, run `git submodule update --init`",
+               root.display());
+    }
+    println!("cargo:rerun-if-changed={}", src.display());
+
+    let include = out_dir.join("include");
+    fs::create_dir_all(&include).unwrap();
+
+    write_version_header(&root, &include);
+    write_config_header(&target, &out_dir);
+    for header in &["modbus.h", "modbus-rtu.h", "modbus-tcp.h"] {
+        fs::copy(src.join(header), include.join(header)).unwrap();
     }
 
-    if !Path::new("modbus/.git").exists() {
-        let _ = Command::new("git").args(&["submodule", "update", "--init"])
-                                        .status();
+    let mut build = cc::Build::new();
+    build.include(&include)
+         .include(&out_dir)
+         .include(&src)
+         .define("HAVE_CONFIG_H", None)
+         .warnings(false);
+    for file in &["modbus.c", "modbus-data.c", "modbus-rtu.c", "modbus-tcp.c"] {
+        build.file(src.join(file));
+    }
+    if target.contains("windows") {
+        build.define("DLLBUILD", None);
     }
+    // Emits cargo:rustc-link-lib=static=modbus and the matching search path
+    build.compile("modbus");
 
-    /*
-    println!("cargo:rustc-link-search={}/lib", dst.display());
-    println!("cargo:rustc-link-lib=static=curl");
-    println!("cargo:root={}", dst.display());
-    println!("cargo:include={}/include", dst.display());
-    */
+    if target.contains("windows") {
+        println!("cargo:rustc-link-lib=ws2_32");
+    }
+    println!("cargo:root={}", out_dir.display());
+    println!("cargo:include={}", include.display());
 
+    let (major, minor, micro) = version(&root);
+    (format!("{}.{}.{}", major, minor, micro), vec![include])
+}
+
+/// Read the version from configure.ac, as autoconf does
+fn version(root: &Path) -> (String, String, String) {
+    let configure = fs::read_to_string(root.join("configure.ac")).unwrap();
+    let find = |name: &str| {
+        let pattern = format!("m4_define([libmodbus_version_{}], [", name);
+        configure.lines()
+                 .filter_map(|line| line.trim().splitn(2, &pattern[..]).nth(1))
+                 .filter_map(|rest| rest.split(']').next())
+                 .next()
+                 .unwrap_or_else(|| panic!("libmodbus_version_{} not found in configure.ac", name))
+                 .to_string()
+    };
+    (find("major"), find("minor"), find("micro"))
+}
+
+/// Generate modbus-version.h from its template
+fn write_version_header(root: &Path, include: &Path) {
+    let (major, minor, micro) = version(root);
+    let template = fs::read_to_string(root.join("src/modbus-version.h.in")).unwrap();
+    let header = template.replace("@LIBMODBUS_VERSION_MAJOR@", &major)
+                         .replace("@LIBMODBUS_VERSION_MINOR@", &minor)
+                         .replace("@LIBMODBUS_VERSION_MICRO@", &micro)
+                         .replace("@LIBMODBUS_VERSION@", &format!("{}.{}.{}", major, minor, micro));
+    fs::write(include.join("modbus-version.h"), header).unwrap();
+}
+
+/// Generate the config.h configure would write for target
+fn write_config_header(target: &str, out_dir: &Path) {
+    let mut defines: Vec<&str> = Vec::new();
+    if !target.contains("windows") {
+        defines.extend(UNIX_HEADERS);
+        defines.extend(UNIX_FUNCTIONS);
+    }
+    if target.contains("linux") || target.contains("android") {
+        defines.extend(&["HAVE_BYTESWAP_H", "HAVE_LINUX_SERIAL_H", "HAVE_ACCEPT4"]);
+    }
+    if target.contains("apple") || target.contains("freebsd") || target.contains("openbsd") ||
+       target.contains("netbsd") {
+        defines.push("HAVE_STRLCPY");
+    }
+
+    let mut config = fs::File::create(out_dir.join("config.h")).unwrap();
+    writeln!(config, "/* Generated by modbus-sys build.rs */").unwrap();
+    for define in defines {
+        writeln!(config, "#define {} 1", define).unwrap();
+    }
+    let linux = target.contains("linux") as u8;
+    writeln!(config, "#define HAVE_DECL_TIOCSRS485 {}", linux).unwrap();
+    writeln!(config, "#define HAVE_DECL_TIOCM_RTS {}", !target.contains("windows") as u8).unwrap();
+    writeln!(config, "#define HAVE_DECL___CYGWIN__ 0").unwrap();
+    writeln!(config, "#define HAVE_STRUCT_TIMEVAL 1").unwrap();
 }
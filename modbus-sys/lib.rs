@@ -10,7 +10,29 @@
 extern crate libc;
 use libc::{c_int, c_char, c_uint, c_long, c_double, c_void, size_t};
 use libc::c_ulong;
-use libc::{uint8_t, uint16_t, uint32_t, uint64_t};
+use libc::{uint8_t, uint16_t, uint64_t};
+
+// Offset of a field, as in the layout tests bindgen generates
+#[cfg(test)]
+macro_rules! offset_of {
+    ($ty:ty, $field:ident) => {{
+        let value = ::std::mem::MaybeUninit::<$ty>::uninit();
+        let base = value.as_ptr();
+        unsafe { ::std::ptr::addr_of!((*base).$field) as usize - base as usize }
+    }};
+}
+
+// The API changed between libmodbus 3.0 and 3.1: timeouts are uint32_t pairs instead of a struct
+// timeval and modbus_mapping_t gained start addresses. build.rs sets libmodbus_3_0 when linking
+// a 3.0 release; the 3.1 bindings follow 3.1.4, the first stable 3.1 release.
+#[cfg(libmodbus_3_0)]
+mod v3_0;
+#[cfg(libmodbus_3_0)]
+pub use v3_0::*;
+#[cfg(not(libmodbus_3_0))]
+mod v3_1;
+#[cfg(not(libmodbus_3_0))]
+pub use v3_1::*;
 
 #[derive(Copy, Clone)]
 #[repr(u32)]
@@ -29,35 +51,30 @@ pub enum Enum_Unnamed24 {
     MODBUS_EXCEPTION_GATEWAY_TARGET = 11,
     MODBUS_EXCEPTION_MAX = 12,
 }
-pub enum _modbus { }
-pub type modbus_t = _modbus;
-// Doing this one by hand
-/*
-#[repr(C)]
-#[derive(Copy, Clone)]
-#[derive(Debug)]
-pub struct modbus_mapping_t {
-    pub nb_bits: c_int,
-    pub start_bits: c_int,
-    pub nb_input_bits: c_int,
-    pub start_input_bits: c_int,
-    pub nb_input_registers: c_int,
-    pub start_input_registers: c_int,
-    pub nb_registers: c_int,
-    pub start_registers: c_int,
-    pub tab_bits: *mut uint8_t,
-    pub tab_input_bits: *mut uint8_t,
-    pub tab_input_registers: *mut uint16_t,
-    pub tab_registers: *mut uint16_t,
-}
-impl ::std::default::Default for modbus_mapping_t {
-    fn default() -> Self { unsafe { ::std::mem::zeroed() } }
-}
-*/
 
-pub enum modbus_mapping_e { }
-pub type modbus_mapping_t = modbus_mapping_e;
+pub const MODBUS_ENOBASE: c_int = 112345678;
 
+pub const EMBXILFUN: c_int = MODBUS_ENOBASE + Enum_Unnamed24::MODBUS_EXCEPTION_ILLEGAL_FUNCTION as c_int;
+pub const EMBXILADD: c_int = MODBUS_ENOBASE + Enum_Unnamed24::MODBUS_EXCEPTION_ILLEGAL_DATA_ADDRESS as c_int;
+pub const EMBXILVAL: c_int = MODBUS_ENOBASE + Enum_Unnamed24::MODBUS_EXCEPTION_ILLEGAL_DATA_VALUE as c_int;
+pub const EMBXSFAIL: c_int = MODBUS_ENOBASE + Enum_Unnamed24::MODBUS_EXCEPTION_SLAVE_OR_SERVER_FAILURE as c_int;
+pub const EMBXACK: c_int = MODBUS_ENOBASE + Enum_Unnamed24::MODBUS_EXCEPTION_ACKNOWLEDGE as c_int;
+pub const EMBXSBUSY: c_int = MODBUS_ENOBASE + Enum_Unnamed24::MODBUS_EXCEPTION_SLAVE_OR_SERVER_BUSY as c_int;
+pub const EMBXNACK: c_int = MODBUS_ENOBASE + Enum_Unnamed24::MODBUS_EXCEPTION_NEGATIVE_ACKNOWLEDGE as c_int;
+pub const EMBXMEMPAR: c_int = MODBUS_ENOBASE + Enum_Unnamed24::MODBUS_EXCEPTION_MEMORY_PARITY as c_int;
+pub const EMBXGPATH: c_int = MODBUS_ENOBASE + Enum_Unnamed24::MODBUS_EXCEPTION_GATEWAY_PATH as c_int;
+pub const EMBXGTAR: c_int = MODBUS_ENOBASE + Enum_Unnamed24::MODBUS_EXCEPTION_GATEWAY_TARGET as c_int;
+
+// Native libmodbus error codes
+pub const EMBBADCRC: c_int = EMBXGTAR + 1;
+pub const EMBBADDATA: c_int = EMBXGTAR + 2;
+pub const EMBBADEXC: c_int = EMBXGTAR + 3;
+pub const EMBUNKEXC: c_int = EMBXGTAR + 4;
+pub const EMBMDATA: c_int = EMBXGTAR + 5;
+pub const EMBBADSLAVE: c_int = EMBXGTAR + 6;
+
+pub enum _modbus { }
+pub type modbus_t = _modbus;
 #[derive(Copy, Clone)]
 #[repr(u32)]
 #[derive(Debug)]
@@ -67,9 +84,28 @@ pub enum modbus_error_recovery_mode {
     MODBUS_ERROR_RECOVERY_PROTOCOL = 4,
 }
 
+pub const MODBUS_BROADCAST_ADDRESS: c_int = 0;
+
+pub const MODBUS_MAX_READ_BITS: c_int = 2000;
+pub const MODBUS_MAX_WRITE_BITS: c_int = 1968;
+pub const MODBUS_MAX_READ_REGISTERS: c_int = 125;
+pub const MODBUS_MAX_WRITE_REGISTERS: c_int = 123;
+pub const MODBUS_MAX_WR_WRITE_REGISTERS: c_int = 121;
+pub const MODBUS_MAX_WR_READ_REGISTERS: c_int = 125;
+
+pub const MODBUS_MAX_PDU_LENGTH: c_int = 253;
+pub const MODBUS_MAX_ADU_LENGTH: c_int = 260;
+
+pub const MODBUS_TCP_DEFAULT_PORT: c_int = 502;
+pub const MODBUS_TCP_SLAVE: c_int = 0xFF;
+pub const MODBUS_TCP_MAX_ADU_LENGTH: c_int = 260;
+
+pub const MODBUS_RTU_MAX_ADU_LENGTH: c_int = 256;
+
+pub const MODBUS_RTU_RS232: c_int = 0;
+pub const MODBUS_RTU_RS485: c_int = 1;
+
 extern {
-    pub static mut _sys_siglist: [*const c_char; 65usize];
-    pub static mut sys_siglist: [*const c_char; 65usize];
     pub static libmodbus_version_major: c_uint;
     pub static libmodbus_version_minor: c_uint;
     pub static libmodbus_version_micro: c_uint;
@@ -77,20 +113,12 @@ extern {
 
 extern {
     pub fn modbus_set_slave(ctx: *mut modbus_t, slave: c_int) -> c_int;
-    pub fn modbus_get_slave(ctx: *mut modbus_t) -> c_int;
+    // The modes of modbus_error_recovery_mode are flags which can be combined, so take them as
+    // an integer rather than as the enum
     pub fn modbus_set_error_recovery(ctx: *mut modbus_t,
-                                     error_recovery: modbus_error_recovery_mode) -> c_int;
+                                     error_recovery: c_uint) -> c_int;
     pub fn modbus_set_socket(ctx: *mut modbus_t, s: c_int) -> c_int;
     pub fn modbus_get_socket(ctx: *mut modbus_t) -> c_int;
-    pub fn modbus_get_response_timeout(ctx: *mut modbus_t,
-                                       to_sec: *mut uint32_t,
-                                       to_usec: *mut uint32_t) -> c_int;
-    pub fn modbus_set_response_timeout(ctx: *mut modbus_t, to_sec: uint32_t,
-                                       to_usec: uint32_t) -> c_int;
-    pub fn modbus_get_byte_timeout(ctx: *mut modbus_t, to_sec: *mut uint32_t,
-                                   to_usec: *mut uint32_t) -> c_int;
-    pub fn modbus_set_byte_timeout(ctx: *mut modbus_t, to_sec: uint32_t,
-                                   to_usec: uint32_t) -> c_int;
     pub fn modbus_get_header_length(ctx: *mut modbus_t) -> c_int;
     pub fn modbus_connect(ctx: *mut modbus_t) -> c_int;
     pub fn modbus_close(ctx: *mut modbus_t);
@@ -124,9 +152,6 @@ extern {
                                   addr: c_int,
                                   nb: c_int,
                                   data: *const uint16_t) -> c_int;
-    pub fn modbus_mask_write_register(ctx: *mut modbus_t,
-                                      addr: c_int,
-                                      and_mask: uint16_t, or_mask: uint16_t) -> c_int;
     pub fn modbus_write_and_read_registers(ctx: *mut modbus_t,
                                            write_addr: c_int,
                                            write_nb: c_int,
@@ -134,17 +159,6 @@ extern {
                                            read_addr: c_int,
                                            read_nb: c_int,
                                            dest: *mut uint16_t) -> c_int;
-    pub fn modbus_report_slave_id(ctx: *mut modbus_t,
-                                  max_dest: c_int,
-                                  dest: *mut uint8_t) -> c_int;
-    pub fn modbus_mapping_new_start_address(start_bits: c_uint,
-                                            nb_bits: c_uint,
-                                            start_input_bits: c_uint,
-                                            nb_input_bits: c_uint,
-                                            start_registers: c_uint,
-                                            nb_registers: c_uint,
-                                            start_input_registers: c_uint,
-                                            nb_input_registers: c_uint) -> *mut modbus_mapping_t;
     pub fn modbus_mapping_new(nb_bits: c_int,
                               nb_input_bits: c_int,
                               nb_registers: c_int,
@@ -170,15 +184,7 @@ extern {
                                      idx: c_int,
                                      nb_bits: c_uint) -> uint8_t;
     pub fn modbus_get_float(src: *const uint16_t) -> f32;
-    pub fn modbus_get_float_abcd(src: *const uint16_t) -> f32;
-    pub fn modbus_get_float_dcba(src: *const uint16_t) -> f32;
-    pub fn modbus_get_float_badc(src: *const uint16_t) -> f32;
-    pub fn modbus_get_float_cdab(src: *const uint16_t) -> f32;
     pub fn modbus_set_float(f: f32, dest: *mut uint16_t);
-    pub fn modbus_set_float_abcd(f: f32, dest: *mut uint16_t);
-    pub fn modbus_set_float_dcba(f: f32, dest: *mut uint16_t);
-    pub fn modbus_set_float_badc(f: f32, dest: *mut uint16_t);
-    pub fn modbus_set_float_cdab(f: f32, dest: *mut uint16_t);
     pub fn modbus_new_tcp(ip_address: *const c_char, port: c_int) -> *mut modbus_t;
     pub fn modbus_tcp_listen(ctx: *mut modbus_t,
                              nb_connection: c_int) -> c_int;
@@ -198,15 +204,21 @@ extern {
     pub fn modbus_rtu_set_serial_mode(ctx: *mut modbus_t,
                                       mode: c_int) -> c_int;
     pub fn modbus_rtu_get_serial_mode(ctx: *mut modbus_t) -> c_int;
-    pub fn modbus_rtu_set_rts(ctx: *mut modbus_t, mode: c_int) -> c_int;
-    pub fn modbus_rtu_get_rts(ctx: *mut modbus_t) -> c_int;
-    pub fn modbus_rtu_set_custom_rts(ctx: *mut modbus_t,
-                                     set_rts:
-                                         ::std::option::Option<unsafe extern "C" fn(ctx: *mut modbus_t,
-                                                                                    on: c_int)>)
-     -> c_int;
-    pub fn modbus_rtu_set_rts_delay(ctx: *mut modbus_t,
-                                    us: c_int) -> c_int;
-    pub fn modbus_rtu_get_rts_delay(ctx: *mut modbus_t) -> c_int;
 }
 
+// sizeof(modbus_mapping_t) then the offset of each field, compiled from modbus.h by build.rs when
+// the headers are found
+#[cfg(all(test, modbus_sys_layout_check))]
+extern {
+    static modbus_sys_mapping_layout: [size_t; MAPPING_FIELDS + 1];
+}
+
+#[cfg(test)]
+fn check_mapping_layout(rust: &[usize])
+{
+    assert_eq!(rust.len(), MAPPING_FIELDS + 1);
+    #[cfg(modbus_sys_layout_check)]
+    unsafe {
+        assert_eq!(rust, &modbus_sys_mapping_layout[..]);
+    }
+}
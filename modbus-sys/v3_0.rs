@@ -0,0 +1,51 @@
+/* bindings for the libmodbus 3.0 API */
+
+use libc::{c_int, timeval};
+use libc::{uint8_t, uint16_t};
+
+use modbus_t;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[derive(Debug)]
+pub struct modbus_mapping_t {
+    pub nb_bits: c_int,
+    pub nb_input_bits: c_int,
+    pub nb_input_registers: c_int,
+    pub nb_registers: c_int,
+    pub tab_bits: *mut uint8_t,
+    pub tab_input_bits: *mut uint8_t,
+    pub tab_input_registers: *mut uint16_t,
+    pub tab_registers: *mut uint16_t,
+}
+impl ::std::default::Default for modbus_mapping_t {
+    fn default() -> Self { unsafe { ::std::mem::zeroed() } }
+}
+
+pub const MAPPING_FIELDS: usize = 8;
+
+#[test]
+fn bindgen_test_layout_modbus_mapping_t() {
+    let ptr = ::std::mem::size_of::<*mut uint8_t>();
+    assert_eq!(::std::mem::size_of::<modbus_mapping_t>(), 16 + 4 * ptr);
+    assert_eq!(::std::mem::align_of::<modbus_mapping_t>(), ptr);
+    ::check_mapping_layout(&[
+        ::std::mem::size_of::<modbus_mapping_t>(),
+        offset_of!(modbus_mapping_t, nb_bits),
+        offset_of!(modbus_mapping_t, nb_input_bits),
+        offset_of!(modbus_mapping_t, nb_input_registers),
+        offset_of!(modbus_mapping_t, nb_registers),
+        offset_of!(modbus_mapping_t, tab_bits),
+        offset_of!(modbus_mapping_t, tab_input_bits),
+        offset_of!(modbus_mapping_t, tab_input_registers),
+        offset_of!(modbus_mapping_t, tab_registers),
+    ]);
+}
+
+extern {
+    pub fn modbus_get_response_timeout(ctx: *mut modbus_t, timeout: *mut timeval);
+    pub fn modbus_set_response_timeout(ctx: *mut modbus_t, timeout: *const timeval);
+    pub fn modbus_get_byte_timeout(ctx: *mut modbus_t, timeout: *mut timeval);
+    pub fn modbus_set_byte_timeout(ctx: *mut modbus_t, timeout: *const timeval);
+    pub fn modbus_report_slave_id(ctx: *mut modbus_t, dest: *mut uint8_t) -> c_int;
+}
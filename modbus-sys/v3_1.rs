@@ -0,0 +1,100 @@
+/* bindings for the libmodbus 3.1 API, from 3.1.4 */
+
+use libc::{c_int, c_uint};
+use libc::{uint8_t, uint16_t, uint32_t};
+
+use modbus_t;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[derive(Debug)]
+pub struct modbus_mapping_t {
+    pub nb_bits: c_int,
+    pub start_bits: c_int,
+    pub nb_input_bits: c_int,
+    pub start_input_bits: c_int,
+    pub nb_input_registers: c_int,
+    pub start_input_registers: c_int,
+    pub nb_registers: c_int,
+    pub start_registers: c_int,
+    pub tab_bits: *mut uint8_t,
+    pub tab_input_bits: *mut uint8_t,
+    pub tab_input_registers: *mut uint16_t,
+    pub tab_registers: *mut uint16_t,
+}
+impl ::std::default::Default for modbus_mapping_t {
+    fn default() -> Self { unsafe { ::std::mem::zeroed() } }
+}
+
+pub const MAPPING_FIELDS: usize = 12;
+
+#[test]
+fn bindgen_test_layout_modbus_mapping_t() {
+    let ptr = ::std::mem::size_of::<*mut uint8_t>();
+    assert_eq!(::std::mem::size_of::<modbus_mapping_t>(), 32 + 4 * ptr);
+    assert_eq!(::std::mem::align_of::<modbus_mapping_t>(), ptr);
+    ::check_mapping_layout(&[
+        ::std::mem::size_of::<modbus_mapping_t>(),
+        offset_of!(modbus_mapping_t, nb_bits),
+        offset_of!(modbus_mapping_t, start_bits),
+        offset_of!(modbus_mapping_t, nb_input_bits),
+        offset_of!(modbus_mapping_t, start_input_bits),
+        offset_of!(modbus_mapping_t, nb_input_registers),
+        offset_of!(modbus_mapping_t, start_input_registers),
+        offset_of!(modbus_mapping_t, nb_registers),
+        offset_of!(modbus_mapping_t, start_registers),
+        offset_of!(modbus_mapping_t, tab_bits),
+        offset_of!(modbus_mapping_t, tab_input_bits),
+        offset_of!(modbus_mapping_t, tab_input_registers),
+        offset_of!(modbus_mapping_t, tab_registers),
+    ]);
+}
+
+pub const MODBUS_RTU_RTS_NONE: c_int = 0;
+pub const MODBUS_RTU_RTS_UP: c_int = 1;
+pub const MODBUS_RTU_RTS_DOWN: c_int = 2;
+
+extern {
+    pub fn modbus_get_slave(ctx: *mut modbus_t) -> c_int;
+    pub fn modbus_get_response_timeout(ctx: *mut modbus_t,
+                                       to_sec: *mut uint32_t,
+                                       to_usec: *mut uint32_t) -> c_int;
+    pub fn modbus_set_response_timeout(ctx: *mut modbus_t, to_sec: uint32_t,
+                                       to_usec: uint32_t) -> c_int;
+    pub fn modbus_get_byte_timeout(ctx: *mut modbus_t, to_sec: *mut uint32_t,
+                                   to_usec: *mut uint32_t) -> c_int;
+    pub fn modbus_set_byte_timeout(ctx: *mut modbus_t, to_sec: uint32_t,
+                                   to_usec: uint32_t) -> c_int;
+    pub fn modbus_mask_write_register(ctx: *mut modbus_t,
+                                      addr: c_int,
+                                      and_mask: uint16_t, or_mask: uint16_t) -> c_int;
+    pub fn modbus_report_slave_id(ctx: *mut modbus_t,
+                                  max_dest: c_int,
+                                  dest: *mut uint8_t) -> c_int;
+    pub fn modbus_mapping_new_start_address(start_bits: c_uint,
+                                            nb_bits: c_uint,
+                                            start_input_bits: c_uint,
+                                            nb_input_bits: c_uint,
+                                            start_registers: c_uint,
+                                            nb_registers: c_uint,
+                                            start_input_registers: c_uint,
+                                            nb_input_registers: c_uint) -> *mut modbus_mapping_t;
+    pub fn modbus_get_float_abcd(src: *const uint16_t) -> f32;
+    pub fn modbus_get_float_dcba(src: *const uint16_t) -> f32;
+    pub fn modbus_get_float_badc(src: *const uint16_t) -> f32;
+    pub fn modbus_get_float_cdab(src: *const uint16_t) -> f32;
+    pub fn modbus_set_float_abcd(f: f32, dest: *mut uint16_t);
+    pub fn modbus_set_float_dcba(f: f32, dest: *mut uint16_t);
+    pub fn modbus_set_float_badc(f: f32, dest: *mut uint16_t);
+    pub fn modbus_set_float_cdab(f: f32, dest: *mut uint16_t);
+    pub fn modbus_rtu_set_rts(ctx: *mut modbus_t, mode: c_int) -> c_int;
+    pub fn modbus_rtu_get_rts(ctx: *mut modbus_t) -> c_int;
+    pub fn modbus_rtu_set_custom_rts(ctx: *mut modbus_t,
+                                     set_rts:
+                                         ::std::option::Option<unsafe extern "C" fn(ctx: *mut modbus_t,
+                                                                                    on: c_int)>)
+     -> c_int;
+    pub fn modbus_rtu_set_rts_delay(ctx: *mut modbus_t,
+                                    us: c_int) -> c_int;
+    pub fn modbus_rtu_get_rts_delay(ctx: *mut modbus_t) -> c_int;
+}
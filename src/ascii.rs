@@ -0,0 +1,200 @@
+use std::time::{Duration, Instant};
+
+use libc::c_int;
+
+use crate::{ModbusError, ModbusResult, Parity, DataBits, StopBits};
+use crate::client::{io_error, ModbusClient};
+use crate::codec::{self, ASCII_MAX_ADU_LENGTH};
+use crate::serial::{self, SerialPort};
+use crate::transport::{Adu, Transport};
+
+/// A Modbus ASCII client, written in Rust since libmodbus does not implement ASCII
+///
+/// Frames are sent as hexadecimal between ':' and CR LF, checked with a LRC. A response must
+/// start within the response timeout, and its characters must then follow each other within the
+/// byte (inter-character) timeout, 1 second by default as the specification recommends.
+///
+/// The methods mirror the ones of `Modbus` for RTU and return the same errors; an invalid LRC is
+/// reported as `ModbusError::BadCrc`.
+///
+/// # Example
+///
+/// ```no_run
+/// use modbus::{ModbusAscii, Parity, DataBits, StopBits};
+///
+/// let mb = ModbusAscii::new_ascii("/dev/ttyUSB0", 9600, Parity::Even,
+///                                 DataBits::Seven, StopBits::One).unwrap();
+/// mb.connect().unwrap();
+/// mb.set_slave(1).unwrap();
+///
+/// let mut dest = [0u16; 4];
+/// mb.read_registers(0, &mut dest).unwrap();
+/// ```
+pub type ModbusAscii = ModbusClient<AsciiTransport>;
+
+/// Modbus ASCII over a serial port configured with termios, see `ModbusAscii`
+pub struct AsciiTransport {
+    device: String,
+    baud: c_int,
+    parity: Parity,
+    data_bits: DataBits,
+    stop_bits: StopBits,
+    port: Option<SerialPort>,
+    byte_timeout: Option<Duration>,
+}
+
+impl AsciiTransport {
+    /// A transport over the serial port device, opened by `Transport::connect`
+    pub fn new(device: &str, baud: c_int, parity: Parity,
+               data_bits: DataBits, stop_bits: StopBits) -> Result<AsciiTransport, ModbusError>
+    {
+        if device.is_empty() || device.contains('\0') {
+            return Err(io_error(libc::EINVAL))
+        }
+        // Only the rates termios knows, rather than failing on connect
+        serial::speed(baud)?;
+
+        Ok(AsciiTransport {
+            device: device.to_string(),
+            baud,
+            parity,
+            data_bits,
+            stop_bits,
+            port: None,
+            byte_timeout: Some(Duration::from_secs(1)),
+        })
+    }
+
+    /// The timeout between two characters of a frame, None if it is disabled
+    pub fn byte_timeout(&self) -> Option<Duration>
+    {
+        self.byte_timeout
+    }
+
+    /// Set the timeout between two characters of a frame, see `ModbusAscii::set_byte_timeout`
+    pub fn set_byte_timeout(&mut self, timeout: Option<Duration>) -> Result<(), ModbusError>
+    {
+        if timeout == Some(Duration::from_secs(0)) {
+            return Err(io_error(libc::EINVAL))
+        }
+        self.byte_timeout = timeout;
+        Ok(())
+    }
+
+    fn port(&mut self) -> Result<&mut SerialPort, ModbusError>
+    {
+        self.port.as_mut().ok_or_else(|| io_error(libc::EBADF))
+    }
+}
+
+impl Transport for AsciiTransport {
+    fn connect(&mut self) -> Result<(), ModbusError>
+    {
+        let port = SerialPort::open(&self.device, self.baud, self.parity,
+                                    self.data_bits, self.stop_bits)?;
+        self.port = Some(port);
+        Ok(())
+    }
+
+    fn close(&mut self)
+    {
+        self.port = None;
+    }
+
+    fn header_length(&self) -> usize
+    {
+        1
+    }
+
+    fn frame_length(&self, adu: &Adu) -> usize
+    {
+        // ':', the slave address, the PDU and the LRC in hexadecimal, then CR LF
+        1 + 2 * (1 + adu.pdu.len() + 1) + 2
+    }
+
+    fn has_transaction_id(&self) -> bool
+    {
+        false
+    }
+
+    fn send(&mut self, adu: &Adu) -> Result<(), ModbusError>
+    {
+        if adu.pdu.len() > codec::MAX_PDU_LENGTH {
+            return Err(ModbusError::TooManyData)
+        }
+        let frame = codec::encode_ascii(adu.slave, &adu.pdu);
+        let port = self.port()?;
+        port.write_frame(&frame)?;
+        Ok(())
+    }
+
+    /// Read a frame: skip anything before ':', then read up to CR LF
+    fn receive(&mut self, timeout: Option<Duration>) -> Result<Adu, ModbusError>
+    {
+        let byte_timeout = self.byte_timeout;
+        let port = self.port()?;
+
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut adu = Vec::with_capacity(ASCII_MAX_ADU_LENGTH);
+        let mut chunk = [0u8; 64];
+        loop {
+            let timeout = match byte_timeout {
+                Some(timeout) if !adu.is_empty() => Some(timeout),
+                _ => match deadline {
+                    Some(deadline) => {
+                        let now = Instant::now();
+                        if now >= deadline {
+                            return Err(io_error(libc::ETIMEDOUT))
+                        }
+                        Some(deadline - now)
+                    }
+                    None => None,
+                },
+            };
+            let n = port.read(&mut chunk, timeout)?;
+            for &c in &chunk[..n] {
+                if c == b':' {
+                    // A colon always starts a new frame
+                    adu.clear();
+                } else if adu.is_empty() {
+                    continue
+                }
+                adu.push(c);
+                if adu.ends_with(b"\r\n") {
+                    let frame = codec::decode_ascii(&adu)?;
+                    return Ok(Adu { slave: frame.slave, transaction_id: 0, pdu: frame.pdu })
+                }
+                if adu.len() >= ASCII_MAX_ADU_LENGTH {
+                    return Err(ModbusError::BadData)
+                }
+            }
+        }
+    }
+}
+
+impl ModbusClient<AsciiTransport> {
+    /// Create a new Modbus ASCII client, see `Modbus::new_rtu`
+    ///
+    /// Modbus ASCII lines usually use 7 data bits. The device is opened by `ModbusClient::connect`.
+    pub fn new_ascii(device: &str, baud: c_int, parity: Parity,
+                     data_bits: DataBits, stop_bits: StopBits) -> Result<ModbusAscii, ModbusError>
+    {
+        Ok(ModbusClient::new(AsciiTransport::new(device, baud, parity, data_bits, stop_bits)?))
+    }
+
+    /// Get the timeout between two characters of a response, None if it is disabled
+    pub fn get_byte_timeout(&self) -> Result<Option<Duration>, ModbusError>
+    {
+        Ok(self.transport().byte_timeout())
+    }
+
+    /// Set the timeout between two characters of a response, 1 s by default
+    ///
+    /// With None, the byte timeout is disabled and the whole response must be received within the
+    /// response timeout. Otherwise the timeout must not be zero.
+    pub fn set_byte_timeout(&self, timeout: Option<Duration>) -> ModbusResult
+    {
+        self.transport().set_byte_timeout(timeout)?;
+        Ok(0)
+    }
+}
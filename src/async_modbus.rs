@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Duration;
+
+use libc::c_int;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+use crate::{ModbusError, ModbusResult, Bit, MAX_ADU_LENGTH, MODBUS_BROADCAST_ADDRESS,
+            MODBUS_TCP_SLAVE};
+use crate::client::{io_error, address, quantity, check_response, bits_into, registers_into};
+use crate::codec::{self, Request, Response};
+
+/// An asynchronous Modbus TCP client, written in Rust on top of Tokio
+///
+/// Requests can be made concurrently from many tasks sharing the client: they are all sent on
+/// the same connection without waiting for the previous responses, each response being matched
+/// with its request by the transaction identifier of its MBAP header. The frames are written by
+/// a task of their own, so a request whose future is dropped never leaves part of a frame on the
+/// connection.
+///
+/// The methods mirror the ones of `Modbus` and return the same errors.
+///
+/// # Example
+///
+/// ```no_run
+/// use modbus::AsyncModbus;
+///
+/// # async fn poll() -> Result<(), modbus::ModbusError> {
+/// let addr = "192.168.1.10:502".parse().unwrap();
+/// let mb = AsyncModbus::connect(&addr).await?;
+///
+/// let (mut a, mut b) = ([0u16; 10], [0u16; 10]);
+/// let (ra, rb) = tokio::join!(mb.read_registers(0, &mut a), mb.read_registers(100, &mut b));
+/// ra?;
+/// rb?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncModbus {
+    inner: Arc<Inner>,
+    frames: mpsc::UnboundedSender<Vec<u8>>,
+    writer: JoinHandle<()>,
+    reader: JoinHandle<()>,
+    slave: AtomicU8,
+    response_timeout: Mutex<Duration>,
+}
+
+struct Inner {
+    transactions: Mutex<Transactions>,
+}
+
+impl Inner {
+    /// Fail the waiting requests and the next ones, once the connection is lost
+    fn close(&self)
+    {
+        let mut transactions = self.transactions.lock().unwrap();
+        transactions.closed = true;
+        // Dropping the senders fails the waiting requests
+        transactions.waiting.clear();
+    }
+}
+
+/// Requests waiting for their response, by transaction identifier
+struct Transactions {
+    next_id: u16,
+    waiting: HashMap<u16, oneshot::Sender<(u8, Vec<u8>)>>,
+    closed: bool,
+}
+
+/// Forget a transaction when its request is answered, fails or is cancelled
+struct TransactionGuard<'a> {
+    inner: &'a Inner,
+    id: u16,
+}
+
+impl<'a> Drop for TransactionGuard<'a> {
+    fn drop(&mut self) {
+        self.inner.transactions.lock().unwrap().waiting.remove(&self.id);
+    }
+}
+
+impl AsyncModbus {
+    /// Connect to a Modbus TCP server
+    ///
+    /// Must be called from a Tokio runtime, which runs the task reading the responses.
+    pub async fn connect(addr: &SocketAddr) -> Result<AsyncModbus, ModbusError>
+    {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        let (reader, writer) = stream.into_split();
+
+        let inner = Arc::new(Inner {
+            transactions: Mutex::new(Transactions {
+                next_id: 0,
+                waiting: HashMap::new(),
+                closed: false,
+            }),
+        });
+
+        let (frames, queue) = mpsc::unbounded_channel();
+        Ok(AsyncModbus {
+            reader: tokio::spawn(read_responses(reader, inner.clone())),
+            writer: tokio::spawn(write_frames(writer, queue, inner.clone())),
+            inner,
+            frames,
+            slave: AtomicU8::new(MODBUS_TCP_SLAVE as u8),
+            response_timeout: Mutex::new(Duration::from_millis(500)),
+        })
+    }
+
+    /// Set the unit identifier of the requests, `MODBUS_TCP_SLAVE` (255) by default
+    ///
+    /// Writes to `MODBUS_BROADCAST_ADDRESS` (0) are sent without waiting for a response, reads
+    /// fail with `EINVAL`.
+    pub fn set_slave(&self, slave: c_int) -> ModbusResult
+    {
+        if !(0..=255).contains(&slave) {
+            return Err(io_error(libc::EINVAL))
+        }
+        self.slave.store(slave as u8, Ordering::SeqCst);
+        Ok(0)
+    }
+
+    /// Get the unit identifier of the requests
+    pub fn slave(&self) -> c_int
+    {
+        self.slave.load(Ordering::SeqCst) as c_int
+    }
+
+    /// Get the timeout to wait for a response
+    pub fn get_response_timeout(&self) -> Result<Duration, ModbusError>
+    {
+        Ok(*self.response_timeout.lock().unwrap())
+    }
+
+    /// Set the timeout to wait for a response, 500 ms by default
+    ///
+    /// A request not answered in time fails with `ETIMEDOUT`; the connection stays open and a
+    /// late response is ignored.
+    pub fn set_response_timeout(&self, timeout: Duration) -> ModbusResult
+    {
+        if timeout == Duration::from_secs(0) {
+            return Err(io_error(libc::EINVAL))
+        }
+        *self.response_timeout.lock().unwrap() = timeout;
+        Ok(0)
+    }
+
+    /// Send a request and wait for its response, an exception being returned as an error
+    async fn call(&self, req: Request) -> Result<Response, ModbusError>
+    {
+        let pdu = req.encode()?;
+        let slave = self.slave.load(Ordering::SeqCst);
+        if slave as c_int == MODBUS_BROADCAST_ADDRESS {
+            return Err(io_error(libc::EINVAL))
+        }
+
+        let (tx, rx) = oneshot::channel();
+        let guard = {
+            let mut transactions = self.inner.transactions.lock().unwrap();
+            if transactions.closed {
+                return Err(io_error(libc::ECONNRESET))
+            }
+            if transactions.waiting.len() > u16::MAX as usize {
+                return Err(io_error(libc::EAGAIN))
+            }
+            let mut id = transactions.next_id;
+            while transactions.waiting.contains_key(&id) {
+                id = id.wrapping_add(1);
+            }
+            transactions.next_id = id.wrapping_add(1);
+            transactions.waiting.insert(id, tx);
+            TransactionGuard { inner: &self.inner, id }
+        };
+
+        self.frames.send(codec::encode_tcp(guard.id, slave, &pdu))
+                   .map_err(|_| io_error(libc::ECONNRESET))?;
+
+        let timeout = *self.response_timeout.lock().unwrap();
+        let (unit_id, pdu) = match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(rsp)) => rsp,
+            Ok(Err(_)) => return Err(io_error(libc::ECONNRESET)),
+            Err(_) => return Err(io_error(libc::ETIMEDOUT)),
+        };
+        drop(guard);
+
+        if unit_id != slave {
+            return Err(ModbusError::BadSlave)
+        }
+        check_response(&req, &pdu)
+    }
+
+    /// Send a write request, which is not answered when broadcast
+    async fn write(&self, req: Request, nb: usize) -> ModbusResult
+    {
+        let slave = self.slave.load(Ordering::SeqCst);
+        if slave as c_int != MODBUS_BROADCAST_ADDRESS {
+            self.call(req).await?;
+            return Ok(nb as i32)
+        }
+
+        let pdu = req.encode()?;
+        let id = {
+            let mut transactions = self.inner.transactions.lock().unwrap();
+            if transactions.closed {
+                return Err(io_error(libc::ECONNRESET))
+            }
+            let id = transactions.next_id;
+            transactions.next_id = id.wrapping_add(1);
+            id
+        };
+        self.frames.send(codec::encode_tcp(id, slave, &pdu))
+                   .map_err(|_| io_error(libc::ECONNRESET))?;
+        Ok(nb as i32)
+    }
+
+    async fn read_bits_with<B: Bit>(&self, req: Request, dest: &mut [B]) -> ModbusResult
+    {
+        bits_into(self.call(req).await?, dest)
+    }
+
+    async fn read_registers_with(&self, req: Request, dest: &mut [u16]) -> ModbusResult
+    {
+        registers_into(self.call(req).await?, dest)
+    }
+
+    /// Read many bits, see `Modbus::read_bits`
+    pub async fn read_bits<B: Bit>(&self, addr: c_int, dest: &mut [B]) -> ModbusResult
+    {
+        let req = Request::ReadCoils { addr: address(addr)?, nb: quantity(dest.len())? };
+        self.read_bits_with(req, dest).await
+    }
+
+    /// Read many input bits, see `Modbus::read_input_bits`
+    pub async fn read_input_bits<B: Bit>(&self, addr: c_int, dest: &mut [B]) -> ModbusResult
+    {
+        let req = Request::ReadDiscreteInputs { addr: address(addr)?, nb: quantity(dest.len())? };
+        self.read_bits_with(req, dest).await
+    }
+
+    /// Read many registers, see `Modbus::read_registers`
+    pub async fn read_registers(&self, addr: c_int, dest: &mut [u16]) -> ModbusResult
+    {
+        let req = Request::ReadHoldingRegisters { addr: address(addr)?, nb: quantity(dest.len())? };
+        self.read_registers_with(req, dest).await
+    }
+
+    /// Read many input registers, see `Modbus::read_input_registers`
+    pub async fn read_input_registers(&self, addr: c_int, dest: &mut [u16]) -> ModbusResult
+    {
+        let req = Request::ReadInputRegisters { addr: address(addr)?, nb: quantity(dest.len())? };
+        self.read_registers_with(req, dest).await
+    }
+
+    /// Write a single bit, see `Modbus::write_bit`
+    pub async fn write_bit(&self, coil_addr: c_int, status: c_int) -> ModbusResult
+    {
+        let req = Request::WriteSingleCoil { addr: address(coil_addr)?, value: status != 0 };
+        self.write(req, 1).await
+    }
+
+    /// Write many bits, see `Modbus::write_bits`
+    pub async fn write_bits<B: Bit>(&self, addr: c_int, data: &[B]) -> ModbusResult
+    {
+        let values = data.iter().map(|b| b.to_u8() != 0).collect();
+        let req = Request::WriteMultipleCoils { addr: address(addr)?, values };
+        self.write(req, data.len()).await
+    }
+
+    /// Write a single register, see `Modbus::write_register`
+    pub async fn write_register(&self, reg_addr: c_int, value: c_int) -> ModbusResult
+    {
+        let req = Request::WriteSingleRegister { addr: address(reg_addr)?, value: value as u16 };
+        self.write(req, 1).await
+    }
+
+    /// Write many registers, see `Modbus::write_registers`
+    pub async fn write_registers(&self, addr: c_int, data: &[u16]) -> ModbusResult
+    {
+        let req = Request::WriteMultipleRegisters { addr: address(addr)?, values: data.to_vec() };
+        self.write(req, data.len()).await
+    }
+
+    /// Modify a single register using masks, see `Modbus::mask_write_register`
+    pub async fn mask_write_register(&self, addr: c_int, and_mask: u16, or_mask: u16)
+        -> ModbusResult
+    {
+        let req = Request::MaskWriteRegister {
+            addr: address(addr)?,
+            and_mask,
+            or_mask,
+        };
+        self.write(req, 1).await
+    }
+
+    /// Write and read many registers in a single transaction, see
+    /// `Modbus::write_and_read_registers`
+    pub async fn write_and_read_registers(&self, write_addr: c_int, src: &[u16],
+                                          read_addr: c_int, dest: &mut [u16]) -> ModbusResult
+    {
+        let req = Request::ReadWriteMultipleRegisters {
+            read_addr: address(read_addr)?,
+            read_nb: quantity(dest.len())?,
+            write_addr: address(write_addr)?,
+            values: src.to_vec(),
+        };
+        self.read_registers_with(req, dest).await
+    }
+}
+
+impl Drop for AsyncModbus {
+    fn drop(&mut self) {
+        self.writer.abort();
+        self.reader.abort();
+    }
+}
+
+/// Write the frames of the requests one after the other, until the connection is closed
+async fn write_frames(mut writer: OwnedWriteHalf, mut frames: mpsc::UnboundedReceiver<Vec<u8>>,
+                      inner: Arc<Inner>)
+{
+    while let Some(frame) = frames.recv().await {
+        if writer.write_all(&frame).await.is_err() {
+            break
+        }
+    }
+    inner.close();
+}
+
+/// Hand the responses to the requests waiting for them, until the connection is closed
+async fn read_responses(mut reader: OwnedReadHalf, inner: Arc<Inner>)
+{
+    let mut buf = Vec::with_capacity(2 * MAX_ADU_LENGTH);
+    let mut chunk = [0u8; MAX_ADU_LENGTH];
+    loop {
+        match codec::tcp_frame_length(&buf) {
+            Ok(Some(len)) if buf.len() >= len => {
+                let adu: Vec<u8> = buf.drain(..len).collect();
+                if let Ok(frame) = codec::decode_tcp(&adu) {
+                    let waiting = inner.transactions.lock().unwrap()
+                                       .waiting.remove(&frame.transaction_id);
+                    if let Some(tx) = waiting {
+                        let _ = tx.send((frame.unit_id, frame.pdu.to_vec()));
+                    }
+                }
+                continue
+            }
+            Ok(_) => {}
+            // Not a Modbus stream, or lost track of the frames
+            Err(_) => break,
+        }
+        match reader.read(&mut chunk).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+        }
+    }
+
+    inner.close();
+}
@@ -1,47 +1,123 @@
 
 extern crate rand;
-extern crate errno;
-use errno::{Errno, errno};
 
 extern crate modbus;
-use modbus::{Modbus, ModbusMapping, ModbusResult};
-use rand::{thread_rng, Rng};
+#[cfg(feature = "libmodbus")]
+use modbus::Modbus;
+use modbus::{ModbusClient, ModbusResult};
+use rand::Rng;
 
 /* The goal of this program is to check all major functions of
    libmodbus:
    - write_coil
    - read_bits
    - write_coils
+   - read_input_bits
    - write_register
    - read_registers
    - write_registers
    - read_registers
+   - mask_write_register
 
    All these functions are called with random values on a address
    range defined by the following defines.
+
+   The first argument selects the client: "libmodbus" (the default when
+   the libmodbus feature is enabled) or "rust" for ModbusClient. The
+   second one is the address of the server, 127.0.0.1:1502 by default.
 */
 
 const LOOP: i32             =   1;
-const SERVER_ID: i32        =   17;
 const ADDRESS_START: i32    =   0;
 const ADDRESS_END: i32      =   99;
 
-fn log_res( result: ModbusResult, log_var: &mut u32 ) -> ModbusResult
-{
-    match result {
-        Ok(_) => return result,
-        Err(_) => { *log_var = *log_var + 1; return result }
+/// The functions exercised, implemented by both clients
+trait TestClient {
+    fn connect(&self) -> ModbusResult;
+    fn close(&self);
+    fn write_bit(&self, addr: i32, status: i32) -> ModbusResult;
+    fn write_bits(&self, addr: i32, data: &[u8]) -> ModbusResult;
+    fn read_bits(&self, addr: i32, dest: &mut [u8]) -> ModbusResult;
+    fn read_input_bits(&self, addr: i32, dest: &mut [u8]) -> ModbusResult;
+    fn write_register(&self, addr: i32, value: i32) -> ModbusResult;
+    fn write_registers(&self, addr: i32, data: &[u16]) -> ModbusResult;
+    fn read_registers(&self, addr: i32, dest: &mut [u16]) -> ModbusResult;
+    // libmodbus 3.0 does not implement it
+    #[cfg(not(libmodbus_3_0))]
+    fn mask_write_register(&self, addr: i32, and_mask: u16, or_mask: u16) -> ModbusResult;
+    fn write_and_read_registers(&self, write_addr: i32, src: &[u16],
+                                read_addr: i32, dest: &mut [u16]) -> ModbusResult;
+}
+
+macro_rules! impl_test_client {
+    ($client:ty) => {
+        impl TestClient for $client {
+            fn connect(&self) -> ModbusResult { <$client>::connect(self) }
+            fn close(&self) { <$client>::close(self) }
+            fn write_bit(&self, addr: i32, status: i32) -> ModbusResult {
+                <$client>::write_bit(self, addr, status)
+            }
+            fn write_bits(&self, addr: i32, data: &[u8]) -> ModbusResult {
+                <$client>::write_bits(self, addr, data)
+            }
+            fn read_bits(&self, addr: i32, dest: &mut [u8]) -> ModbusResult {
+                <$client>::read_bits(self, addr, dest)
+            }
+            fn read_input_bits(&self, addr: i32, dest: &mut [u8]) -> ModbusResult {
+                <$client>::read_input_bits(self, addr, dest)
+            }
+            fn write_register(&self, addr: i32, value: i32) -> ModbusResult {
+                <$client>::write_register(self, addr, value)
+            }
+            fn write_registers(&self, addr: i32, data: &[u16]) -> ModbusResult {
+                <$client>::write_registers(self, addr, data)
+            }
+            fn read_registers(&self, addr: i32, dest: &mut [u16]) -> ModbusResult {
+                <$client>::read_registers(self, addr, dest)
+            }
+            #[cfg(not(libmodbus_3_0))]
+            fn mask_write_register(&self, addr: i32, and_mask: u16, or_mask: u16) -> ModbusResult {
+                <$client>::mask_write_register(self, addr, and_mask, or_mask)
+            }
+            fn write_and_read_registers(&self, write_addr: i32, src: &[u16],
+                                        read_addr: i32, dest: &mut [u16]) -> ModbusResult {
+                <$client>::write_and_read_registers(self, write_addr, src, read_addr, dest)
+            }
+        }
     }
 }
 
+#[cfg(feature = "libmodbus")]
+impl_test_client!(Modbus);
+impl_test_client!(ModbusClient<modbus::TcpTransport>);
+
 pub fn main() {
 
-    let addr = "127.0.0.1:1502".parse().unwrap();
-    let mb = Modbus::new_tcp(&addr);
+    let backend = std::env::args().nth(1);
+    let addr = std::env::args().nth(2).unwrap_or_else(|| "127.0.0.1:1502".to_string());
+    let addr = addr.parse().unwrap();
 
-    mb.set_debug(true);
+    match backend.as_deref() {
+        #[cfg(feature = "libmodbus")]
+        None | Some("libmodbus") => {
+            let mb = Modbus::new_tcp(&addr);
+            mb.set_debug(true);
+            run(&mb);
+        }
+        #[cfg(not(feature = "libmodbus"))]
+        None => run(&ModbusClient::new_tcp(&addr)),
+        Some("rust") => run(&ModbusClient::new_tcp(&addr)),
+        Some(other) => println!("Unknown client {}, expected libmodbus or rust", other),
+    }
+}
 
-    mb.connect();
+fn run<C: TestClient>(mb: &C) {
+
+    if let Err(e) = mb.connect() {
+        println!("Error: connect");
+        println!("{}", e);
+        return;
+    }
 
 
     let nb = (ADDRESS_END - ADDRESS_START) as usize;
@@ -50,7 +126,7 @@ pub fn main() {
     let mut tab_rp_bits = vec![0u8; nb];
     let mut tab_rp_registers = vec![0u16; nb];
 
-    for nb_loop in 0..LOOP {
+    for _ in 0..LOOP {
         for addr in ADDRESS_START..ADDRESS_END {
 
             let tab_rq_registers = rand::thread_rng()
@@ -108,6 +184,21 @@ pub fn main() {
             }
 
 
+            /* INPUT BITS */
+            match mb.read_input_bits(addr, &mut tab_rp_bits[..]) {
+                Ok(rv) =>
+                    if rv != nb as i32 {
+                        println!("Error: Modbus::read_input_bits mismatch");
+                        nb_fail += 1;
+                    },
+                Err(e) => {
+                    println!("Error: Modbus::read_input_bits");
+                    println!("{}", e);
+                    nb_fail += 1;
+                }
+            }
+
+
             /* SINGLE REGISTER */
             if let Err(e) = mb.write_register(addr, tab_rq_registers[0] as i32) {
                 println!("Error: Modbus::write_register");
@@ -151,6 +242,33 @@ pub fn main() {
                 }
             }
 
+            /* MASK WRITE REGISTER */
+            #[cfg(not(libmodbus_3_0))]
+            {
+                let and_mask = tab_rw_rq_registers[0];
+                let or_mask = tab_rq_registers[1];
+                if let Err(e) = mb.mask_write_register(addr, and_mask, or_mask) {
+                    println!("Error: Modbus::mask_write_register");
+                    println!("{}", e);
+                    nb_fail += 1;
+                }
+                else {
+                    if let Err(e) = mb.read_registers(addr, &mut tab_rp_registers[0..1]) {
+                        println!("Error: Modbus::read_registers");
+                        println!("{}", e);
+                        nb_fail += 1;
+                    }
+                    else {
+                        let expected = (tab_rq_registers[0] & and_mask) | (or_mask & !and_mask);
+                        if tab_rp_registers[0] != expected {
+                            println!("Error: mask_write_register/read_registers: read {}, expected {}",
+                                     tab_rp_registers[0], expected);
+                            nb_fail += 1;
+                        }
+                    }
+                }
+            }
+
             /* R/W MULTIPLE REGISTERS */
             match mb.write_and_read_registers(addr, &tab_rq_registers[..],
                                                         addr, &mut tab_rp_registers[..]) {
@@ -168,8 +286,11 @@ pub fn main() {
                             }
                         }
                     },
-                Err(e) =>
-                    println!("Error: Modbus::write_and_read_registers"),
+                Err(e) => {
+                    println!("Error: Modbus::write_and_read_registers");
+                    println!("{}", e);
+                    nb_fail += 1;
+                }
             }
         }
 
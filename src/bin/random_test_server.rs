@@ -0,0 +1,28 @@
+
+extern crate modbus;
+use modbus::{ModbusServer, ModbusMapping};
+
+/* Counterpart of random_test_client: serves the address range it exercises from a
+   mapping, one client at a time, forever.
+*/
+
+pub fn main() {
+
+    let addr = "127.0.0.1:1502".parse().unwrap();
+    let mut server = ModbusServer::listen(&addr, 1).unwrap();
+    let mut mapping = ModbusMapping::new(500, 500, 500, 500);
+
+    server.modbus().set_debug(true);
+
+    loop {
+        if let Err(e) = server.accept() {
+            println!("Error: ModbusServer::accept");
+            println!("{}", e);
+            continue;
+        }
+        if let Err(e) = server.serve(&mut mapping) {
+            println!("Error: ModbusServer::serve");
+            println!("{}", e);
+        }
+    }
+}
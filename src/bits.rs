@@ -0,0 +1,179 @@
+//! Bit packing helpers for coil and discrete input buffers
+//!
+//! libmodbus stores bits one u8 set to 1 or 0 per bit, while Modbus frames pack eight bits per
+//! byte, the first bit in the least significant position.
+
+use std::ops::{Deref, DerefMut};
+
+#[cfg(feature = "libmodbus")]
+use libc::{c_int, c_uint};
+
+/// A type bits can be read into or written from, u8 set to 1 or 0 or bool
+pub trait Bit: Copy {
+    fn from_u8(value: u8) -> Self;
+    fn to_u8(self) -> u8;
+}
+
+impl Bit for u8 {
+    fn from_u8(value: u8) -> u8
+    {
+        value
+    }
+
+    fn to_u8(self) -> u8
+    {
+        (self != 0) as u8
+    }
+}
+
+impl Bit for bool {
+    fn from_u8(value: u8) -> bool
+    {
+        value != 0
+    }
+
+    fn to_u8(self) -> u8
+    {
+        self as u8
+    }
+}
+
+/// Set the 8 bits of dest starting at idx from the bits of value
+///
+/// Panics if dest holds fewer than idx + 8 bits.
+#[cfg(feature = "libmodbus")]
+pub fn set_bits_from_byte(dest: &mut [u8], idx: usize, value: u8)
+{
+    assert!(idx + 8 <= dest.len());
+    unsafe {
+        modbus_sys::modbus_set_bits_from_byte(dest.as_mut_ptr(), idx as c_int, value);
+    }
+}
+
+/// Set nb_bits bits of dest starting at idx from the packed bytes of src
+///
+/// Panics if dest holds fewer than idx + nb_bits bits or src fewer than nb_bits bits.
+#[cfg(feature = "libmodbus")]
+pub fn set_bits_from_bytes(dest: &mut [u8], idx: usize, nb_bits: usize, src: &[u8])
+{
+    assert!(idx + nb_bits <= dest.len());
+    assert!(nb_bits <= src.len() * 8);
+    unsafe {
+        modbus_sys::modbus_set_bits_from_bytes(dest.as_mut_ptr(), idx as c_int,
+                                               nb_bits as c_uint, src.as_ptr());
+    }
+}
+
+/// Pack nb_bits (at most 8) bits of src starting at idx into a byte
+///
+/// Panics if nb_bits is greater than 8 or src holds fewer than idx + nb_bits bits.
+#[cfg(feature = "libmodbus")]
+pub fn get_byte_from_bits(src: &[u8], idx: usize, nb_bits: usize) -> u8
+{
+    assert!(nb_bits <= 8);
+    assert!(idx + nb_bits <= src.len());
+    unsafe {
+        modbus_sys::modbus_get_byte_from_bits(src.as_ptr(), idx as c_int, nb_bits as c_uint)
+    }
+}
+
+/// Pack bits, eight per byte
+pub fn pack_bits<B: Bit>(bits: &[B]) -> Vec<u8>
+{
+    bits.chunks(8)
+        .map(|chunk| chunk.iter().enumerate()
+                          .fold(0u8, |byte, (i, bit)| byte | (bit.to_u8() << i)))
+        .collect()
+}
+
+/// Unpack the first nb bits of bytes
+///
+/// Panics if bytes holds fewer than nb bits.
+pub fn unpack_bits<B: Bit>(bytes: &[u8], nb: usize) -> Vec<B>
+{
+    assert!(nb <= bytes.len() * 8);
+    (0..nb).map(|i| B::from_u8((bytes[i / 8] >> (i % 8)) & 1)).collect()
+}
+
+/// A sequence of coils or discrete inputs
+///
+/// It dereferences to `[bool]`, so it can be passed to `Modbus::read_bits` and
+/// `Modbus::write_bits` directly.
+///
+/// # Example
+///
+/// ```
+/// use modbus::Coils;
+///
+/// let mut coils = Coils::new(10);
+/// coils[0] = true;
+/// coils[9] = true;
+/// assert_eq!(coils.to_packed(), vec![0x01, 0x02]);
+/// assert_eq!(Coils::from_packed(&[0x01, 0x02], 10), coils);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Coils {
+    bits: Vec<bool>,
+}
+
+impl Coils {
+    /// Create nb coils, all off
+    pub fn new(nb: usize) -> Coils
+    {
+        Coils { bits: vec![false; nb] }
+    }
+
+    /// Create coils from the first nb bits of packed bytes
+    pub fn from_packed(bytes: &[u8], nb: usize) -> Coils
+    {
+        Coils { bits: unpack_bits(bytes, nb) }
+    }
+
+    /// Create coils from one u8 set to 1 or 0 per bit, the libmodbus format
+    pub fn from_bits(bits: &[u8]) -> Coils
+    {
+        Coils { bits: bits.iter().map(|&b| b != 0).collect() }
+    }
+
+    /// Pack the coils, eight per byte
+    pub fn to_packed(&self) -> Vec<u8>
+    {
+        pack_bits(&self.bits)
+    }
+
+    /// One u8 set to 1 or 0 per coil, the libmodbus format
+    pub fn to_bits(&self) -> Vec<u8>
+    {
+        self.bits.iter().map(|&b| b as u8).collect()
+    }
+}
+
+impl Deref for Coils {
+    type Target = [bool];
+
+    fn deref(&self) -> &[bool]
+    {
+        &self.bits
+    }
+}
+
+impl DerefMut for Coils {
+    fn deref_mut(&mut self) -> &mut [bool]
+    {
+        &mut self.bits
+    }
+}
+
+impl From<Vec<bool>> for Coils {
+    fn from(bits: Vec<bool>) -> Coils
+    {
+        Coils { bits }
+    }
+}
+
+impl From<Coils> for Vec<bool> {
+    fn from(coils: Coils) -> Vec<bool>
+    {
+        coils.bits
+    }
+}
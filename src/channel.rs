@@ -0,0 +1,107 @@
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+use crate::ModbusError;
+use crate::client::io_error;
+use crate::transport::{Adu, Transport};
+
+/// One end of an in-memory link, moving ADUs between threads without framing them
+///
+/// Useful to test request logic, e.g. a `ModbusClient` against a `TransportServer` and its
+/// `RequestHandler`, without sockets or serial ports. Frames keep their transaction identifier,
+/// like over TCP. Once one end is closed or dropped, receiving on the other fails with
+/// `ECONNRESET`.
+///
+/// # Example
+///
+/// ```
+/// use std::thread;
+/// use modbus::{ChannelTransport, ModbusClient, TransportServer, RequestHandler, Exception};
+///
+/// struct Constant;
+///
+/// impl RequestHandler for Constant {
+///     fn read_input_registers(&mut self, _addr: u16, dest: &mut [u16]) -> Result<(), Exception>
+///     {
+///         for r in dest.iter_mut() {
+///             *r = 42;
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let (client, server) = ChannelTransport::pair();
+/// let server = thread::spawn(move || TransportServer::new(server).serve(&mut Constant));
+///
+/// let mb = ModbusClient::new(client);
+/// mb.set_slave(1).unwrap();
+/// let mut dest = [0u16; 2];
+/// mb.read_input_registers(0, &mut dest).unwrap();
+/// assert_eq!(dest, [42, 42]);
+///
+/// mb.close();
+/// server.join().unwrap().unwrap();
+/// ```
+pub struct ChannelTransport {
+    tx: Option<Sender<Adu>>,
+    rx: Receiver<Adu>,
+}
+
+impl ChannelTransport {
+    /// The two ends of a new link
+    pub fn pair() -> (ChannelTransport, ChannelTransport)
+    {
+        let (tx_a, rx_b) = mpsc::channel();
+        let (tx_b, rx_a) = mpsc::channel();
+        (ChannelTransport { tx: Some(tx_a), rx: rx_a },
+         ChannelTransport { tx: Some(tx_b), rx: rx_b })
+    }
+}
+
+impl Transport for ChannelTransport {
+    /// The link is connected from its creation, this only checks it was not closed
+    fn connect(&mut self) -> Result<(), ModbusError>
+    {
+        if self.tx.is_none() {
+            return Err(io_error(libc::ECONNRESET))
+        }
+        Ok(())
+    }
+
+    fn close(&mut self)
+    {
+        self.tx = None;
+    }
+
+    /// The slave address is the only header of the frames
+    fn header_length(&self) -> usize
+    {
+        1
+    }
+
+    fn has_transaction_id(&self) -> bool
+    {
+        true
+    }
+
+    fn send(&mut self, adu: &Adu) -> Result<(), ModbusError>
+    {
+        let tx = self.tx.as_ref().ok_or_else(|| io_error(libc::EBADF))?;
+        tx.send(adu.clone()).map_err(|_| io_error(libc::ECONNRESET))
+    }
+
+    fn receive(&mut self, timeout: Option<Duration>) -> Result<Adu, ModbusError>
+    {
+        if self.tx.is_none() {
+            return Err(io_error(libc::EBADF))
+        }
+        let adu = match timeout {
+            Some(timeout) => self.rx.recv_timeout(timeout).map_err(|e| match e {
+                RecvTimeoutError::Timeout => io_error(libc::ETIMEDOUT),
+                RecvTimeoutError::Disconnected => io_error(libc::ECONNRESET),
+            })?,
+            None => self.rx.recv().map_err(|_| io_error(libc::ECONNRESET))?,
+        };
+        Ok(adu)
+    }
+}
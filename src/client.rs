@@ -0,0 +1,429 @@
+use std::cell::{Cell, RefCell, RefMut};
+use std::io;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use libc::c_int;
+
+use crate::{ModbusError, ModbusResult, ServerId, Bit, MODBUS_BROADCAST_ADDRESS, MODBUS_TCP_SLAVE};
+use crate::codec::{self, Request, Response};
+use crate::tcp::TcpTransport;
+use crate::transport::{Adu, Transport};
+
+/// Function code of report server ID
+pub const REPORT_SERVER_ID: u8 = 0x11;
+
+/// A Modbus client written in Rust, sending its requests over any `Transport`
+///
+/// The methods mirror the ones of `Modbus` and return the same errors, without linking against
+/// libmodbus. Responses are matched with their request by transaction identifier on transports
+/// which have one, late responses to previous requests being skipped.
+///
+/// # Example
+///
+/// ```no_run
+/// use modbus::ModbusClient;
+///
+/// let addr = "192.168.1.10:502".parse().unwrap();
+/// let mb = ModbusClient::new_tcp(&addr);
+/// mb.connect().unwrap();
+///
+/// let mut dest = [0u16; 4];
+/// mb.read_registers(0, &mut dest).unwrap();
+/// ```
+pub struct ModbusClient<T: Transport> {
+    transport: RefCell<T>,
+    slave: Cell<Option<u8>>,
+    transaction_id: Cell<u16>,
+    response_timeout: Cell<Duration>,
+}
+
+impl ModbusClient<TcpTransport> {
+    /// Create a new Modbus TCP client, see `Modbus::new_tcp`
+    ///
+    /// Requests are sent to the unit identifier `MODBUS_TCP_SLAVE` (255) by default.
+    pub fn new_tcp(addr: &SocketAddr) -> ModbusClient<TcpTransport>
+    {
+        let client = ModbusClient::new(TcpTransport::new(addr));
+        client.slave.set(Some(MODBUS_TCP_SLAVE as u8));
+        client
+    }
+}
+
+impl<T: Transport> ModbusClient<T> {
+    /// Create a client sending its requests over transport
+    pub fn new(transport: T) -> ModbusClient<T>
+    {
+        ModbusClient {
+            transport: RefCell::new(transport),
+            slave: Cell::new(None),
+            transaction_id: Cell::new(0),
+            response_timeout: Cell::new(Duration::from_millis(500)),
+        }
+    }
+
+    /// Returns the transport, e.g. to change its settings
+    pub fn transport<'a>(&'a self) -> RefMut<'a, T>
+    {
+        self.transport.borrow_mut()
+    }
+
+    /// Open the link
+    pub fn connect(&self) -> ModbusResult
+    {
+        self.transport.borrow_mut().connect()?;
+        Ok(0)
+    }
+
+    /// Close the link
+    pub fn close(&self)
+    {
+        self.transport.borrow_mut().close()
+    }
+
+    /// Set the slave the requests are addressed to, see `Modbus::set_slave`
+    ///
+    /// The slave must be between 1 and 247, `MODBUS_TCP_SLAVE` (255) or
+    /// `MODBUS_BROADCAST_ADDRESS` (0).
+    pub fn set_slave(&self, slave: c_int) -> ModbusResult
+    {
+        if slave < 0 || (slave > 247 && slave != MODBUS_TCP_SLAVE) {
+            return Err(io_error(libc::EINVAL))
+        }
+        self.slave.set(Some(slave as u8));
+        Ok(0)
+    }
+
+    /// Get the slave the requests are addressed to, None if it was never set
+    pub fn slave(&self) -> Option<c_int>
+    {
+        self.slave.get().map(|slave| slave as c_int)
+    }
+
+    /// Get the length of the header of the ADUs, see `Modbus::get_header_length`
+    pub fn get_header_length(&self) -> ModbusResult
+    {
+        Ok(self.transport.borrow().header_length() as i32)
+    }
+
+    /// Get the timeout to wait for a response
+    pub fn get_response_timeout(&self) -> Result<Duration, ModbusError>
+    {
+        Ok(self.response_timeout.get())
+    }
+
+    /// Set the timeout to wait for a response, 500 ms by default
+    ///
+    /// The timeout must not be zero.
+    pub fn set_response_timeout(&self, timeout: Duration) -> ModbusResult
+    {
+        if timeout == Duration::from_secs(0) {
+            return Err(io_error(libc::EINVAL))
+        }
+        self.response_timeout.set(timeout);
+        Ok(0)
+    }
+
+    fn send(&self, slave: u8, pdu: &[u8]) -> Result<u16, ModbusError>
+    {
+        let transaction_id = self.transaction_id.get().wrapping_add(1);
+        self.transaction_id.set(transaction_id);
+        let adu = Adu { slave, transaction_id, pdu: pdu.to_vec() };
+        self.transport.borrow_mut().send(&adu)?;
+        Ok(transaction_id)
+    }
+
+    /// Receive the response to the request sent with transaction_id
+    fn receive(&self, transaction_id: u16) -> Result<Adu, ModbusError>
+    {
+        let mut transport = self.transport.borrow_mut();
+        let deadline = Instant::now() + self.response_timeout.get();
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(io_error(libc::ETIMEDOUT))
+            }
+            let adu = transport.receive(Some(deadline - now))?;
+            if !transport.has_transaction_id() || adu.transaction_id == transaction_id {
+                return Ok(adu)
+            }
+        }
+    }
+
+    /// The slave of a request expecting a response
+    fn unicast_slave(&self) -> Result<u8, ModbusError>
+    {
+        match self.slave.get() {
+            None => Err(io_error(libc::EINVAL)),
+            Some(slave) if slave as c_int == MODBUS_BROADCAST_ADDRESS => {
+                Err(io_error(libc::EINVAL))
+            }
+            Some(slave) => Ok(slave),
+        }
+    }
+
+    /// Send a request and return the PDU of the response from the same slave
+    fn transaction(&self, pdu: &[u8]) -> Result<Vec<u8>, ModbusError>
+    {
+        let slave = self.unicast_slave()?;
+        let transaction_id = self.send(slave, pdu)?;
+        let adu = self.receive(transaction_id)?;
+        if adu.slave != slave {
+            return Err(ModbusError::BadSlave)
+        }
+        Ok(adu.pdu)
+    }
+
+    fn call(&self, req: Request) -> Result<Response, ModbusError>
+    {
+        let pdu = self.transaction(&req.encode()?)?;
+        check_response(&req, &pdu)
+    }
+
+    /// Send a write request, which is not answered when broadcast
+    fn write(&self, req: Request, nb: usize) -> ModbusResult
+    {
+        match self.slave.get() {
+            Some(slave) if slave as c_int == MODBUS_BROADCAST_ADDRESS => {
+                self.send(slave, &req.encode()?)?;
+            }
+            _ => {
+                self.call(req)?;
+            }
+        }
+        Ok(nb as i32)
+    }
+
+    /// Read many bits, see `Modbus::read_bits`
+    pub fn read_bits<B: Bit>(&self, addr: c_int, dest: &mut [B]) -> ModbusResult
+    {
+        let req = Request::ReadCoils { addr: address(addr)?, nb: quantity(dest.len())? };
+        bits_into(self.call(req)?, dest)
+    }
+
+    /// Read many input bits, see `Modbus::read_input_bits`
+    pub fn read_input_bits<B: Bit>(&self, addr: c_int, dest: &mut [B]) -> ModbusResult
+    {
+        let req = Request::ReadDiscreteInputs { addr: address(addr)?, nb: quantity(dest.len())? };
+        bits_into(self.call(req)?, dest)
+    }
+
+    /// Read many registers, see `Modbus::read_registers`
+    pub fn read_registers(&self, addr: c_int, dest: &mut [u16]) -> ModbusResult
+    {
+        let req = Request::ReadHoldingRegisters { addr: address(addr)?, nb: quantity(dest.len())? };
+        registers_into(self.call(req)?, dest)
+    }
+
+    /// Read many input registers, see `Modbus::read_input_registers`
+    pub fn read_input_registers(&self, addr: c_int, dest: &mut [u16]) -> ModbusResult
+    {
+        let req = Request::ReadInputRegisters { addr: address(addr)?, nb: quantity(dest.len())? };
+        registers_into(self.call(req)?, dest)
+    }
+
+    /// Write a single bit, see `Modbus::write_bit`
+    pub fn write_bit(&self, coil_addr: c_int, status: c_int) -> ModbusResult
+    {
+        let req = Request::WriteSingleCoil { addr: address(coil_addr)?, value: status != 0 };
+        self.write(req, 1)
+    }
+
+    /// Write many bits, see `Modbus::write_bits`
+    pub fn write_bits<B: Bit>(&self, addr: c_int, data: &[B]) -> ModbusResult
+    {
+        let values = data.iter().map(|b| b.to_u8() != 0).collect();
+        let req = Request::WriteMultipleCoils { addr: address(addr)?, values };
+        self.write(req, data.len())
+    }
+
+    /// Write a single register, see `Modbus::write_register`
+    pub fn write_register(&self, reg_addr: c_int, value: c_int) -> ModbusResult
+    {
+        let req = Request::WriteSingleRegister { addr: address(reg_addr)?, value: value as u16 };
+        self.write(req, 1)
+    }
+
+    /// Write many registers, see `Modbus::write_registers`
+    pub fn write_registers(&self, addr: c_int, data: &[u16]) -> ModbusResult
+    {
+        let req = Request::WriteMultipleRegisters { addr: address(addr)?, values: data.to_vec() };
+        self.write(req, data.len())
+    }
+
+    /// Modify a single register using masks, see `Modbus::mask_write_register`
+    pub fn mask_write_register(&self, addr: c_int, and_mask: u16, or_mask: u16) -> ModbusResult
+    {
+        let req = Request::MaskWriteRegister {
+            addr: address(addr)?,
+            and_mask,
+            or_mask,
+        };
+        self.write(req, 1)
+    }
+
+    /// Write and read many registers in a single transaction, see
+    /// `Modbus::write_and_read_registers`
+    pub fn write_and_read_registers(&self, write_addr: c_int, src: &[u16],
+                                    read_addr: c_int, dest: &mut [u16]) -> ModbusResult
+    {
+        let req = Request::ReadWriteMultipleRegisters {
+            read_addr: address(read_addr)?,
+            read_nb: quantity(dest.len())?,
+            write_addr: address(write_addr)?,
+            values: src.to_vec(),
+        };
+        registers_into(self.call(req)?, dest)
+    }
+
+    /// Report server ID, see `Modbus::report_server_id`
+    pub fn report_server_id(&self) -> Result<ServerId, ModbusError>
+    {
+        let pdu = self.transaction(&[REPORT_SERVER_ID])?;
+        server_id(&pdu)
+    }
+
+    /// Send a raw request, the slave address followed by the PDU, see
+    /// `Modbus::send_raw_request`
+    ///
+    /// Returns the length of the frame sent, with its header and checksum.
+    pub fn send_raw_request(&self, raw_req: &[u8]) -> ModbusResult
+    {
+        if raw_req.len() < 2 || raw_req.len() > codec::MAX_PDU_LENGTH + 1 {
+            return Err(io_error(libc::EINVAL))
+        }
+        let (slave, pdu) = (raw_req[0], &raw_req[1..]);
+        let transaction_id = self.send(slave, pdu)?;
+        let adu = Adu { slave, transaction_id, pdu: pdu.to_vec() };
+        Ok(self.transport.borrow().frame_length(&adu) as i32)
+    }
+
+    /// Receive the response to a request sent with `ModbusClient::send_raw_request`
+    ///
+    /// The response is returned with its header, e.g. the MBAP header of Modbus TCP, but without
+    /// a checksum. As with `Modbus::receive_confirmation`, the PDU starts at the offset given by
+    /// `ModbusClient::get_header_length`.
+    pub fn receive_confirmation(&self) -> Result<Vec<u8>, ModbusError>
+    {
+        let adu = self.receive(self.transaction_id.get())?;
+        let mut rsp = self.transport.borrow().encode_header(&adu);
+        rsp.extend_from_slice(&adu.pdu);
+        Ok(rsp)
+    }
+}
+
+pub fn io_error(errnum: c_int) -> ModbusError
+{
+    ModbusError::Io(io::Error::from_raw_os_error(errnum))
+}
+
+pub fn address(addr: c_int) -> Result<u16, ModbusError>
+{
+    if addr < 0 || addr > u16::MAX as c_int {
+        Err(io_error(libc::EINVAL))
+    } else {
+        Ok(addr as u16)
+    }
+}
+
+pub fn quantity(nb: usize) -> Result<u16, ModbusError>
+{
+    if nb > u16::MAX as usize {
+        Err(ModbusError::TooManyData)
+    } else {
+        Ok(nb as u16)
+    }
+}
+
+/// Decode the response PDU to a request, an exception being returned as an error
+///
+/// The response to a write must echo its address and quantity, or its value, otherwise
+/// `ModbusError::BadData` is returned.
+pub fn check_response(req: &Request, pdu: &[u8]) -> Result<Response, ModbusError>
+{
+    let rsp = match Response::decode(pdu)? {
+        Response::Exception { exception, .. } => return Err(ModbusError::Exception(exception)),
+        ref rsp if rsp.function_code() != req.function_code() => return Err(ModbusError::BadData),
+        rsp => rsp,
+    };
+    let echoed = match (req, &rsp) {
+        (&Request::WriteSingleCoil { addr, value },
+         &Response::WriteSingleCoil { addr: a, value: v }) => (a, v) == (addr, value),
+        (&Request::WriteSingleRegister { addr, value },
+         &Response::WriteSingleRegister { addr: a, value: v }) => (a, v) == (addr, value),
+        (&Request::WriteMultipleCoils { addr, ref values },
+         &Response::WriteMultipleCoils { addr: a, nb }) => {
+            (a, nb as usize) == (addr, values.len())
+        }
+        (&Request::WriteMultipleRegisters { addr, ref values },
+         &Response::WriteMultipleRegisters { addr: a, nb }) => {
+            (a, nb as usize) == (addr, values.len())
+        }
+        (&Request::MaskWriteRegister { addr, and_mask, or_mask },
+         &Response::MaskWriteRegister { addr: a, and_mask: and, or_mask: or }) => {
+            (a, and, or) == (addr, and_mask, or_mask)
+        }
+        _ => true,
+    };
+    if echoed {
+        Ok(rsp)
+    } else {
+        Err(ModbusError::BadData)
+    }
+}
+
+/// Copy the bits of a read coils or discrete inputs response
+pub fn bits_into<B: Bit>(rsp: Response, dest: &mut [B]) -> ModbusResult
+{
+    let bits = match rsp {
+        Response::ReadCoils(bits) | Response::ReadDiscreteInputs(bits) => bits,
+        _ => return Err(ModbusError::BadData),
+    };
+    if bits.len() != dest.len().div_ceil(8) * 8 {
+        return Err(ModbusError::BadData)
+    }
+    for (d, &b) in dest.iter_mut().zip(bits.iter()) {
+        *d = B::from_u8(b as u8);
+    }
+    Ok(dest.len() as i32)
+}
+
+/// Decode a report server ID response PDU
+pub fn server_id(pdu: &[u8]) -> Result<ServerId, ModbusError>
+{
+    match pdu.first() {
+        Some(&REPORT_SERVER_ID) => {}
+        Some(&function) if function == REPORT_SERVER_ID | 0x80 => {
+            return match Response::decode(pdu)? {
+                Response::Exception { exception, .. } => Err(ModbusError::Exception(exception)),
+                _ => Err(ModbusError::BadData),
+            }
+        }
+        _ => return Err(ModbusError::BadData),
+    }
+    if pdu.len() < 4 || pdu[1] as usize != pdu.len() - 2 {
+        return Err(ModbusError::BadData)
+    }
+
+    Ok(ServerId {
+        server_id: pdu[2],
+        running: pdu[3] == 0xFF,
+        data: pdu[4..].to_vec(),
+    })
+}
+
+/// Copy the registers of a read registers response
+pub fn registers_into(rsp: Response, dest: &mut [u16]) -> ModbusResult
+{
+    let values = match rsp {
+        Response::ReadHoldingRegisters(values) |
+        Response::ReadInputRegisters(values) |
+        Response::ReadWriteMultipleRegisters(values) => values,
+        _ => return Err(ModbusError::BadData),
+    };
+    if values.len() != dest.len() {
+        return Err(ModbusError::BadData)
+    }
+    dest.copy_from_slice(&values);
+    Ok(dest.len() as i32)
+}
@@ -0,0 +1,772 @@
+//! Encoding and decoding of Modbus frames, independently of libmodbus
+//!
+//! A request or response is a PDU: a function code followed by its data. `Request` and
+//! `Response` encode and decode the PDUs of the function codes below, exceptions included:
+//!
+//! | Code | Function                                          |
+//! |------|---------------------------------------------------|
+//! | 0x01 | Read coils                                        |
+//! | 0x02 | Read discrete inputs                              |
+//! | 0x03 | Read holding registers                            |
+//! | 0x04 | Read input registers                              |
+//! | 0x05 | Write single coil                                 |
+//! | 0x06 | Write single register                             |
+//! | 0x0F | Write multiple coils                              |
+//! | 0x10 | Write multiple registers                          |
+//! | 0x16 | Mask write register                               |
+//! | 0x17 | Read/write multiple registers                     |
+//! | 0x2B | Read device identification (MEI type 0x0E)        |
+//!
+//! The PDU is then framed into an ADU: `encode_tcp` prepends the MBAP header of Modbus TCP,
+//! `encode_rtu` the slave address and appends the CRC-16 of Modbus RTU, and `encode_ascii` writes
+//! the slave address, PDU and LRC of Modbus ASCII as hexadecimal between ':' and CR LF.
+//!
+//! Malformed frames are reported as `ModbusError::BadData`, invalid CRCs and LRCs as
+//! `ModbusError::BadCrc`, unknown exception codes as `ModbusError::BadException` and quantities
+//! beyond the limits of the protocol as `ModbusError::TooManyData`, as libmodbus does.
+//!
+//! # Example
+//!
+//! ```
+//! use modbus::codec::{self, Request};
+//!
+//! let req = Request::ReadHoldingRegisters { addr: 0x10, nb: 2 };
+//! let adu = codec::encode_tcp(1, 0xFF, &req.encode().unwrap());
+//! assert_eq!(adu, [0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0xFF, 0x03, 0x00, 0x10, 0x00, 0x02]);
+//!
+//! let frame = codec::decode_tcp(&adu).unwrap();
+//! assert_eq!(frame.transaction_id, 1);
+//! assert_eq!(Request::decode(frame.pdu).unwrap(), req);
+//! ```
+
+use crate::{ModbusError, Exception};
+use crate::bits::{pack_bits, unpack_bits};
+
+/// Length of the MBAP header of a Modbus TCP frame, unit identifier included
+pub const MBAP_HEADER_LENGTH: usize = 7;
+
+/// Maximum length of a PDU
+pub const MAX_PDU_LENGTH: usize = 253;
+
+/// Maximum length of a Modbus RTU frame: the slave address, PDU and CRC
+pub const RTU_MAX_ADU_LENGTH: usize = 256;
+
+// Quantity limits of the protocol, as in modbus.h
+const MAX_READ_BITS: i32 = 2000;
+const MAX_WRITE_BITS: i32 = 1968;
+const MAX_READ_REGISTERS: i32 = 125;
+const MAX_WRITE_REGISTERS: i32 = 123;
+const MAX_WR_WRITE_REGISTERS: i32 = 121;
+const MAX_WR_READ_REGISTERS: i32 = 125;
+
+/// Maximum length of a Modbus ASCII frame: ':', the slave address, PDU and LRC in hexadecimal,
+/// then CR LF
+pub const ASCII_MAX_ADU_LENGTH: usize = 1 + 2 * (MAX_PDU_LENGTH + 2) + 2;
+
+/// Modbus Encapsulated Interface type of read device identification
+pub const MEI_READ_DEVICE_ID: u8 = 0x0E;
+
+/// A request PDU
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    /// Read nb coils at addr (0x01)
+    ReadCoils { addr: u16, nb: u16 },
+    /// Read nb discrete inputs at addr (0x02)
+    ReadDiscreteInputs { addr: u16, nb: u16 },
+    /// Read nb holding registers at addr (0x03)
+    ReadHoldingRegisters { addr: u16, nb: u16 },
+    /// Read nb input registers at addr (0x04)
+    ReadInputRegisters { addr: u16, nb: u16 },
+    /// Write a single coil (0x05)
+    WriteSingleCoil { addr: u16, value: bool },
+    /// Write a single holding register (0x06)
+    WriteSingleRegister { addr: u16, value: u16 },
+    /// Write coils starting at addr (0x0F)
+    WriteMultipleCoils { addr: u16, values: Vec<bool> },
+    /// Write holding registers starting at addr (0x10)
+    WriteMultipleRegisters { addr: u16, values: Vec<u16> },
+    /// Set the register at addr to `(value & and_mask) | (or_mask & !and_mask)` (0x16)
+    MaskWriteRegister { addr: u16, and_mask: u16, or_mask: u16 },
+    /// Write values at write_addr then read read_nb holding registers at read_addr (0x17)
+    ReadWriteMultipleRegisters { read_addr: u16, read_nb: u16, write_addr: u16, values: Vec<u16> },
+    /// Read the identification objects of a device (0x2B / 0x0E). read_code is 1 (basic), 2
+    /// (regular), 3 (extended) or 4 (one specific object).
+    ReadDeviceIdentification { read_code: u8, object_id: u8 },
+}
+
+/// A response PDU
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    /// Coils read, padded with zeros to a multiple of 8 (0x01)
+    ReadCoils(Vec<bool>),
+    /// Discrete inputs read, padded with zeros to a multiple of 8 (0x02)
+    ReadDiscreteInputs(Vec<bool>),
+    /// Holding registers read (0x03)
+    ReadHoldingRegisters(Vec<u16>),
+    /// Input registers read (0x04)
+    ReadInputRegisters(Vec<u16>),
+    /// Echo of a write single coil request (0x05)
+    WriteSingleCoil { addr: u16, value: bool },
+    /// Echo of a write single register request (0x06)
+    WriteSingleRegister { addr: u16, value: u16 },
+    /// nb coils written at addr (0x0F)
+    WriteMultipleCoils { addr: u16, nb: u16 },
+    /// nb holding registers written at addr (0x10)
+    WriteMultipleRegisters { addr: u16, nb: u16 },
+    /// Echo of a mask write register request (0x16)
+    MaskWriteRegister { addr: u16, and_mask: u16, or_mask: u16 },
+    /// Holding registers read (0x17)
+    ReadWriteMultipleRegisters(Vec<u16>),
+    /// Identification objects (0x2B / 0x0E)
+    ReadDeviceIdentification(DeviceIdentification),
+    /// Exception reply to a request with the given function code
+    Exception { function: u8, exception: Exception },
+}
+
+/// Response to a read device identification request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceIdentification {
+    pub read_code: u8,
+    pub conformity_level: u8,
+    /// The objects did not fit in one response, ask again starting at next_object_id
+    pub more_follows: bool,
+    pub next_object_id: u8,
+    /// Object ids and values, e.g. 0 for the vendor name, 1 for the product code and 2 for the
+    /// revision
+    pub objects: Vec<(u8, Vec<u8>)>,
+}
+
+fn be16(pdu: &[u8], i: usize) -> u16
+{
+    (pdu[i] as u16) << 8 | pdu[i + 1] as u16
+}
+
+fn push16(pdu: &mut Vec<u8>, value: u16)
+{
+    pdu.push((value >> 8) as u8);
+    pdu.push(value as u8);
+}
+
+fn push_registers(pdu: &mut Vec<u8>, values: &[u16])
+{
+    for &value in values {
+        push16(pdu, value);
+    }
+}
+
+fn registers(data: &[u8]) -> Vec<u16>
+{
+    (0..data.len() / 2).map(|i| be16(data, 2 * i)).collect()
+}
+
+fn check_len(pdu: &[u8], len: usize) -> Result<(), ModbusError>
+{
+    if pdu.len() == len { Ok(()) } else { Err(ModbusError::BadData) }
+}
+
+fn check_min_len(pdu: &[u8], len: usize) -> Result<(), ModbusError>
+{
+    if pdu.len() >= len { Ok(()) } else { Err(ModbusError::BadData) }
+}
+
+fn check_quantity(nb: usize, max: i32) -> Result<(), ModbusError>
+{
+    if nb < 1 {
+        Err(ModbusError::BadData)
+    } else if nb > max as usize {
+        Err(ModbusError::TooManyData)
+    } else {
+        Ok(())
+    }
+}
+
+fn coil_value(value: u16) -> Result<bool, ModbusError>
+{
+    match value {
+        0xFF00 => Ok(true),
+        0x0000 => Ok(false),
+        _ => Err(ModbusError::BadData),
+    }
+}
+
+fn coil_word(value: bool) -> u16
+{
+    if value { 0xFF00 } else { 0x0000 }
+}
+
+impl Request {
+    /// The function code of the request
+    pub fn function_code(&self) -> u8
+    {
+        match *self {
+            Request::ReadCoils { .. } => 0x01,
+            Request::ReadDiscreteInputs { .. } => 0x02,
+            Request::ReadHoldingRegisters { .. } => 0x03,
+            Request::ReadInputRegisters { .. } => 0x04,
+            Request::WriteSingleCoil { .. } => 0x05,
+            Request::WriteSingleRegister { .. } => 0x06,
+            Request::WriteMultipleCoils { .. } => 0x0F,
+            Request::WriteMultipleRegisters { .. } => 0x10,
+            Request::MaskWriteRegister { .. } => 0x16,
+            Request::ReadWriteMultipleRegisters { .. } => 0x17,
+            Request::ReadDeviceIdentification { .. } => 0x2B,
+        }
+    }
+
+    /// Encode the request PDU
+    pub fn encode(&self) -> Result<Vec<u8>, ModbusError>
+    {
+        let mut pdu = vec![self.function_code()];
+        match *self {
+            Request::ReadCoils { addr, nb } | Request::ReadDiscreteInputs { addr, nb } => {
+                check_quantity(nb as usize, MAX_READ_BITS)?;
+                push16(&mut pdu, addr);
+                push16(&mut pdu, nb);
+            }
+            Request::ReadHoldingRegisters { addr, nb } |
+            Request::ReadInputRegisters { addr, nb } => {
+                check_quantity(nb as usize, MAX_READ_REGISTERS)?;
+                push16(&mut pdu, addr);
+                push16(&mut pdu, nb);
+            }
+            Request::WriteSingleCoil { addr, value } => {
+                push16(&mut pdu, addr);
+                push16(&mut pdu, coil_word(value));
+            }
+            Request::WriteSingleRegister { addr, value } => {
+                push16(&mut pdu, addr);
+                push16(&mut pdu, value);
+            }
+            Request::WriteMultipleCoils { addr, ref values } => {
+                check_quantity(values.len(), MAX_WRITE_BITS)?;
+                let packed = pack_bits(values);
+                push16(&mut pdu, addr);
+                push16(&mut pdu, values.len() as u16);
+                pdu.push(packed.len() as u8);
+                pdu.extend_from_slice(&packed);
+            }
+            Request::WriteMultipleRegisters { addr, ref values } => {
+                check_quantity(values.len(), MAX_WRITE_REGISTERS)?;
+                push16(&mut pdu, addr);
+                push16(&mut pdu, values.len() as u16);
+                pdu.push((values.len() * 2) as u8);
+                push_registers(&mut pdu, values);
+            }
+            Request::MaskWriteRegister { addr, and_mask, or_mask } => {
+                push16(&mut pdu, addr);
+                push16(&mut pdu, and_mask);
+                push16(&mut pdu, or_mask);
+            }
+            Request::ReadWriteMultipleRegisters { read_addr, read_nb, write_addr, ref values } => {
+                check_quantity(read_nb as usize, MAX_WR_READ_REGISTERS)?;
+                check_quantity(values.len(), MAX_WR_WRITE_REGISTERS)?;
+                push16(&mut pdu, read_addr);
+                push16(&mut pdu, read_nb);
+                push16(&mut pdu, write_addr);
+                push16(&mut pdu, values.len() as u16);
+                pdu.push((values.len() * 2) as u8);
+                push_registers(&mut pdu, values);
+            }
+            Request::ReadDeviceIdentification { read_code, object_id } => {
+                pdu.push(MEI_READ_DEVICE_ID);
+                pdu.push(read_code);
+                pdu.push(object_id);
+            }
+        }
+        Ok(pdu)
+    }
+
+    /// Decode a request PDU
+    pub fn decode(pdu: &[u8]) -> Result<Request, ModbusError>
+    {
+        check_min_len(pdu, 1)?;
+        match pdu[0] {
+            0x01 | 0x02 => {
+                check_len(pdu, 5)?;
+                let (addr, nb) = (be16(pdu, 1), be16(pdu, 3));
+                check_quantity(nb as usize, MAX_READ_BITS)?;
+                if pdu[0] == 0x01 {
+                    Ok(Request::ReadCoils { addr, nb })
+                } else {
+                    Ok(Request::ReadDiscreteInputs { addr, nb })
+                }
+            }
+            0x03 | 0x04 => {
+                check_len(pdu, 5)?;
+                let (addr, nb) = (be16(pdu, 1), be16(pdu, 3));
+                check_quantity(nb as usize, MAX_READ_REGISTERS)?;
+                if pdu[0] == 0x03 {
+                    Ok(Request::ReadHoldingRegisters { addr, nb })
+                } else {
+                    Ok(Request::ReadInputRegisters { addr, nb })
+                }
+            }
+            0x05 => {
+                check_len(pdu, 5)?;
+                let value = coil_value(be16(pdu, 3))?;
+                Ok(Request::WriteSingleCoil { addr: be16(pdu, 1), value })
+            }
+            0x06 => {
+                check_len(pdu, 5)?;
+                Ok(Request::WriteSingleRegister { addr: be16(pdu, 1), value: be16(pdu, 3) })
+            }
+            0x0F => {
+                check_min_len(pdu, 6)?;
+                let (addr, nb, count) = (be16(pdu, 1), be16(pdu, 3), pdu[5] as usize);
+                check_quantity(nb as usize, MAX_WRITE_BITS)?;
+                if count != (nb as usize).div_ceil(8) {
+                    return Err(ModbusError::BadData)
+                }
+                check_len(pdu, 6 + count)?;
+                let values = unpack_bits(&pdu[6..], nb as usize);
+                Ok(Request::WriteMultipleCoils { addr, values })
+            }
+            0x10 => {
+                check_min_len(pdu, 6)?;
+                let (addr, nb, count) = (be16(pdu, 1), be16(pdu, 3), pdu[5] as usize);
+                check_quantity(nb as usize, MAX_WRITE_REGISTERS)?;
+                if count != nb as usize * 2 {
+                    return Err(ModbusError::BadData)
+                }
+                check_len(pdu, 6 + count)?;
+                Ok(Request::WriteMultipleRegisters { addr, values: registers(&pdu[6..]) })
+            }
+            0x16 => {
+                check_len(pdu, 7)?;
+                Ok(Request::MaskWriteRegister {
+                    addr: be16(pdu, 1),
+                    and_mask: be16(pdu, 3),
+                    or_mask: be16(pdu, 5),
+                })
+            }
+            0x17 => {
+                check_min_len(pdu, 10)?;
+                let (read_addr, read_nb) = (be16(pdu, 1), be16(pdu, 3));
+                let (write_addr, write_nb, count) = (be16(pdu, 5), be16(pdu, 7), pdu[9] as usize);
+                check_quantity(read_nb as usize, MAX_WR_READ_REGISTERS)?;
+                check_quantity(write_nb as usize, MAX_WR_WRITE_REGISTERS)?;
+                if count != write_nb as usize * 2 {
+                    return Err(ModbusError::BadData)
+                }
+                check_len(pdu, 10 + count)?;
+                Ok(Request::ReadWriteMultipleRegisters {
+                    read_addr,
+                    read_nb,
+                    write_addr,
+                    values: registers(&pdu[10..]),
+                })
+            }
+            0x2B => {
+                check_len(pdu, 4)?;
+                if pdu[1] != MEI_READ_DEVICE_ID {
+                    return Err(ModbusError::BadData)
+                }
+                Ok(Request::ReadDeviceIdentification { read_code: pdu[2], object_id: pdu[3] })
+            }
+            _ => Err(ModbusError::BadData),
+        }
+    }
+}
+
+impl Response {
+    /// The function code of the response, with the high bit set for an exception
+    pub fn function_code(&self) -> u8
+    {
+        match *self {
+            Response::ReadCoils(_) => 0x01,
+            Response::ReadDiscreteInputs(_) => 0x02,
+            Response::ReadHoldingRegisters(_) => 0x03,
+            Response::ReadInputRegisters(_) => 0x04,
+            Response::WriteSingleCoil { .. } => 0x05,
+            Response::WriteSingleRegister { .. } => 0x06,
+            Response::WriteMultipleCoils { .. } => 0x0F,
+            Response::WriteMultipleRegisters { .. } => 0x10,
+            Response::MaskWriteRegister { .. } => 0x16,
+            Response::ReadWriteMultipleRegisters(_) => 0x17,
+            Response::ReadDeviceIdentification(_) => 0x2B,
+            Response::Exception { function, .. } => function | 0x80,
+        }
+    }
+
+    /// Encode the response PDU
+    pub fn encode(&self) -> Result<Vec<u8>, ModbusError>
+    {
+        let mut pdu = vec![self.function_code()];
+        match *self {
+            Response::ReadCoils(ref bits) | Response::ReadDiscreteInputs(ref bits) => {
+                check_quantity(bits.len(), MAX_READ_BITS + 7)?;
+                let packed = pack_bits(bits);
+                pdu.push(packed.len() as u8);
+                pdu.extend_from_slice(&packed);
+            }
+            Response::ReadHoldingRegisters(ref values) |
+            Response::ReadInputRegisters(ref values) |
+            Response::ReadWriteMultipleRegisters(ref values) => {
+                check_quantity(values.len(), MAX_READ_REGISTERS)?;
+                pdu.push((values.len() * 2) as u8);
+                push_registers(&mut pdu, values);
+            }
+            Response::WriteSingleCoil { addr, value } => {
+                push16(&mut pdu, addr);
+                push16(&mut pdu, coil_word(value));
+            }
+            Response::WriteSingleRegister { addr, value } => {
+                push16(&mut pdu, addr);
+                push16(&mut pdu, value);
+            }
+            Response::WriteMultipleCoils { addr, nb } |
+            Response::WriteMultipleRegisters { addr, nb } => {
+                push16(&mut pdu, addr);
+                push16(&mut pdu, nb);
+            }
+            Response::MaskWriteRegister { addr, and_mask, or_mask } => {
+                push16(&mut pdu, addr);
+                push16(&mut pdu, and_mask);
+                push16(&mut pdu, or_mask);
+            }
+            Response::ReadDeviceIdentification(ref id) => {
+                pdu.push(MEI_READ_DEVICE_ID);
+                pdu.push(id.read_code);
+                pdu.push(id.conformity_level);
+                pdu.push(if id.more_follows { 0xFF } else { 0x00 });
+                pdu.push(id.next_object_id);
+                pdu.push(id.objects.len() as u8);
+                for &(object_id, ref value) in &id.objects {
+                    if value.len() > 0xFF {
+                        return Err(ModbusError::TooManyData)
+                    }
+                    pdu.push(object_id);
+                    pdu.push(value.len() as u8);
+                    pdu.extend_from_slice(value);
+                }
+                if pdu.len() > MAX_PDU_LENGTH {
+                    return Err(ModbusError::TooManyData)
+                }
+            }
+            Response::Exception { exception, .. } => {
+                pdu.push(exception.code());
+            }
+        }
+        Ok(pdu)
+    }
+
+    /// Decode a response PDU
+    pub fn decode(pdu: &[u8]) -> Result<Response, ModbusError>
+    {
+        check_min_len(pdu, 1)?;
+        if pdu[0] & 0x80 != 0 {
+            check_len(pdu, 2)?;
+            return match Exception::from_code(pdu[1]) {
+                Some(exception) => Ok(Response::Exception { function: pdu[0] & 0x7F,
+                                                            exception }),
+                None => Err(ModbusError::BadException),
+            }
+        }
+
+        match pdu[0] {
+            0x01 | 0x02 => {
+                check_min_len(pdu, 2)?;
+                let count = pdu[1] as usize;
+                check_len(pdu, 2 + count)?;
+                let bits = unpack_bits(&pdu[2..], count * 8);
+                if pdu[0] == 0x01 {
+                    Ok(Response::ReadCoils(bits))
+                } else {
+                    Ok(Response::ReadDiscreteInputs(bits))
+                }
+            }
+            0x03 | 0x04 | 0x17 => {
+                check_min_len(pdu, 2)?;
+                let count = pdu[1] as usize;
+                if count % 2 != 0 {
+                    return Err(ModbusError::BadData)
+                }
+                check_len(pdu, 2 + count)?;
+                let values = registers(&pdu[2..]);
+                Ok(match pdu[0] {
+                    0x03 => Response::ReadHoldingRegisters(values),
+                    0x04 => Response::ReadInputRegisters(values),
+                    _ => Response::ReadWriteMultipleRegisters(values),
+                })
+            }
+            0x05 => {
+                check_len(pdu, 5)?;
+                let value = coil_value(be16(pdu, 3))?;
+                Ok(Response::WriteSingleCoil { addr: be16(pdu, 1), value })
+            }
+            0x06 => {
+                check_len(pdu, 5)?;
+                Ok(Response::WriteSingleRegister { addr: be16(pdu, 1), value: be16(pdu, 3) })
+            }
+            0x0F => {
+                check_len(pdu, 5)?;
+                Ok(Response::WriteMultipleCoils { addr: be16(pdu, 1), nb: be16(pdu, 3) })
+            }
+            0x10 => {
+                check_len(pdu, 5)?;
+                Ok(Response::WriteMultipleRegisters { addr: be16(pdu, 1), nb: be16(pdu, 3) })
+            }
+            0x16 => {
+                check_len(pdu, 7)?;
+                Ok(Response::MaskWriteRegister {
+                    addr: be16(pdu, 1),
+                    and_mask: be16(pdu, 3),
+                    or_mask: be16(pdu, 5),
+                })
+            }
+            0x2B => {
+                check_min_len(pdu, 7)?;
+                if pdu[1] != MEI_READ_DEVICE_ID {
+                    return Err(ModbusError::BadData)
+                }
+                let more_follows = match pdu[4] {
+                    0x00 => false,
+                    0xFF => true,
+                    _ => return Err(ModbusError::BadData),
+                };
+                let mut objects = Vec::with_capacity(pdu[6] as usize);
+                let mut pos = 7;
+                for _ in 0..pdu[6] {
+                    check_min_len(pdu, pos + 2)?;
+                    let len = pdu[pos + 1] as usize;
+                    check_min_len(pdu, pos + 2 + len)?;
+                    objects.push((pdu[pos], pdu[pos + 2..pos + 2 + len].to_vec()));
+                    pos += 2 + len;
+                }
+                check_len(pdu, pos)?;
+                Ok(Response::ReadDeviceIdentification(DeviceIdentification {
+                    read_code: pdu[2],
+                    conformity_level: pdu[3],
+                    more_follows,
+                    next_object_id: pdu[5],
+                    objects,
+                }))
+            }
+            _ => Err(ModbusError::BadData),
+        }
+    }
+}
+
+/// Length of the request PDU starting with the given bytes, None if more bytes are needed to
+/// tell
+///
+/// RTU frames carry no length, so a receiver must know the function code to find where a frame
+/// ends.
+pub fn request_length(pdu: &[u8]) -> Result<Option<usize>, ModbusError>
+{
+    if pdu.is_empty() {
+        return Ok(None)
+    }
+    let length = match pdu[0] {
+        0x01..=0x06 => 5,
+        0x0F | 0x10 if pdu.len() >= 6 => 6 + pdu[5] as usize,
+        0x0F | 0x10 => return Ok(None),
+        0x16 => 7,
+        0x17 if pdu.len() >= 10 => 10 + pdu[9] as usize,
+        0x17 => return Ok(None),
+        0x2B => 4,
+        _ => return Err(ModbusError::BadData),
+    };
+    Ok(Some(length))
+}
+
+/// Length of the response PDU starting with the given bytes, None if more bytes are needed to
+/// tell
+pub fn response_length(pdu: &[u8]) -> Result<Option<usize>, ModbusError>
+{
+    if pdu.is_empty() {
+        return Ok(None)
+    }
+    let length = match pdu[0] {
+        0x80..=0xFF => 2,
+        0x01..=0x04 | 0x17 if pdu.len() >= 2 => 2 + pdu[1] as usize,
+        0x01..=0x04 | 0x17 => return Ok(None),
+        0x05 | 0x06 | 0x0F | 0x10 => 5,
+        0x16 => 7,
+        0x2B => {
+            if pdu.len() < 7 {
+                return Ok(None)
+            }
+            let mut pos = 7;
+            for _ in 0..pdu[6] {
+                if pdu.len() < pos + 2 {
+                    return Ok(None)
+                }
+                pos += 2 + pdu[pos + 1] as usize;
+            }
+            pos
+        }
+        _ => return Err(ModbusError::BadData),
+    };
+    Ok(Some(length))
+}
+
+/// A Modbus TCP frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TcpFrame<'a> {
+    /// Identifier copied by the server into its response, to match it with the request
+    pub transaction_id: u16,
+    /// Slave behind a gateway, `MODBUS_TCP_SLAVE` (255) otherwise
+    pub unit_id: u8,
+    pub pdu: &'a [u8],
+}
+
+/// Frame a PDU with an MBAP header
+pub fn encode_tcp(transaction_id: u16, unit_id: u8, pdu: &[u8]) -> Vec<u8>
+{
+    let mut adu = Vec::with_capacity(MBAP_HEADER_LENGTH + pdu.len());
+    push16(&mut adu, transaction_id);
+    push16(&mut adu, 0);
+    push16(&mut adu, (pdu.len() + 1) as u16);
+    adu.push(unit_id);
+    adu.extend_from_slice(pdu);
+    adu
+}
+
+/// Decode a Modbus TCP frame, which must be complete
+pub fn decode_tcp<'a>(adu: &'a [u8]) -> Result<TcpFrame<'a>, ModbusError>
+{
+    match tcp_frame_length(adu)? {
+        Some(len) if len == adu.len() => {}
+        _ => return Err(ModbusError::BadData),
+    }
+    Ok(TcpFrame {
+        transaction_id: be16(adu, 0),
+        unit_id: adu[6],
+        pdu: &adu[MBAP_HEADER_LENGTH..],
+    })
+}
+
+/// Length of the Modbus TCP frame starting with the given bytes, None if its header is
+/// incomplete
+pub fn tcp_frame_length(adu: &[u8]) -> Result<Option<usize>, ModbusError>
+{
+    if adu.len() < 6 {
+        return Ok(None)
+    }
+    let length = be16(adu, 4) as usize;
+    if be16(adu, 2) != 0 || !(2..=MAX_PDU_LENGTH + 1).contains(&length) {
+        return Err(ModbusError::BadData)
+    }
+    Ok(Some(6 + length))
+}
+
+/// A Modbus RTU frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtuFrame<'a> {
+    /// Address of the slave the request is sent to or the response comes from
+    pub slave: u8,
+    pub pdu: &'a [u8],
+}
+
+/// CRC-16 of Modbus RTU frames (polynomial 0xA001, initial value 0xFFFF)
+pub fn crc16(data: &[u8]) -> u16
+{
+    let mut crc = 0xFFFFu16;
+    for &byte in data {
+        crc ^= byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xA001 } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+/// Frame a PDU with the slave address and the CRC
+pub fn encode_rtu(slave: u8, pdu: &[u8]) -> Vec<u8>
+{
+    let mut adu = Vec::with_capacity(pdu.len() + 3);
+    adu.push(slave);
+    adu.extend_from_slice(pdu);
+    let crc = crc16(&adu);
+    adu.push(crc as u8);
+    adu.push((crc >> 8) as u8);
+    adu
+}
+
+/// Decode a Modbus RTU frame, checking its CRC
+pub fn decode_rtu<'a>(adu: &'a [u8]) -> Result<RtuFrame<'a>, ModbusError>
+{
+    if adu.len() < 4 || adu.len() > RTU_MAX_ADU_LENGTH {
+        return Err(ModbusError::BadData)
+    }
+    let (body, crc) = adu.split_at(adu.len() - 2);
+    if crc16(body) != (crc[0] as u16 | (crc[1] as u16) << 8) {
+        return Err(ModbusError::BadCrc)
+    }
+    Ok(RtuFrame { slave: body[0], pdu: &body[1..] })
+}
+
+/// A Modbus ASCII frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsciiFrame {
+    /// Address of the slave the request is sent to or the response comes from
+    pub slave: u8,
+    /// The PDU, decoded from hexadecimal
+    pub pdu: Vec<u8>,
+}
+
+/// Longitudinal redundancy check of Modbus ASCII frames: the two's complement of the sum of the
+/// bytes
+pub fn lrc(data: &[u8]) -> u8
+{
+    data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)).wrapping_neg()
+}
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+fn push_hex(adu: &mut Vec<u8>, byte: u8)
+{
+    adu.push(HEX_DIGITS[(byte >> 4) as usize]);
+    adu.push(HEX_DIGITS[(byte & 0x0F) as usize]);
+}
+
+fn hex_value(digit: u8) -> Result<u8, ModbusError>
+{
+    match digit {
+        b'0'..=b'9' => Ok(digit - b'0'),
+        b'A'..=b'F' => Ok(digit - b'A' + 10),
+        b'a'..=b'f' => Ok(digit - b'a' + 10),
+        _ => Err(ModbusError::BadData),
+    }
+}
+
+/// Frame a PDU with the slave address and the LRC, in hexadecimal between ':' and CR LF
+pub fn encode_ascii(slave: u8, pdu: &[u8]) -> Vec<u8>
+{
+    let mut adu = Vec::with_capacity(2 * pdu.len() + 7);
+    adu.push(b':');
+    push_hex(&mut adu, slave);
+    for &byte in pdu {
+        push_hex(&mut adu, byte);
+    }
+    let sum = pdu.iter().fold(slave, |sum, &byte| sum.wrapping_add(byte));
+    push_hex(&mut adu, sum.wrapping_neg());
+    adu.extend_from_slice(b"\r\n");
+    adu
+}
+
+/// Decode a Modbus ASCII frame, from ':' to CR LF, checking its LRC
+///
+/// Hexadecimal digits may be upper or lower case.
+pub fn decode_ascii(adu: &[u8]) -> Result<AsciiFrame, ModbusError>
+{
+    if adu.len() < 9 || adu.len() > ASCII_MAX_ADU_LENGTH || adu[0] != b':' ||
+       !adu.ends_with(b"\r\n") {
+        return Err(ModbusError::BadData)
+    }
+    let hex = &adu[1..adu.len() - 2];
+    if hex.len() % 2 != 0 {
+        return Err(ModbusError::BadData)
+    }
+    let mut body = Vec::with_capacity(hex.len() / 2);
+    for pair in hex.chunks(2) {
+        body.push(hex_value(pair[0])? << 4 | hex_value(pair[1])?);
+    }
+    // The LRC of the whole body, LRC included, is zero
+    if lrc(&body) != 0 {
+        return Err(ModbusError::BadCrc)
+    }
+    body.pop();
+    Ok(AsciiFrame { slave: body[0], pdu: body.split_off(1) })
+}
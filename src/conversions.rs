@@ -0,0 +1,150 @@
+//! Conversions between registers and 32 or 64 bit values
+//!
+//! Devices disagree on how to spread a value over many registers, so every conversion takes a
+//! `WordOrder`. The letters name the bytes of a 32 bit value from the most significant (A) to
+//! the least significant (D), in the order they appear in the registers, high byte of the first
+//! register first. 64 bit values follow the same pattern over four registers, e.g. `Cdab` puts
+//! the least significant register first.
+//!
+//! # Example
+//!
+//! ```
+//! use modbus::conversions::{get_f32, set_f32, WordOrder};
+//!
+//! let mut regs = [0u16; 2];
+//! set_f32(1.5, &mut regs, WordOrder::Abcd);
+//! assert_eq!(regs, [0x3FC0, 0x0000]);
+//! assert_eq!(get_f32(&regs, WordOrder::Abcd), 1.5);
+//! ```
+//!
+//! All functions panic if the slice holds fewer registers than the value needs.
+
+/// Order of the bytes of a value spread over many registers
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WordOrder {
+    /// Big endian, most significant register first (`modbus_get_float_abcd`)
+    Abcd,
+    /// Little endian, least significant register first with swapped bytes
+    /// (`modbus_get_float_dcba`)
+    Dcba,
+    /// Most significant register first with swapped bytes (`modbus_get_float_badc`)
+    Badc,
+    /// Least significant register first (`modbus_get_float_cdab`)
+    Cdab,
+}
+
+impl WordOrder {
+    fn swap_words(&self) -> bool
+    {
+        match *self {
+            WordOrder::Cdab | WordOrder::Dcba => true,
+            WordOrder::Abcd | WordOrder::Badc => false,
+        }
+    }
+
+    fn swap_bytes(&self) -> bool
+    {
+        match *self {
+            WordOrder::Badc | WordOrder::Dcba => true,
+            WordOrder::Abcd | WordOrder::Cdab => false,
+        }
+    }
+}
+
+fn get(src: &[u16], nb: usize, order: WordOrder) -> u64
+{
+    let src = &src[..nb];
+    let mut value = 0u64;
+    for i in 0..nb {
+        let word = if order.swap_words() { src[nb - 1 - i] } else { src[i] };
+        let word = if order.swap_bytes() { word.swap_bytes() } else { word };
+        value = (value << 16) | word as u64;
+    }
+    value
+}
+
+fn set(value: u64, dest: &mut [u16], nb: usize, order: WordOrder)
+{
+    let dest = &mut dest[..nb];
+    for i in 0..nb {
+        let word = (value >> (16 * (nb - 1 - i))) as u16;
+        let word = if order.swap_bytes() { word.swap_bytes() } else { word };
+        if order.swap_words() {
+            dest[nb - 1 - i] = word;
+        } else {
+            dest[i] = word;
+        }
+    }
+}
+
+/// Get a u32 from the first two registers of src
+pub fn get_u32(src: &[u16], order: WordOrder) -> u32
+{
+    get(src, 2, order) as u32
+}
+
+/// Store a u32 in the first two registers of dest
+pub fn set_u32(value: u32, dest: &mut [u16], order: WordOrder)
+{
+    set(value as u64, dest, 2, order)
+}
+
+/// Get an i32 from the first two registers of src
+pub fn get_i32(src: &[u16], order: WordOrder) -> i32
+{
+    get_u32(src, order) as i32
+}
+
+/// Store an i32 in the first two registers of dest
+pub fn set_i32(value: i32, dest: &mut [u16], order: WordOrder)
+{
+    set_u32(value as u32, dest, order)
+}
+
+/// Get an IEEE 754 single precision float from the first two registers of src
+pub fn get_f32(src: &[u16], order: WordOrder) -> f32
+{
+    f32::from_bits(get_u32(src, order))
+}
+
+/// Store an IEEE 754 single precision float in the first two registers of dest
+pub fn set_f32(value: f32, dest: &mut [u16], order: WordOrder)
+{
+    set_u32(value.to_bits(), dest, order)
+}
+
+/// Get a u64 from the first four registers of src
+pub fn get_u64(src: &[u16], order: WordOrder) -> u64
+{
+    get(src, 4, order)
+}
+
+/// Store a u64 in the first four registers of dest
+pub fn set_u64(value: u64, dest: &mut [u16], order: WordOrder)
+{
+    set(value, dest, 4, order)
+}
+
+/// Get an i64 from the first four registers of src
+pub fn get_i64(src: &[u16], order: WordOrder) -> i64
+{
+    get_u64(src, order) as i64
+}
+
+/// Store an i64 in the first four registers of dest
+pub fn set_i64(value: i64, dest: &mut [u16], order: WordOrder)
+{
+    set_u64(value as u64, dest, order)
+}
+
+/// Get an IEEE 754 double precision float from the first four registers of src
+pub fn get_f64(src: &[u16], order: WordOrder) -> f64
+{
+    f64::from_bits(get_u64(src, order))
+}
+
+/// Store an IEEE 754 double precision float in the first four registers of dest
+pub fn set_f64(value: f64, dest: &mut [u16], order: WordOrder)
+{
+    set_u64(value.to_bits(), dest, order)
+}
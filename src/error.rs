@@ -0,0 +1,185 @@
+use std::error::Error;
+#[cfg(feature = "libmodbus")]
+use std::ffi::CStr;
+use std::fmt;
+use std::io;
+
+use libc::c_int;
+
+// The errno values of libmodbus, see modbus.h
+const MODBUS_ENOBASE: c_int = 112345678;
+const EMBXGTAR: c_int = MODBUS_ENOBASE + Exception::GatewayTarget as c_int;
+const EMBBADCRC: c_int = EMBXGTAR + 1;
+const EMBBADDATA: c_int = EMBXGTAR + 2;
+const EMBBADEXC: c_int = EMBXGTAR + 3;
+const EMBUNKEXC: c_int = EMBXGTAR + 4;
+const EMBMDATA: c_int = EMBXGTAR + 5;
+const EMBBADSLAVE: c_int = EMBXGTAR + 6;
+
+/// Exception codes a Modbus server can reply with, numbered as `modbus_sys::Enum_Unnamed24`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Exception {
+    IllegalFunction = 1,
+    IllegalDataAddress = 2,
+    IllegalDataValue = 3,
+    ServerFailure = 4,
+    Acknowledge = 5,
+    ServerBusy = 6,
+    NegativeAcknowledge = 7,
+    MemoryParity = 8,
+    NotDefined = 9,
+    GatewayPath = 10,
+    GatewayTarget = 11,
+}
+
+impl Exception {
+    /// Decode an exception code as found in an exception response
+    pub fn from_code(code: u8) -> Option<Exception>
+    {
+        match code {
+            1 => Some(Exception::IllegalFunction),
+            2 => Some(Exception::IllegalDataAddress),
+            3 => Some(Exception::IllegalDataValue),
+            4 => Some(Exception::ServerFailure),
+            5 => Some(Exception::Acknowledge),
+            6 => Some(Exception::ServerBusy),
+            7 => Some(Exception::NegativeAcknowledge),
+            8 => Some(Exception::MemoryParity),
+            9 => Some(Exception::NotDefined),
+            10 => Some(Exception::GatewayPath),
+            11 => Some(Exception::GatewayTarget),
+            _ => None,
+        }
+    }
+
+    /// The exception code sent on the wire
+    pub fn code(&self) -> u8
+    {
+        *self as u8
+    }
+}
+
+/// Error returned by the functions of a Modbus context
+#[derive(Debug)]
+pub enum ModbusError {
+    /// The remote device replied with a Modbus exception
+    Exception(Exception),
+    /// Invalid CRC in a RTU frame
+    BadCrc,
+    /// Invalid data in a response
+    BadData,
+    /// Invalid exception code in a response
+    BadException,
+    /// Response with an exception code libmodbus does not know
+    UnknownException,
+    /// Too many data requested or received
+    TooManyData,
+    /// Response not from the requested slave
+    BadSlave,
+    /// RTU frame broken by a silent interval, or too short or too long to be a frame, see
+    /// `RtuTransport`
+    BadFrame,
+    /// System error, e.g. a timeout, a closed connection or invalid arguments
+    Io(io::Error),
+}
+
+impl ModbusError {
+    /// Decode an `errno` value set by libmodbus
+    pub fn from_errno(errnum: c_int) -> ModbusError
+    {
+        if errnum > MODBUS_ENOBASE && errnum <= EMBXGTAR {
+            if let Some(e) = Exception::from_code((errnum - MODBUS_ENOBASE) as u8) {
+                return ModbusError::Exception(e)
+            }
+        }
+
+        match errnum {
+            EMBBADCRC   => ModbusError::BadCrc,
+            EMBBADDATA  => ModbusError::BadData,
+            EMBBADEXC   => ModbusError::BadException,
+            EMBUNKEXC   => ModbusError::UnknownException,
+            EMBMDATA    => ModbusError::TooManyData,
+            EMBBADSLAVE => ModbusError::BadSlave,
+            _ => ModbusError::Io(io::Error::from_raw_os_error(errnum)),
+        }
+    }
+
+    /// The `errno` value libmodbus uses for this error, if there is one
+    pub fn errno(&self) -> Option<c_int>
+    {
+        match *self {
+            ModbusError::Exception(e) => Some(MODBUS_ENOBASE + e.code() as c_int),
+            ModbusError::BadCrc => Some(EMBBADCRC),
+            ModbusError::BadData => Some(EMBBADDATA),
+            ModbusError::BadException => Some(EMBBADEXC),
+            ModbusError::UnknownException => Some(EMBUNKEXC),
+            ModbusError::TooManyData => Some(EMBMDATA),
+            ModbusError::BadSlave => Some(EMBBADSLAVE),
+            ModbusError::BadFrame => None,
+            ModbusError::Io(ref e) => e.raw_os_error(),
+        }
+    }
+}
+
+impl fmt::Display for ModbusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self.errno() {
+            Some(errnum) => write!(f, "{}", strerror(errnum)),
+            None => match *self {
+                ModbusError::BadFrame => write!(f, "Malformed frame"),
+                ModbusError::Io(ref e) => write!(f, "{}", e),
+                _ => unreachable!(),
+            },
+        }
+    }
+}
+
+#[cfg(feature = "libmodbus")]
+fn strerror(errnum: c_int) -> String
+{
+    let msg = unsafe { CStr::from_ptr(modbus_sys::modbus_strerror(errnum)) };
+    msg.to_string_lossy().into_owned()
+}
+
+/// The messages of `modbus_strerror`
+#[cfg(not(feature = "libmodbus"))]
+fn strerror(errnum: c_int) -> String
+{
+    let msg = match errnum - MODBUS_ENOBASE {
+        1 => "Illegal function",
+        2 => "Illegal data address",
+        3 => "Illegal data value",
+        4 => "Slave device or server failure",
+        5 => "Acknowledge",
+        6 => "Slave device or server is busy",
+        7 => "Negative acknowledge",
+        8 => "Memory parity error",
+        10 => "Gateway path unavailable",
+        11 => "Target device failed to respond",
+        12 => "Invalid CRC",
+        13 => "Invalid data",
+        14 => "Invalid exception code",
+        16 => "Too many data",
+        17 => "Response not from requested slave",
+        _ => return io::Error::from_raw_os_error(errnum).to_string(),
+    };
+    msg.to_string()
+}
+
+impl Error for ModbusError {
+    fn source(&self) -> Option<&(dyn Error + 'static)>
+    {
+        match *self {
+            ModbusError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ModbusError {
+    fn from(e: io::Error) -> ModbusError
+    {
+        ModbusError::Io(e)
+    }
+}
@@ -0,0 +1,245 @@
+#[cfg(all(feature = "libmodbus", not(libmodbus_3_0)))]
+use std::io::Write;
+#[cfg(all(feature = "libmodbus", not(libmodbus_3_0)))]
+use std::mem::ManuallyDrop;
+#[cfg(all(feature = "libmodbus", not(libmodbus_3_0)))]
+use std::net::TcpStream;
+#[cfg(all(feature = "libmodbus", not(libmodbus_3_0)))]
+use std::os::unix::io::FromRawFd;
+
+use crate::Exception;
+#[cfg(all(feature = "libmodbus", not(libmodbus_3_0)))]
+use crate::{Modbus, ModbusError, ModbusResult, MODBUS_BROADCAST_ADDRESS};
+use crate::codec::{self, Request, Response};
+
+/// Custom server logic, for registers computed on read or writes triggering actions
+///
+/// A server decodes each request and calls the matching method. Returning an exception sends it
+/// to the client instead of a normal response. Every method not implemented replies
+/// `Exception::IllegalFunction`.
+///
+/// Bits are one u8 set to 1 or 0 per bit, as with `Modbus::read_bits`. The quantity of values
+/// and the address range are checked before the handler is called.
+///
+/// # Example
+///
+/// ```
+/// use modbus::{RequestHandler, Exception};
+///
+/// struct Counter {
+///     reads: u16,
+/// }
+///
+/// impl RequestHandler for Counter {
+///     fn read_holding_registers(&mut self, _addr: u16, dest: &mut [u16]) -> Result<(), Exception>
+///     {
+///         self.reads += 1;
+///         for r in dest.iter_mut() {
+///             *r = self.reads;
+///         }
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait RequestHandler {
+    /// Read dest.len() coils at addr (function code 0x01)
+    fn read_coils(&mut self, _addr: u16, _dest: &mut [u8]) -> Result<(), Exception>
+    {
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Read dest.len() discrete inputs at addr (function code 0x02)
+    fn read_discrete_inputs(&mut self, _addr: u16, _dest: &mut [u8]) -> Result<(), Exception>
+    {
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Read dest.len() holding registers at addr (function code 0x03)
+    fn read_holding_registers(&mut self, _addr: u16, _dest: &mut [u16]) -> Result<(), Exception>
+    {
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Read dest.len() input registers at addr (function code 0x04)
+    fn read_input_registers(&mut self, _addr: u16, _dest: &mut [u16]) -> Result<(), Exception>
+    {
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Write a single coil (function code 0x05). Calls `write_coils` by default.
+    fn write_coil(&mut self, addr: u16, value: u8) -> Result<(), Exception>
+    {
+        self.write_coils(addr, &[value])
+    }
+
+    /// Write a single holding register (function code 0x06). Calls `write_registers` by default.
+    fn write_register(&mut self, addr: u16, value: u16) -> Result<(), Exception>
+    {
+        self.write_registers(addr, &[value])
+    }
+
+    /// Write values.len() coils at addr (function code 0x0F)
+    fn write_coils(&mut self, _addr: u16, _values: &[u8]) -> Result<(), Exception>
+    {
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Write values.len() holding registers at addr (function code 0x10)
+    fn write_registers(&mut self, _addr: u16, _values: &[u16]) -> Result<(), Exception>
+    {
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Modify the holding register at addr to `(value & and_mask) | (or_mask & !and_mask)`
+    /// (function code 0x16). Calls `read_holding_registers` then `write_register` by default.
+    fn mask_write_register(&mut self, addr: u16, and_mask: u16, or_mask: u16)
+        -> Result<(), Exception>
+    {
+        let mut value = [0u16];
+        self.read_holding_registers(addr, &mut value)?;
+        self.write_register(addr, (value[0] & and_mask) | (or_mask & !and_mask))
+    }
+
+    /// Write src.len() holding registers at write_addr then read dest.len() holding registers at
+    /// read_addr (function code 0x17). Calls `write_registers` then `read_holding_registers` by
+    /// default.
+    fn write_and_read_registers(&mut self, write_addr: u16, src: &[u16],
+                                read_addr: u16, dest: &mut [u16]) -> Result<(), Exception>
+    {
+        self.write_registers(write_addr, src)?;
+        self.read_holding_registers(read_addr, dest)
+    }
+}
+
+#[cfg(all(feature = "libmodbus", not(libmodbus_3_0)))]
+impl Modbus {
+    /// Send a response to the received request, computed by handler
+    ///
+    /// This function shall decode the request req, as returned by `Modbus::receive`, call the
+    /// matching method of handler and send its result, or the exception it returned, to the
+    /// client. As with `Modbus::reply`, no response is sent to a RTU broadcast.
+    pub fn reply_handler<H: RequestHandler + ?Sized>(&self, req: &[u8], handler: &mut H)
+        -> ModbusResult
+    {
+        let offset = self.get_header_length()? as usize;
+        if req.len() <= offset {
+            return Err(ModbusError::from_errno(libc::EINVAL))
+        }
+        let rsp = respond(&req[offset..], handler);
+
+        if offset == codec::MBAP_HEADER_LENGTH {
+            // modbus_send_raw_request would not copy the transaction identifier of the request
+            let frame = codec::decode_tcp(req)?;
+            let adu = codec::encode_tcp(frame.transaction_id, frame.unit_id, &rsp);
+            let socket = self.get_socket()?;
+            // The socket belongs to the context
+            let mut stream = ManuallyDrop::new(unsafe { TcpStream::from_raw_fd(socket) });
+            stream.write_all(&adu)?;
+            Ok(adu.len() as i32)
+        } else if req[0] == MODBUS_BROADCAST_ADDRESS as u8 {
+            Ok(0)
+        } else {
+            let mut raw_rsp = Vec::with_capacity(rsp.len() + 1);
+            raw_rsp.push(req[0]);
+            raw_rsp.extend_from_slice(&rsp);
+            self.send_raw_request(&raw_rsp)
+        }
+    }
+}
+
+/// Check the address range of a request decoded by `Request::decode`, which checked its quantity
+fn check_addresses(addr: u16, nb: usize) -> Result<(), Exception>
+{
+    if addr as usize + nb > 0x10000 {
+        Err(Exception::IllegalDataAddress)
+    } else {
+        Ok(())
+    }
+}
+
+/// Run the handler for the decoded request
+fn call<H: RequestHandler + ?Sized>(pdu: &[u8], handler: &mut H) -> Result<Response, Exception>
+{
+    let req = match Request::decode(pdu) {
+        Ok(req) => req,
+        // Invalid data for a known function, or an unknown function
+        Err(_) if codec::request_length(pdu).is_ok() => return Err(Exception::IllegalDataValue),
+        Err(_) => return Err(Exception::IllegalFunction),
+    };
+
+    match req {
+        Request::ReadCoils { addr, nb } => {
+            check_addresses(addr, nb as usize)?;
+            let mut dest = vec![0u8; nb as usize];
+            handler.read_coils(addr, &mut dest)?;
+            Ok(Response::ReadCoils(dest.iter().map(|&bit| bit != 0).collect()))
+        }
+        Request::ReadDiscreteInputs { addr, nb } => {
+            check_addresses(addr, nb as usize)?;
+            let mut dest = vec![0u8; nb as usize];
+            handler.read_discrete_inputs(addr, &mut dest)?;
+            Ok(Response::ReadDiscreteInputs(dest.iter().map(|&bit| bit != 0).collect()))
+        }
+        Request::ReadHoldingRegisters { addr, nb } => {
+            check_addresses(addr, nb as usize)?;
+            let mut dest = vec![0u16; nb as usize];
+            handler.read_holding_registers(addr, &mut dest)?;
+            Ok(Response::ReadHoldingRegisters(dest))
+        }
+        Request::ReadInputRegisters { addr, nb } => {
+            check_addresses(addr, nb as usize)?;
+            let mut dest = vec![0u16; nb as usize];
+            handler.read_input_registers(addr, &mut dest)?;
+            Ok(Response::ReadInputRegisters(dest))
+        }
+        Request::WriteSingleCoil { addr, value } => {
+            handler.write_coil(addr, value as u8)?;
+            Ok(Response::WriteSingleCoil { addr, value })
+        }
+        Request::WriteSingleRegister { addr, value } => {
+            handler.write_register(addr, value)?;
+            Ok(Response::WriteSingleRegister { addr, value })
+        }
+        Request::WriteMultipleCoils { addr, ref values } => {
+            check_addresses(addr, values.len())?;
+            let values = values.iter().map(|&bit| bit as u8).collect::<Vec<u8>>();
+            handler.write_coils(addr, &values)?;
+            Ok(Response::WriteMultipleCoils { addr, nb: values.len() as u16 })
+        }
+        Request::WriteMultipleRegisters { addr, ref values } => {
+            check_addresses(addr, values.len())?;
+            handler.write_registers(addr, values)?;
+            Ok(Response::WriteMultipleRegisters { addr, nb: values.len() as u16 })
+        }
+        Request::MaskWriteRegister { addr, and_mask, or_mask } => {
+            handler.mask_write_register(addr, and_mask, or_mask)?;
+            Ok(Response::MaskWriteRegister { addr, and_mask, or_mask })
+        }
+        Request::ReadWriteMultipleRegisters { read_addr, read_nb, write_addr, ref values } => {
+            check_addresses(read_addr, read_nb as usize)?;
+            check_addresses(write_addr, values.len())?;
+            let mut dest = vec![0u16; read_nb as usize];
+            handler.write_and_read_registers(write_addr, values, read_addr, &mut dest)?;
+            Ok(Response::ReadWriteMultipleRegisters(dest))
+        }
+        Request::ReadDeviceIdentification { .. } => Err(Exception::IllegalFunction),
+    }
+}
+
+/// Run the handler for the request PDU and return the PDU of its response, or of the exception
+/// it returned, without libmodbus
+///
+/// A response the protocol cannot carry, e.g. values filled by the handler beyond the limits,
+/// is replaced with a `ServerFailure` exception.
+pub fn respond<H: RequestHandler + ?Sized>(pdu: &[u8], handler: &mut H) -> Vec<u8>
+{
+    let rsp = call(pdu, handler)
+        .and_then(|rsp| rsp.encode().map_err(|_| Exception::ServerFailure));
+    match rsp {
+        Ok(rsp) => rsp,
+        Err(exception) => {
+            let function = pdu.first().map_or(0, |&function| function & 0x7F);
+            vec![function | 0x80, exception as u8]
+        }
+    }
+}
@@ -4,53 +4,147 @@
 #![crate_type = "lib"]
 */
 
+#[cfg(feature = "libmodbus")]
 extern crate modbus_sys;
 extern crate libc;
+#[cfg(feature = "libmodbus")]
 extern crate errno;
+#[cfg(feature = "libmodbus")]
+#[macro_use]
+extern crate bitflags;
 
-use std::ffi::{CString, CStr};
+#[cfg(unix)]
+mod ascii;
+#[cfg(feature = "tokio")]
+mod async_modbus;
+pub mod bits;
+mod channel;
+mod client;
+pub mod codec;
+pub mod conversions;
+mod error;
+mod handler;
+#[cfg(feature = "libmodbus")]
+mod reconnect;
+#[cfg(unix)]
+mod rtu;
+#[cfg(unix)]
+mod serial;
+#[cfg(feature = "libmodbus")]
+mod server;
+#[cfg(feature = "libmodbus")]
+mod shared;
+mod tcp;
+mod transport;
+mod transport_server;
+mod udp;
 
-use std::net::{SocketAddrV4};
+#[cfg(unix)]
+pub use ascii::{ModbusAscii, AsciiTransport};
+#[cfg(feature = "tokio")]
+pub use async_modbus::AsyncModbus;
+pub use bits::{Bit, Coils};
+pub use channel::ChannelTransport;
+pub use client::ModbusClient;
+pub use error::{ModbusError, Exception};
+pub use handler::RequestHandler;
+#[cfg(feature = "libmodbus")]
+pub use reconnect::ReconnectingModbus;
+#[cfg(unix)]
+pub use rtu::RtuTransport;
+#[cfg(feature = "libmodbus")]
+pub use server::{ModbusServer, ShutdownHandle};
+#[cfg(feature = "libmodbus")]
+pub use shared::SharedModbus;
+pub use tcp::TcpTransport;
+pub use transport::{Adu, Transport};
+pub use transport_server::TransportServer;
+pub use udp::UdpTransport;
 
-use libc::{c_uint, c_int};
-use errno::{Errno, errno};
+#[cfg(feature = "libmodbus")]
+use std::ffi::CString;
 
-pub type ModbusResult = Result<i32, Errno>;
+#[cfg(feature = "libmodbus")]
+use std::net::{SocketAddr};
+#[cfg(all(feature = "libmodbus", not(libmodbus_3_0)))]
+use std::ops::Deref;
+#[cfg(feature = "libmodbus")]
+use std::time::Duration;
 
-fn octets_to_str(oct : &[u8; 4]) -> String
-{
-    format!("{}.{}.{}.{}", oct[0], oct[1], oct[2], oct[3])
+use libc::c_int;
+#[cfg(feature = "libmodbus")]
+use libc::{c_uint, c_char};
+#[cfg(feature = "libmodbus")]
+use errno::errno;
+
+pub type ModbusResult = Result<i32, ModbusError>;
+
+/// Maximum length of an ADU, i.e. the size of a buffer able to hold any request or response
+pub const MAX_ADU_LENGTH: usize = 260;
+
+/// Slave address broadcasting a request to all slaves
+pub const MODBUS_BROADCAST_ADDRESS: c_int = 0;
+
+/// Unit identifier of a Modbus TCP request not going through a gateway
+pub const MODBUS_TCP_SLAVE: c_int = 0xFF;
+
+#[cfg(feature = "libmodbus")]
+fn last_error() -> ModbusError {
+    ModbusError::from_errno(errno().0)
 }
 
+#[cfg(feature = "libmodbus")]
 fn cvt(r: c_int) -> ModbusResult {
     if r == -1 {
-        Err(errno())
+        Err(last_error())
     } else {
         Ok(r as i32)
     }
 }
 
+/// Split a duration into the seconds and microseconds libmodbus expects, None if it overflows
+#[cfg(feature = "libmodbus")]
+fn duration_to_timeval(d: Duration) -> Option<(u32, u32)>
+{
+    if d.as_secs() > u32::MAX as u64 {
+        None
+    } else {
+        Some((d.as_secs() as u32, d.subsec_micros()))
+    }
+}
+
+#[cfg(all(feature = "libmodbus", libmodbus_3_0))]
+fn timeval_to_duration(tv: &libc::timeval) -> Duration
+{
+    Duration::new(tv.tv_sec as u64, tv.tv_usec as u32 * 1000)
+}
+
 /// Returns the Major version number of the libmodbus library
+#[cfg(feature = "libmodbus")]
 pub fn get_major_version() -> c_uint
 {
-    modbus_sys::libmodbus_version_major
+    unsafe { modbus_sys::libmodbus_version_major }
 }
 /// Returns the Minor version number of the libmodbus library
+#[cfg(feature = "libmodbus")]
 pub fn get_minor_version() -> c_uint
 {
-    modbus_sys::libmodbus_version_minor
+    unsafe { modbus_sys::libmodbus_version_minor }
 }
 /// Returns the Patch version number of the libmodbus library
+#[cfg(feature = "libmodbus")]
 pub fn get_patch_version() -> c_uint
 {
-    modbus_sys::libmodbus_version_micro
+    unsafe { modbus_sys::libmodbus_version_micro }
 }
 
 /// Mapping struct which servers can use to reply to clients
+#[cfg(feature = "libmodbus")]
 pub struct ModbusMapping {
     handle: *mut modbus_sys::modbus_mapping_t,
 }
 
+#[cfg(feature = "libmodbus")]
 impl ModbusMapping {
     /// Create a new mapping. This will allocate four arrays to store bits, input bits, registers
     /// and input registers. All values are initialized to zero.
@@ -69,7 +163,8 @@ impl ModbusMapping {
             let handle = modbus_sys::modbus_mapping_new(
                             nb_bits, nb_input_bits,
                             nb_registers, nb_input_registers);
-            let mapping = ModbusMapping{ handle: handle };
+            assert!(!handle.is_null());
+            let mapping = ModbusMapping{ handle };
             return mapping
         }
     }
@@ -82,6 +177,9 @@ impl ModbusMapping {
     ///
     /// let mbm = ModbusMapping::new_start_address(0, 500, 0, 500, 0, 500, 0, 500);
     /// ```
+    ///
+    /// Needs libmodbus 3.1, the tables of libmodbus 3.0 always start at address 0.
+    #[cfg(not(libmodbus_3_0))]
     pub fn new_start_address( start_bits: c_uint,
                                 nb_bits: c_uint,
                                 start_input_bits: c_uint,
@@ -96,14 +194,213 @@ impl ModbusMapping {
                                 start_bits, nb_bits, start_input_bits,
                                 nb_input_bits, start_registers, nb_registers,
                                 start_input_registers, nb_input_registers);
-            let mapping = ModbusMapping{ handle: handle };
+            assert!(!handle.is_null());
+            let mapping = ModbusMapping{ handle };
             return mapping
 
         }
     }
+
+    fn raw(&self) -> &modbus_sys::modbus_mapping_t
+    {
+        unsafe { &*self.handle }
+    }
+
+    /// Address of the first bit (coil)
+    #[cfg(not(libmodbus_3_0))]
+    pub fn start_bits(&self) -> c_int
+    {
+        self.raw().start_bits
+    }
+
+    /// Address of the first bit (coil), always 0 with libmodbus 3.0
+    #[cfg(libmodbus_3_0)]
+    pub fn start_bits(&self) -> c_int
+    {
+        0
+    }
+
+    /// Address of the first input bit (discrete input)
+    #[cfg(not(libmodbus_3_0))]
+    pub fn start_input_bits(&self) -> c_int
+    {
+        self.raw().start_input_bits
+    }
+
+    /// Address of the first input bit (discrete input), always 0 with libmodbus 3.0
+    #[cfg(libmodbus_3_0)]
+    pub fn start_input_bits(&self) -> c_int
+    {
+        0
+    }
+
+    /// Address of the first holding register
+    #[cfg(not(libmodbus_3_0))]
+    pub fn start_registers(&self) -> c_int
+    {
+        self.raw().start_registers
+    }
+
+    /// Address of the first holding register, always 0 with libmodbus 3.0
+    #[cfg(libmodbus_3_0)]
+    pub fn start_registers(&self) -> c_int
+    {
+        0
+    }
+
+    /// Address of the first input register
+    #[cfg(not(libmodbus_3_0))]
+    pub fn start_input_registers(&self) -> c_int
+    {
+        self.raw().start_input_registers
+    }
+
+    /// Address of the first input register, always 0 with libmodbus 3.0
+    #[cfg(libmodbus_3_0)]
+    pub fn start_input_registers(&self) -> c_int
+    {
+        0
+    }
+
+    /// The bits (coils), one u8 set to 1 or 0 per bit. Index 0 is the bit at `start_bits()`.
+    pub fn bits(&self) -> &[u8]
+    {
+        unsafe { table(self.raw().tab_bits, self.raw().nb_bits) }
+    }
+
+    /// Mutable access to the bits (coils)
+    pub fn bits_mut(&mut self) -> &mut [u8]
+    {
+        unsafe { table_mut(self.raw().tab_bits, self.raw().nb_bits) }
+    }
+
+    /// The input bits (discrete inputs), one u8 set to 1 or 0 per bit. Index 0 is the bit at
+    /// `start_input_bits()`.
+    pub fn input_bits(&self) -> &[u8]
+    {
+        unsafe { table(self.raw().tab_input_bits, self.raw().nb_input_bits) }
+    }
+
+    /// Mutable access to the input bits (discrete inputs)
+    pub fn input_bits_mut(&mut self) -> &mut [u8]
+    {
+        unsafe { table_mut(self.raw().tab_input_bits, self.raw().nb_input_bits) }
+    }
+
+    /// The holding registers. Index 0 is the register at `start_registers()`.
+    pub fn registers(&self) -> &[u16]
+    {
+        unsafe { table(self.raw().tab_registers, self.raw().nb_registers) }
+    }
+
+    /// Mutable access to the holding registers
+    pub fn registers_mut(&mut self) -> &mut [u16]
+    {
+        unsafe { table_mut(self.raw().tab_registers, self.raw().nb_registers) }
+    }
+
+    /// The input registers. Index 0 is the register at `start_input_registers()`.
+    pub fn input_registers(&self) -> &[u16]
+    {
+        unsafe { table(self.raw().tab_input_registers, self.raw().nb_input_registers) }
+    }
+
+    /// Mutable access to the input registers
+    pub fn input_registers_mut(&mut self) -> &mut [u16]
+    {
+        unsafe { table_mut(self.raw().tab_input_registers, self.raw().nb_input_registers) }
+    }
+
+    /// Get the bit (coil) at the Modbus address addr, or None if it is not mapped
+    pub fn bit(&self, addr: c_int) -> Option<u8>
+    {
+        let start = self.start_bits();
+        table_index(start, addr).and_then(|i| self.bits().get(i).cloned())
+    }
+
+    /// Get a mutable reference to the bit (coil) at the Modbus address addr
+    pub fn bit_mut(&mut self, addr: c_int) -> Option<&mut u8>
+    {
+        let start = self.start_bits();
+        table_index(start, addr).and_then(move |i| self.bits_mut().get_mut(i))
+    }
+
+    /// Get the input bit (discrete input) at the Modbus address addr, or None if it is not
+    /// mapped
+    pub fn input_bit(&self, addr: c_int) -> Option<u8>
+    {
+        let start = self.start_input_bits();
+        table_index(start, addr).and_then(|i| self.input_bits().get(i).cloned())
+    }
+
+    /// Get a mutable reference to the input bit (discrete input) at the Modbus address addr
+    pub fn input_bit_mut(&mut self, addr: c_int) -> Option<&mut u8>
+    {
+        let start = self.start_input_bits();
+        table_index(start, addr).and_then(move |i| self.input_bits_mut().get_mut(i))
+    }
+
+    /// Get the holding register at the Modbus address addr, or None if it is not mapped
+    pub fn register(&self, addr: c_int) -> Option<u16>
+    {
+        let start = self.start_registers();
+        table_index(start, addr).and_then(|i| self.registers().get(i).cloned())
+    }
+
+    /// Get a mutable reference to the holding register at the Modbus address addr
+    pub fn register_mut(&mut self, addr: c_int) -> Option<&mut u16>
+    {
+        let start = self.start_registers();
+        table_index(start, addr).and_then(move |i| self.registers_mut().get_mut(i))
+    }
+
+    /// Get the input register at the Modbus address addr, or None if it is not mapped
+    pub fn input_register(&self, addr: c_int) -> Option<u16>
+    {
+        let start = self.start_input_registers();
+        table_index(start, addr).and_then(|i| self.input_registers().get(i).cloned())
+    }
+
+    /// Get a mutable reference to the input register at the Modbus address addr
+    pub fn input_register_mut(&mut self, addr: c_int) -> Option<&mut u16>
+    {
+        let start = self.start_input_registers();
+        table_index(start, addr).and_then(move |i| self.input_registers_mut().get_mut(i))
+    }
+}
+
+#[cfg(feature = "libmodbus")]
+unsafe fn table<'a, T>(tab: *mut T, nb: c_int) -> &'a [T]
+{
+    if tab.is_null() || nb <= 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(tab, nb as usize)
+    }
+}
+
+#[cfg(feature = "libmodbus")]
+unsafe fn table_mut<'a, T>(tab: *mut T, nb: c_int) -> &'a mut [T]
+{
+    if tab.is_null() || nb <= 0 {
+        &mut []
+    } else {
+        std::slice::from_raw_parts_mut(tab, nb as usize)
+    }
+}
+
+#[cfg(feature = "libmodbus")]
+fn table_index(start: c_int, addr: c_int) -> Option<usize>
+{
+    if addr < start {
+        None
+    } else {
+        Some((addr - start) as usize)
+    }
 }
 
 /// Frees a ModbusMapping
+#[cfg(feature = "libmodbus")]
 impl Drop for ModbusMapping {
     fn drop(&mut self) {
         unsafe {
@@ -112,21 +409,185 @@ impl Drop for ModbusMapping {
     }
 }
 
+#[cfg(feature = "libmodbus")]
 unsafe impl Send for ModbusMapping { }
+
+// SAFETY: a shared &ModbusMapping only reads the tables, through the slices of bits(),
+// registers() and the like; they are written through &mut self only, by the *_mut accessors and
+// by `Modbus::reply` which takes &mut ModbusMapping, so no write can race a read
+#[cfg(feature = "libmodbus")]
 unsafe impl Sync for ModbusMapping { }
 
 
+/// Parity of a serial line
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+#[cfg(feature = "libmodbus")]
+impl Parity {
+    fn as_c_char(&self) -> c_char
+    {
+        match *self {
+            Parity::None => b'N' as c_char,
+            Parity::Even => b'E' as c_char,
+            Parity::Odd  => b'O' as c_char,
+        }
+    }
+}
+
+/// Number of data bits per character on a serial line
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DataBits {
+    Five,
+    Six,
+    Seven,
+    Eight,
+}
+
+#[cfg(feature = "libmodbus")]
+impl DataBits {
+    fn as_c_int(&self) -> c_int
+    {
+        match *self {
+            DataBits::Five  => 5,
+            DataBits::Six   => 6,
+            DataBits::Seven => 7,
+            DataBits::Eight => 8,
+        }
+    }
+}
+
+/// Number of stop bits per character on a serial line
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StopBits {
+    One,
+    Two,
+}
+
+#[cfg(feature = "libmodbus")]
+impl StopBits {
+    fn as_c_int(&self) -> c_int
+    {
+        match *self {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        }
+    }
+}
+
+/// Electrical interface used by a RTU context
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg(feature = "libmodbus")]
+pub enum SerialMode {
+    /// RS-232, the libmodbus default
+    Rs232,
+    /// RS-485, half duplex
+    Rs485,
+}
+
+#[cfg(feature = "libmodbus")]
+impl SerialMode {
+    fn as_c_int(&self) -> c_int
+    {
+        match *self {
+            SerialMode::Rs232 => modbus_sys::MODBUS_RTU_RS232,
+            SerialMode::Rs485 => modbus_sys::MODBUS_RTU_RS485,
+        }
+    }
+
+    fn from_c_int(mode: c_int) -> Option<SerialMode>
+    {
+        match mode {
+            modbus_sys::MODBUS_RTU_RS232 => Some(SerialMode::Rs232),
+            modbus_sys::MODBUS_RTU_RS485 => Some(SerialMode::Rs485),
+            _ => None,
+        }
+    }
+}
+
+/// How the RTS line is driven while a RTU context transmits, libmodbus 3.1 only
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg(all(feature = "libmodbus", not(libmodbus_3_0)))]
+pub enum RtsMode {
+    /// RTS is not used
+    None,
+    /// RTS is set high while sending and low otherwise
+    Up,
+    /// RTS is set low while sending and high otherwise
+    Down,
+}
+
+#[cfg(all(feature = "libmodbus", not(libmodbus_3_0)))]
+impl RtsMode {
+    fn as_c_int(&self) -> c_int
+    {
+        match *self {
+            RtsMode::None => modbus_sys::MODBUS_RTU_RTS_NONE,
+            RtsMode::Up   => modbus_sys::MODBUS_RTU_RTS_UP,
+            RtsMode::Down => modbus_sys::MODBUS_RTU_RTS_DOWN,
+        }
+    }
+
+    fn from_c_int(mode: c_int) -> Option<RtsMode>
+    {
+        match mode {
+            modbus_sys::MODBUS_RTU_RTS_NONE => Some(RtsMode::None),
+            modbus_sys::MODBUS_RTU_RTS_UP   => Some(RtsMode::Up),
+            modbus_sys::MODBUS_RTU_RTS_DOWN => Some(RtsMode::Down),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "libmodbus")]
+bitflags! {
+    /// Error recovery modes of a context, see `Modbus::set_error_recovery`
+    ///
+    /// The empty set, the default, disables error recovery.
+    pub struct ErrorRecovery: c_uint {
+        /// Reconnect after a timeout or a closed connection
+        const LINK =
+            modbus_sys::modbus_error_recovery_mode::MODBUS_ERROR_RECOVERY_LINK as c_uint;
+        /// Flush the connection and retry after an invalid response
+        const PROTOCOL =
+            modbus_sys::modbus_error_recovery_mode::MODBUS_ERROR_RECOVERY_PROTOCOL as c_uint;
+    }
+}
+
+/// Identification of a remote device, as returned by `Modbus::report_server_id`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerId {
+    /// Server ID, a device specific value
+    pub server_id: u8,
+    /// Run indicator status, true when the device reports ON (0xFF)
+    pub running: bool,
+    /// Additional device specific data
+    pub data: Vec<u8>,
+}
+
 /// Context for modbus functions
+///
+/// A context can be moved to another thread but not shared between threads, use `SharedModbus`
+/// to send requests from several threads.
+#[cfg(feature = "libmodbus")]
 pub struct Modbus {
     handle: *mut modbus_sys::modbus_t,
 }
 
+#[cfg(feature = "libmodbus")]
 impl Modbus {
 
-    /// Create a new Modbus context for TCP/IPv4
+    /// Create a new Modbus context for TCP
+    ///
+    /// IPv4 addresses use the TCP/IPv4 backend of libmodbus and IPv6 addresses the protocol
+    /// independent one, see `Modbus::new_tcp_pi`.
     ///
     /// # Arguments
-    /// * `addr` - A TCP/IPv4 socket address
+    /// * `addr` - A TCP/IPv4 or TCP/IPv6 socket address
     ///
     /// # Example
     ///
@@ -134,23 +595,34 @@ impl Modbus {
     /// use modbus::Modbus;
     /// let addr = "127.0.0.1:1502".parse().unwrap();
     /// let mut mb = Modbus::new_tcp(&addr);
+    /// let addr = "[::1]:1502".parse().unwrap();
+    /// let mut mb = Modbus::new_tcp(&addr);
     /// ```
-    pub fn new_tcp(addr: &SocketAddrV4) -> Modbus
+    pub fn new_tcp(addr: &SocketAddr) -> Modbus
     {
-        let addr_str = octets_to_str( &(addr.ip().octets()) );
+        let addr = match *addr {
+            SocketAddr::V4(ref addr) => addr,
+            SocketAddr::V6(ref addr) => {
+                let node = match addr.scope_id() {
+                    0 => addr.ip().to_string(),
+                    scope_id => format!("{}%{}", addr.ip(), scope_id),
+                };
+                return Modbus::new_tcp_pi(&node, &addr.port().to_string()).unwrap()
+            }
+        };
 
-        println!(" addr_str = {} ", addr_str);
+        let addr_str = CString::new(addr.ip().to_string()).unwrap();
 
         unsafe {
             let handle = modbus_sys::modbus_new_tcp(
-                CString::new(addr_str).unwrap().as_ptr(),
+                addr_str.as_ptr(),
                 addr.port() as i32
             );
 
             assert!(!handle.is_null());
 
-            let mut ret = Modbus {
-                handle: handle,
+            let ret = Modbus {
+                handle,
             };
 
             return ret
@@ -159,6 +631,255 @@ impl Modbus {
 
     }
 
+    /// Create a new Modbus context for TCP, protocol independent
+    ///
+    /// The node and service are resolved with getaddrinfo when connecting, so host names and
+    /// IPv6 addresses can be used.
+    ///
+    /// # Arguments
+    /// * `node` - Host name or IPv4 or IPv6 address of the server
+    /// * `service` - Service name or port number, e.g. "502"
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use modbus::Modbus;
+    /// let mb = Modbus::new_tcp_pi("plc.example.com", "502").unwrap();
+    /// ```
+    pub fn new_tcp_pi(node: &str, service: &str) -> Result<Modbus, ModbusError>
+    {
+        let (node, service) = match (CString::new(node), CString::new(service)) {
+            (Ok(node), Ok(service)) => (node, service),
+            _ => return Err(ModbusError::from_errno(libc::EINVAL)),
+        };
+
+        unsafe {
+            let handle = modbus_sys::modbus_new_tcp_pi(node.as_ptr(), service.as_ptr());
+            if handle.is_null() {
+                return Err(last_error())
+            }
+
+            Ok(Modbus { handle })
+        }
+    }
+
+    /// Create a new Modbus context for RTU over a serial line
+    ///
+    /// The serial port is only opened by `Modbus::connect`. An error is returned if libmodbus
+    /// rejects the settings, e.g. a baud rate of zero.
+    ///
+    /// # Arguments
+    /// * `device` - Name of the serial port, e.g. `/dev/ttyUSB0`
+    /// * `baud` - Baud rate of the line, e.g. 9600 or 19200
+    /// * `parity` - Parity of the line
+    /// * `data_bits` - Number of data bits per character
+    /// * `stop_bits` - Number of stop bits per character
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use modbus::{Modbus, Parity, DataBits, StopBits};
+    /// let mb = Modbus::new_rtu("/dev/ttyUSB0", 19200, Parity::Even,
+    ///                          DataBits::Eight, StopBits::One).unwrap();
+    /// ```
+    pub fn new_rtu(device: &str, baud: c_int, parity: Parity,
+                   data_bits: DataBits, stop_bits: StopBits) -> Result<Modbus, ModbusError>
+    {
+        let device = match CString::new(device) {
+            Ok(device) => device,
+            Err(_) => return Err(ModbusError::from_errno(libc::EINVAL)),
+        };
+
+        unsafe {
+            let handle = modbus_sys::modbus_new_rtu(device.as_ptr(), baud,
+                                                    parity.as_c_char(),
+                                                    data_bits.as_c_int(),
+                                                    stop_bits.as_c_int());
+            if handle.is_null() {
+                return Err(last_error())
+            }
+
+            Ok(Modbus { handle })
+        }
+    }
+
+    /// Set the serial mode of a RTU context
+    ///
+    /// Switching to RS-485 requires a driver supporting the `TIOCSRS485` ioctl, so this must be
+    /// called after `Modbus::connect`.
+    pub fn set_serial_mode(&self, mode: SerialMode) -> ModbusResult
+    {
+        unsafe {
+            cvt( modbus_sys::modbus_rtu_set_serial_mode(self.handle, mode.as_c_int()) )
+        }
+    }
+
+    /// Get the serial mode of a RTU context
+    pub fn get_serial_mode(&self) -> Result<SerialMode, ModbusError>
+    {
+        let mode = unsafe { cvt( modbus_sys::modbus_rtu_get_serial_mode(self.handle) )? };
+        SerialMode::from_c_int(mode).ok_or(ModbusError::BadData)
+    }
+
+    /// Set the RTS mode of a RTU context
+    ///
+    /// The RTS line is toggled around each transmission to drive the direction of a RS-485
+    /// transceiver. The RTS functions need libmodbus 3.1.
+    #[cfg(not(libmodbus_3_0))]
+    pub fn set_rts(&self, mode: RtsMode) -> ModbusResult
+    {
+        unsafe {
+            cvt( modbus_sys::modbus_rtu_set_rts(self.handle, mode.as_c_int()) )
+        }
+    }
+
+    /// Get the RTS mode of a RTU context
+    #[cfg(not(libmodbus_3_0))]
+    pub fn get_rts(&self) -> Result<RtsMode, ModbusError>
+    {
+        let mode = unsafe { cvt( modbus_sys::modbus_rtu_get_rts(self.handle) )? };
+        RtsMode::from_c_int(mode).ok_or(ModbusError::BadData)
+    }
+
+    /// Set the delay, in microseconds, between toggling RTS and sending or receiving data
+    #[cfg(not(libmodbus_3_0))]
+    pub fn set_rts_delay(&self, us: c_int) -> ModbusResult
+    {
+        unsafe {
+            cvt( modbus_sys::modbus_rtu_set_rts_delay(self.handle, us) )
+        }
+    }
+
+    /// Get the delay, in microseconds, between toggling RTS and sending or receiving data
+    #[cfg(not(libmodbus_3_0))]
+    pub fn get_rts_delay(&self) -> ModbusResult
+    {
+        unsafe {
+            cvt( modbus_sys::modbus_rtu_get_rts_delay(self.handle) )
+        }
+    }
+
+    /// Set the slave (unit identifier) the requests of the context are addressed to
+    ///
+    /// With RTU, the slave must be between 1 and 247. With TCP, it is only needed to reach a
+    /// device behind a gateway; `MODBUS_TCP_SLAVE` (255) is used by default and 1 to 247 are
+    /// valid too.
+    ///
+    /// `MODBUS_BROADCAST_ADDRESS` (0) broadcasts the requests to all slaves. Slaves do not reply
+    /// to a broadcast, so writes return as soon as the request is sent and reads are refused.
+    ///
+    /// On a server, this is the slave whose requests are answered.
+    pub fn set_slave(&self, slave: c_int) -> ModbusResult
+    {
+        unsafe {
+            cvt( modbus_sys::modbus_set_slave(self.handle, slave) )
+        }
+    }
+
+    /// Get the slave the requests of the context are addressed to, None if it was never set
+    ///
+    /// Needs libmodbus 3.1.
+    #[cfg(not(libmodbus_3_0))]
+    pub fn slave(&self) -> Option<c_int>
+    {
+        match unsafe { modbus_sys::modbus_get_slave(self.handle) } {
+            -1 => None,
+            slave => Some(slave),
+        }
+    }
+
+    /// Address the requests made through the returned guard to another slave
+    ///
+    /// The previous slave is restored when the guard is dropped. This lets one connection to a
+    /// TCP gateway poll many RTU slaves. Needs libmodbus 3.1.
+    ///
+    /// Returns `EINVAL` if no slave was set yet, as on a new RTU context: libmodbus cannot set a
+    /// context back to no slave, so it could not be restored.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use modbus::Modbus;
+    ///
+    /// let addr = "192.168.1.10:502".parse().unwrap();
+    /// let mb = Modbus::new_tcp(&addr);
+    /// mb.connect().unwrap();
+    ///
+    /// let mut dest = [0u16; 4];
+    /// for unit in 1..248 {
+    ///     mb.with_slave(unit).unwrap().read_registers(0, &mut dest).unwrap();
+    /// }
+    /// ```
+    #[cfg(not(libmodbus_3_0))]
+    pub fn with_slave<'a>(&'a self, slave: c_int) -> Result<SlaveGuard<'a>, ModbusError>
+    {
+        let previous = unsafe { modbus_sys::modbus_get_slave(self.handle) };
+        if previous == -1 {
+            return Err(ModbusError::from_errno(libc::EINVAL))
+        }
+        self.set_slave(slave)?;
+        Ok(SlaveGuard { ctx: self, previous })
+    }
+
+    #[cfg(not(libmodbus_3_0))]
+    fn is_broadcast(&self) -> bool
+    {
+        self.slave() == Some(modbus_sys::MODBUS_BROADCAST_ADDRESS)
+    }
+
+    // libmodbus 3.0 cannot tell the slave of a context, broadcasts are left to it
+    #[cfg(libmodbus_3_0)]
+    fn is_broadcast(&self) -> bool
+    {
+        false
+    }
+
+    /// Reads get no response from a broadcast
+    fn check_unicast(&self) -> Result<(), ModbusError>
+    {
+        if self.is_broadcast() {
+            Err(ModbusError::from_errno(libc::EINVAL))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Send a broadcast request without waiting for a response, which would never come
+    fn broadcast(&self, pdu: &[u8], nb: c_int) -> ModbusResult
+    {
+        let mut req = Vec::with_capacity(pdu.len() + 1);
+        req.push(modbus_sys::MODBUS_BROADCAST_ADDRESS as u8);
+        req.extend_from_slice(pdu);
+        unsafe {
+            cvt( modbus_sys::modbus_send_raw_request(self.handle, req.as_mut_ptr(),
+                                                     req.len() as c_int) )?;
+        }
+        Ok(nb)
+    }
+
+    /// Set the error recovery mode of the context
+    ///
+    /// With `ErrorRecovery::LINK`, libmodbus reconnects after a link error, sleeping the response
+    /// timeout first. With `ErrorRecovery::PROTOCOL`, it flushes the connection after an invalid
+    /// response. In both cases the failed function still returns an error; use
+    /// `ReconnectingModbus` to also retry requests.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use modbus::{Modbus, ErrorRecovery};
+    ///
+    /// let addr = "127.0.0.1:1502".parse().unwrap();
+    /// let mb = Modbus::new_tcp(&addr);
+    /// mb.set_error_recovery(ErrorRecovery::LINK | ErrorRecovery::PROTOCOL).unwrap();
+    /// ```
+    pub fn set_error_recovery(&self, mode: ErrorRecovery) -> ModbusResult
+    {
+        unsafe {
+            cvt( modbus_sys::modbus_set_error_recovery(self.handle, mode.bits()) )
+        }
+    }
+
     /// Set debug flag of the context
     pub fn set_debug(&self, flag: bool)
     {
@@ -187,6 +908,246 @@ impl Modbus {
         }
     }
 
+    /// Set the socket or file descriptor used by the context
+    ///
+    /// This is useful to manage multiple client connections to the same server.
+    pub fn set_socket(&self, socket: c_int) -> ModbusResult
+    {
+        unsafe {
+            cvt( modbus_sys::modbus_set_socket(self.handle, socket) )
+        }
+    }
+
+    /// Get the socket or file descriptor used by the context
+    pub fn get_socket(&self) -> ModbusResult
+    {
+        unsafe {
+            cvt( modbus_sys::modbus_get_socket(self.handle) )
+        }
+    }
+
+    /// Create a socket listening for TCP/IPv4 connections
+    ///
+    /// This function shall create a socket and listen to at most nb_connection incoming
+    /// connections on the address the context was created with. The listening socket is returned
+    /// and is owned by the caller.
+    pub fn tcp_listen(&self, nb_connection: c_int) -> ModbusResult
+    {
+        unsafe {
+            cvt( modbus_sys::modbus_tcp_listen(self.handle, nb_connection) )
+        }
+    }
+
+    /// Create a socket listening for TCP connections, protocol independent
+    ///
+    /// This is `Modbus::tcp_listen` for contexts made with `Modbus::new_tcp_pi`.
+    pub fn tcp_pi_listen(&self, nb_connection: c_int) -> ModbusResult
+    {
+        unsafe {
+            cvt( modbus_sys::modbus_tcp_pi_listen(self.handle, nb_connection) )
+        }
+    }
+
+    /// Accept a new TCP/IPv4 connection
+    ///
+    /// This function shall extract the first connection on the queue of pending connections of
+    /// the listening socket and make the context use it. The new socket is returned.
+    pub fn tcp_accept(&self, socket: c_int) -> ModbusResult
+    {
+        let mut s = socket;
+        unsafe {
+            cvt( modbus_sys::modbus_tcp_accept(self.handle, &mut s) )
+        }
+    }
+
+    /// Accept a new TCP connection, protocol independent
+    ///
+    /// This is `Modbus::tcp_accept` for contexts made with `Modbus::new_tcp_pi`.
+    pub fn tcp_pi_accept(&self, socket: c_int) -> ModbusResult
+    {
+        let mut s = socket;
+        unsafe {
+            cvt( modbus_sys::modbus_tcp_pi_accept(self.handle, &mut s) )
+        }
+    }
+
+    /// Receive an indication request
+    ///
+    /// This function shall receive an indication request from the socket of the context and
+    /// store it in req. The length of the request is returned, or 0 if the request was ignored
+    /// because it was addressed to another slave.
+    pub fn receive(&self, req: &mut [u8; MAX_ADU_LENGTH]) -> ModbusResult
+    {
+        unsafe {
+            cvt( modbus_sys::modbus_receive(self.handle, req.as_mut_ptr()) )
+        }
+    }
+
+    /// Send a response to the received request
+    ///
+    /// This function shall send a response to the request req, as returned by `Modbus::receive`,
+    /// reading or writing the values of mapping. An exception response is sent if the request
+    /// is invalid or addresses values outside of the mapping.
+    pub fn reply(&self, req: &[u8], mapping: &mut ModbusMapping) -> ModbusResult
+    {
+        if req.len() > MAX_ADU_LENGTH {
+            return Err(ModbusError::from_errno(libc::EINVAL))
+        }
+        // libmodbus trusts the lengths encoded in the request, so never hand it a short buffer
+        let mut buf = [0u8; MAX_ADU_LENGTH];
+        buf[..req.len()].copy_from_slice(req);
+
+        unsafe {
+            cvt( modbus_sys::modbus_reply(self.handle, buf.as_ptr(), req.len() as c_int,
+                                          mapping.handle) )
+        }
+    }
+
+    /// Send an exception response to the received request
+    ///
+    /// This function shall send an exception response with the given exception code to the
+    /// request req, as returned by `Modbus::receive`.
+    pub fn reply_exception(&self, req: &[u8], exception: Exception) -> ModbusResult
+    {
+        if req.len() > MAX_ADU_LENGTH {
+            return Err(ModbusError::from_errno(libc::EINVAL))
+        }
+        let mut buf = [0u8; MAX_ADU_LENGTH];
+        buf[..req.len()].copy_from_slice(req);
+
+        unsafe {
+            cvt( modbus_sys::modbus_reply_exception(self.handle, buf.as_ptr(),
+                                                    exception.code() as c_uint) )
+        }
+    }
+
+    /// Get the length of the header of the ADUs of the context
+    ///
+    /// This is 1 for RTU (the slave address) and 7 for TCP (the MBAP header). The function code
+    /// of a request or response is found at this offset.
+    pub fn get_header_length(&self) -> ModbusResult
+    {
+        unsafe {
+            cvt( modbus_sys::modbus_get_header_length(self.handle) )
+        }
+    }
+
+    /// Get the timeout to wait for a response
+    #[cfg(not(libmodbus_3_0))]
+    pub fn get_response_timeout(&self) -> Result<Duration, ModbusError>
+    {
+        let (mut sec, mut usec) = (0, 0);
+        unsafe {
+            cvt( modbus_sys::modbus_get_response_timeout(self.handle, &mut sec, &mut usec) )?;
+        }
+        Ok(Duration::new(sec as u64, usec * 1000))
+    }
+
+    /// Get the timeout to wait for a response
+    #[cfg(libmodbus_3_0)]
+    pub fn get_response_timeout(&self) -> Result<Duration, ModbusError>
+    {
+        let mut tv = libc::timeval { tv_sec: 0, tv_usec: 0 };
+        unsafe {
+            modbus_sys::modbus_get_response_timeout(self.handle, &mut tv);
+        }
+        Ok(timeval_to_duration(&tv))
+    }
+
+    /// Set the timeout to wait for a response
+    ///
+    /// The timeout must not be zero and is truncated to the microsecond.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use modbus::Modbus;
+    ///
+    /// let addr = "127.0.0.1:1502".parse().unwrap();
+    /// let mb = Modbus::new_tcp(&addr);
+    /// mb.set_response_timeout(Duration::from_millis(100)).unwrap();
+    /// ```
+    pub fn set_response_timeout(&self, timeout: Duration) -> ModbusResult
+    {
+        let (sec, usec) = match duration_to_timeval(timeout) {
+            Some((0, 0)) | None => return Err(ModbusError::from_errno(libc::EINVAL)),
+            Some(tv) => tv,
+        };
+        self.set_timeout(modbus_sys::modbus_set_response_timeout, sec, usec)
+    }
+
+    /// Get the timeout between two bytes of a message, None if it is disabled
+    #[cfg(not(libmodbus_3_0))]
+    pub fn get_byte_timeout(&self) -> Result<Option<Duration>, ModbusError>
+    {
+        let (mut sec, mut usec) = (0, 0);
+        unsafe {
+            cvt( modbus_sys::modbus_get_byte_timeout(self.handle, &mut sec, &mut usec) )?;
+        }
+        if sec == 0 && usec == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(Duration::new(sec as u64, usec * 1000)))
+        }
+    }
+
+    /// Get the timeout between two bytes of a message, None if it is disabled
+    #[cfg(libmodbus_3_0)]
+    pub fn get_byte_timeout(&self) -> Result<Option<Duration>, ModbusError>
+    {
+        let mut tv = libc::timeval { tv_sec: 0, tv_usec: 0 };
+        unsafe {
+            modbus_sys::modbus_get_byte_timeout(self.handle, &mut tv);
+        }
+        if tv.tv_sec == 0 && tv.tv_usec == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(timeval_to_duration(&tv)))
+        }
+    }
+
+    /// Set the timeout between two bytes of a message
+    ///
+    /// With None, the byte timeout is disabled and only the response timeout is used to wait for
+    /// a whole response. Otherwise the timeout must not be zero and is truncated to the
+    /// microsecond.
+    ///
+    /// libmodbus 3.0 cannot disable the byte timeout, a zero timeout expiring at once, so None
+    /// fails with `EINVAL` there.
+    pub fn set_byte_timeout(&self, timeout: Option<Duration>) -> ModbusResult
+    {
+        let (sec, usec) = match timeout.map(duration_to_timeval) {
+            None if cfg!(libmodbus_3_0) => return Err(ModbusError::from_errno(libc::EINVAL)),
+            None => (0, 0),
+            Some(Some((0, 0))) | Some(None) => return Err(ModbusError::from_errno(libc::EINVAL)),
+            Some(Some(tv)) => tv,
+        };
+        self.set_timeout(modbus_sys::modbus_set_byte_timeout, sec, usec)
+    }
+
+    #[cfg(not(libmodbus_3_0))]
+    fn set_timeout(&self, set: unsafe extern "C" fn(*mut modbus_sys::modbus_t, u32, u32) -> c_int,
+                   sec: u32, usec: u32) -> ModbusResult
+    {
+        unsafe {
+            cvt( set(self.handle, sec, usec) )
+        }
+    }
+
+    // libmodbus 3.0 takes a struct timeval and cannot fail
+    #[cfg(libmodbus_3_0)]
+    fn set_timeout(&self,
+                   set: unsafe extern "C" fn(*mut modbus_sys::modbus_t, *const libc::timeval),
+                   sec: u32, usec: u32) -> ModbusResult
+    {
+        let tv = libc::timeval { tv_sec: sec as libc::time_t, tv_usec: usec as libc::suseconds_t };
+        unsafe {
+            set(self.handle, &tv);
+        }
+        Ok(0)
+    }
+
     /// Write a single bit
     ///
     /// This function will write the status of status at the address addr of the remote device. The
@@ -195,6 +1156,10 @@ impl Modbus {
     /// The function uses the Modbus function code 0x05 (force single coil).
     pub fn write_bit(&self, coil_addr: c_int, status: c_int) -> ModbusResult
     {
+        if self.is_broadcast() {
+            let value = if status != 0 { 0xFF00 } else { 0 };
+            return self.broadcast(&pdu_header(0x05, coil_addr, value), 1)
+        }
         unsafe {
             let r = modbus_sys::modbus_write_bit(self.handle, coil_addr, status);
             return cvt(r)
@@ -204,11 +1169,19 @@ impl Modbus {
     /// Write many bits
     ///
     /// This function shall write the status of the data.len() bits from data at the address addr
-    /// of the remote device. The data slice must contain bytes set to 1 or 0.
+    /// of the remote device. The data slice must contain bools or bytes set to 1 or 0.
     ///
     /// The function uses the Modbus function code 0x0F (force multiple coils).
-    pub fn write_bits(&self, addr: c_int, data: &[u8]) -> ModbusResult
+    pub fn write_bits<B: Bit>(&self, addr: c_int, data: &[B]) -> ModbusResult
     {
+        if self.is_broadcast() {
+            check_quantity(data.len(), modbus_sys::MODBUS_MAX_WRITE_BITS)?;
+            let mut pdu = pdu_header(0x0F, addr, data.len() as c_int);
+            pdu.push(data.len().div_ceil(8) as u8);
+            pdu.extend(bits::pack_bits(data));
+            return self.broadcast(&pdu, data.len() as c_int)
+        }
+        let data = data.iter().map(|b| b.to_u8()).collect::<Vec<u8>>();
         unsafe {
             cvt( modbus_sys::modbus_write_bits(self.handle, addr, data.len() as c_int, data.as_ptr()) )
         }
@@ -218,19 +1191,45 @@ impl Modbus {
     /// Read many bits
     ///
     /// This function shall read the status of the dest.len() bits (coils) to the address
-    /// addr of the remote device. The result of reading is stored in dest slice as u8
+    /// addr of the remote device. The result of reading is stored in dest slice as bools or u8
     /// set to 1 or 0.
     ///
     /// The function uses the Modbus function code 0x01 (read coil status).
     ///
-    pub fn read_bits(&self, addr: c_int, dest: &mut [u8]) -> ModbusResult
+    pub fn read_bits<B: Bit>(&self, addr: c_int, dest: &mut [B]) -> ModbusResult
     {
-        unsafe {
+        self.check_unicast()?;
+        let mut buf = vec![0u8; dest.len()];
+        let r = unsafe {
             cvt( modbus_sys::modbus_read_bits(self.handle,
-                                                 addr, dest.len() as c_int,
-                                                 dest.as_mut_ptr())
-                 )
-        }
+                                                 addr, buf.len() as c_int,
+                                                 buf.as_mut_ptr())
+                 )?
+        };
+        copy_bits(&buf, dest);
+        Ok(r)
+    }
+
+    /// Read many input bits
+    ///
+    /// This function shall read the content of the dest.len() input bits (discrete inputs) to
+    /// the address addr of the remote device. The result of reading is stored in dest slice as
+    /// bools or u8 set to 1 or 0.
+    ///
+    /// The function uses the Modbus function code 0x02 (read input status).
+    ///
+    pub fn read_input_bits<B: Bit>(&self, addr: c_int, dest: &mut [B]) -> ModbusResult
+    {
+        self.check_unicast()?;
+        let mut buf = vec![0u8; dest.len()];
+        let r = unsafe {
+            cvt( modbus_sys::modbus_read_input_bits(self.handle,
+                                                       addr, buf.len() as c_int,
+                                                       buf.as_mut_ptr())
+                 )?
+        };
+        copy_bits(&buf, dest);
+        Ok(r)
     }
 
     /// Write a single register
@@ -242,6 +1241,9 @@ impl Modbus {
     ///
     pub fn write_register(&self, reg_addr: c_int, value: c_int) -> ModbusResult
     {
+        if self.is_broadcast() {
+            return self.broadcast(&pdu_header(0x06, reg_addr, value), 1)
+        }
         unsafe {
             cvt( modbus_sys::modbus_write_register(self.handle, reg_addr, value) )
         }
@@ -256,11 +1258,50 @@ impl Modbus {
     ///
     pub fn write_registers(&self, addr: c_int, data: &[u16]) -> ModbusResult
     {
+        if self.is_broadcast() {
+            check_quantity(data.len(), modbus_sys::MODBUS_MAX_WRITE_REGISTERS)?;
+            let mut pdu = pdu_header(0x10, addr, data.len() as c_int);
+            pdu.push((data.len() * 2) as u8);
+            for value in data {
+                pdu.push((value >> 8) as u8);
+                pdu.push(*value as u8);
+            }
+            return self.broadcast(&pdu, data.len() as c_int)
+        }
         unsafe {
             cvt( modbus_sys::modbus_write_registers(self.handle, addr, data.len() as i32, data.as_ptr()) )
         }
     }
 
+    /// Modify a single register using masks
+    ///
+    /// This function shall modify the value of the holding register at the address addr of the
+    /// remote device using the algorithm:
+    ///
+    /// ```text
+    /// new value = (current value AND 'and') OR ('or' AND (NOT 'and'))
+    /// ```
+    ///
+    /// The modification is done by the remote device, so bits of a control word can be set or
+    /// cleared without racing with other writers.
+    ///
+    /// The function uses the Modbus function code 0x16 (mask single register). Needs libmodbus
+    /// 3.1.
+    ///
+    #[cfg(not(libmodbus_3_0))]
+    pub fn mask_write_register(&self, addr: c_int, and_mask: u16, or_mask: u16) -> ModbusResult
+    {
+        if self.is_broadcast() {
+            let mut pdu = pdu_header(0x16, addr, and_mask as c_int);
+            pdu.push((or_mask >> 8) as u8);
+            pdu.push(or_mask as u8);
+            return self.broadcast(&pdu, 1)
+        }
+        unsafe {
+            cvt( modbus_sys::modbus_mask_write_register(self.handle, addr, and_mask, or_mask) )
+        }
+    }
+
     /// Read many registers
     ///
     /// This function shall read the content of the dest.len() holding registers to
@@ -271,6 +1312,7 @@ impl Modbus {
     ///
     pub fn read_registers(&self, addr: c_int, dest: &mut [u16]) -> ModbusResult
     {
+        self.check_unicast()?;
         unsafe {
             cvt( modbus_sys::modbus_read_registers(self.handle, addr, dest.len() as i32, dest.as_mut_ptr()) )
         }
@@ -289,11 +1331,90 @@ impl Modbus {
     ///
     pub fn read_input_registers(&self, addr: c_int, dest: &mut [u16]) -> ModbusResult
     {
+        self.check_unicast()?;
         unsafe {
             cvt( modbus_sys::modbus_read_input_registers(self.handle, addr, dest.len() as i32, dest.as_mut_ptr()) )
         }
     }
 
+    /// Report server ID
+    ///
+    /// This function shall ask the remote device for its server ID, run indicator status and
+    /// additional device specific data.
+    ///
+    /// The function uses the Modbus function code 0x11 (report slave ID).
+    ///
+    pub fn report_server_id(&self) -> Result<ServerId, ModbusError>
+    {
+        self.check_unicast()?;
+
+        // The byte count of the response is a u8, so the data always fits
+        let mut dest = [0u8; 256];
+        let len = unsafe { cvt( report_slave_id(self.handle, &mut dest) )? };
+        // libmodbus returns the byte count of the response even if it filled dest up
+        let len = std::cmp::min(len as usize, dest.len());
+        if len < 2 {
+            return Err(ModbusError::BadData)
+        }
+
+        Ok(ServerId {
+            server_id: dest[0],
+            running: dest[1] == 0xFF,
+            data: dest[2..len].to_vec(),
+        })
+    }
+
+    /// Send a raw request
+    ///
+    /// This function shall send a request made of raw_req, the slave address followed by the PDU
+    /// (function code and data), completed with the header and checksum of the backend. This
+    /// allows function codes libmodbus does not implement, e.g. vendor specific ones. The
+    /// response must be read with `Modbus::receive_confirmation`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use modbus::Modbus;
+    ///
+    /// let addr = "127.0.0.1:1502".parse().unwrap();
+    /// let mb = Modbus::new_tcp(&addr);
+    /// mb.connect().unwrap();
+    ///
+    /// // Read 2 holding registers at address 10 of slave 255
+    /// mb.send_raw_request(&[0xFF, 0x03, 0x00, 0x0A, 0x00, 0x02]).unwrap();
+    /// let rsp = mb.receive_confirmation().unwrap();
+    /// let pdu = &rsp[mb.get_header_length().unwrap() as usize..];
+    /// ```
+    pub fn send_raw_request(&self, raw_req: &[u8]) -> ModbusResult
+    {
+        if raw_req.len() < 2 || raw_req.len() > modbus_sys::MODBUS_MAX_PDU_LENGTH as usize + 1 {
+            return Err(ModbusError::from_errno(libc::EINVAL))
+        }
+        // libmodbus only reads the request but does not declare it const
+        let mut req = raw_req.to_vec();
+        unsafe {
+            cvt( modbus_sys::modbus_send_raw_request(self.handle, req.as_mut_ptr(),
+                                                     req.len() as c_int) )
+        }
+    }
+
+    /// Receive a confirmation
+    ///
+    /// This function shall receive the response to a request sent with
+    /// `Modbus::send_raw_request` and return it whole, header and checksum included. The PDU
+    /// starts at the offset given by `Modbus::get_header_length`.
+    pub fn receive_confirmation(&self) -> Result<Vec<u8>, ModbusError>
+    {
+        let mut rsp = [0u8; MAX_ADU_LENGTH];
+        let len = unsafe {
+            cvt( modbus_sys::modbus_receive_confirmation(self.handle, rsp.as_mut_ptr()) )?
+        } as usize;
+        if len > rsp.len() || len <= self.get_header_length()? as usize {
+            return Err(ModbusError::BadData)
+        }
+        Ok(rsp[..len].to_vec())
+    }
+
     /// Write and read many registers in a single transaction
     ///
     /// This function shall write the content of the src.len()
@@ -306,6 +1427,7 @@ impl Modbus {
     pub fn write_and_read_registers(&self, write_addr: c_int, src: &[u16],
                                            read_addr: c_int, dest: &mut [u16]) -> ModbusResult
     {
+        self.check_unicast()?;
         unsafe {
             cvt( modbus_sys::modbus_write_and_read_registers(self.handle, write_addr, src.len() as i32, src.as_ptr(),
                                                                     read_addr, dest.len() as i32, dest.as_mut_ptr()) )
@@ -314,6 +1436,71 @@ impl Modbus {
 
 }
 
+#[cfg(all(feature = "libmodbus", not(libmodbus_3_0)))]
+unsafe fn report_slave_id(ctx: *mut modbus_sys::modbus_t, dest: &mut [u8; 256]) -> c_int
+{
+    modbus_sys::modbus_report_slave_id(ctx, dest.len() as c_int, dest.as_mut_ptr())
+}
+
+// libmodbus 3.0 does not bound dest, which holds the largest byte count anyway
+#[cfg(all(feature = "libmodbus", libmodbus_3_0))]
+unsafe fn report_slave_id(ctx: *mut modbus_sys::modbus_t, dest: &mut [u8; 256]) -> c_int
+{
+    modbus_sys::modbus_report_slave_id(ctx, dest.as_mut_ptr())
+}
+
+/// A context temporarily addressing another slave, see `Modbus::with_slave`
+#[cfg(all(feature = "libmodbus", not(libmodbus_3_0)))]
+pub struct SlaveGuard<'a> {
+    ctx: &'a Modbus,
+    previous: c_int,
+}
+
+#[cfg(all(feature = "libmodbus", not(libmodbus_3_0)))]
+impl<'a> Deref for SlaveGuard<'a> {
+    type Target = Modbus;
+
+    fn deref(&self) -> &Modbus
+    {
+        self.ctx
+    }
+}
+
+#[cfg(all(feature = "libmodbus", not(libmodbus_3_0)))]
+impl<'a> Drop for SlaveGuard<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            modbus_sys::modbus_set_slave(self.ctx.handle, self.previous);
+        }
+    }
+}
+
+#[cfg(feature = "libmodbus")]
+fn copy_bits<B: Bit>(src: &[u8], dest: &mut [B])
+{
+    for (d, &s) in dest.iter_mut().zip(src) {
+        *d = B::from_u8(s);
+    }
+}
+
+/// Function code, address and a 16 bit value, the start of most request PDUs
+#[cfg(feature = "libmodbus")]
+fn pdu_header(function: u8, addr: c_int, value: c_int) -> Vec<u8>
+{
+    vec![function, (addr >> 8) as u8, addr as u8, (value >> 8) as u8, value as u8]
+}
+
+#[cfg(feature = "libmodbus")]
+fn check_quantity(nb: usize, max: c_int) -> Result<(), ModbusError>
+{
+    if nb < 1 || nb > max as usize {
+        Err(ModbusError::TooManyData)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "libmodbus")]
 impl Drop for Modbus {
     fn drop(&mut self) {
         unsafe {
@@ -322,6 +1509,7 @@ impl Drop for Modbus {
     }
 }
 
+// Not Sync: concurrent requests would interleave on the connection, see `SharedModbus`
+#[cfg(feature = "libmodbus")]
 unsafe impl Send for Modbus { }
-unsafe impl Sync for Modbus { }
 
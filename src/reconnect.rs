@@ -0,0 +1,231 @@
+use std::cmp;
+use std::thread;
+use std::time::Duration;
+
+use libc::c_int;
+
+use crate::{Modbus, ModbusError, ModbusResult, ServerId, Bit};
+
+/// A client which survives link errors, e.g. a PLC rebooting
+///
+/// After a link error (closed connection, timeout...), the connection is closed and opened
+/// again, waiting longer and longer between failed attempts. Reads are then replayed with the
+/// same backoff, up to the maximum number of attempts; writes return the error, since the
+/// remote device may have applied them already.
+///
+/// A response timeout (`ETIMEDOUT`) is handled as a link error too: the late response could
+/// otherwise be read as the one of the next request, so a slow device gets its connection
+/// dropped and opened again. Raise the response timeout of the wrapped context for such devices.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use modbus::{Modbus, ReconnectingModbus};
+///
+/// let addr = "192.168.1.10:502".parse().unwrap();
+/// let mut mb = ReconnectingModbus::new(Modbus::new_tcp(&addr));
+/// mb.set_backoff(Duration::from_millis(100), Duration::from_secs(30));
+///
+/// let mut dest = [0u16; 10];
+/// loop {
+///     mb.read_registers(0, &mut dest).unwrap();
+///     std::thread::sleep(Duration::from_secs(1));
+/// }
+/// ```
+pub struct ReconnectingModbus {
+    ctx: Modbus,
+    connected: bool,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    max_attempts: u32,
+}
+
+impl ReconnectingModbus {
+    /// Wrap a context, which is connected on first use
+    pub fn new(ctx: Modbus) -> ReconnectingModbus
+    {
+        ReconnectingModbus {
+            ctx,
+            connected: false,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            max_attempts: 10,
+        }
+    }
+
+    /// Set the delay after the first failed connection attempt, doubled after each following
+    /// one up to max. Defaults to 100 ms and 10 s.
+    pub fn set_backoff(&mut self, initial: Duration, max: Duration)
+    {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+    }
+
+    /// Set how many connection attempts are made, and how many times a read is sent, before
+    /// giving up and returning the error, 10 by default
+    pub fn set_max_attempts(&mut self, max_attempts: u32)
+    {
+        self.max_attempts = cmp::max(max_attempts, 1);
+    }
+
+    /// Returns the wrapped context, e.g. to set its timeouts or slave
+    pub fn modbus(&self) -> &Modbus
+    {
+        &self.ctx
+    }
+
+    /// Connect, retrying with backoff
+    pub fn connect(&mut self) -> Result<(), ModbusError>
+    {
+        if self.connected {
+            self.ctx.close();
+            self.connected = false;
+        }
+
+        let mut delay = self.initial_backoff;
+        let mut attempts = 0;
+        loop {
+            match self.ctx.connect() {
+                Ok(_) => {
+                    self.connected = true;
+                    return Ok(())
+                }
+                Err(e) => {
+                    attempts += 1;
+                    if attempts >= self.max_attempts {
+                        return Err(e)
+                    }
+                    thread::sleep(delay);
+                    delay = cmp::min(delay * 2, self.max_backoff);
+                }
+            }
+        }
+    }
+
+    /// Close the connection
+    pub fn close(&mut self)
+    {
+        if self.connected {
+            self.ctx.close();
+            self.connected = false;
+        }
+    }
+
+    fn run<T, F>(&mut self, idempotent: bool, mut request: F) -> Result<T, ModbusError>
+        where F: FnMut(&Modbus) -> Result<T, ModbusError>
+    {
+        let mut delay = self.initial_backoff;
+        let mut attempts = 0;
+        loop {
+            if !self.connected {
+                self.connect()?;
+            }
+            match request(&self.ctx) {
+                Err(e) => {
+                    if !is_link_error(&e) {
+                        return Err(e)
+                    }
+                    // Connected again on the next attempt or call
+                    self.close();
+                    attempts += 1;
+                    if !idempotent || attempts >= self.max_attempts {
+                        return Err(e)
+                    }
+                    thread::sleep(delay);
+                    delay = cmp::min(delay * 2, self.max_backoff);
+                }
+                r => return r,
+            }
+        }
+    }
+
+    /// Read many bits, see `Modbus::read_bits`
+    pub fn read_bits<B: Bit>(&mut self, addr: c_int, dest: &mut [B]) -> ModbusResult
+    {
+        self.run(true, |ctx| ctx.read_bits(addr, dest))
+    }
+
+    /// Read many input bits, see `Modbus::read_input_bits`
+    pub fn read_input_bits<B: Bit>(&mut self, addr: c_int, dest: &mut [B]) -> ModbusResult
+    {
+        self.run(true, |ctx| ctx.read_input_bits(addr, dest))
+    }
+
+    /// Read many registers, see `Modbus::read_registers`
+    pub fn read_registers(&mut self, addr: c_int, dest: &mut [u16]) -> ModbusResult
+    {
+        self.run(true, |ctx| ctx.read_registers(addr, dest))
+    }
+
+    /// Read many input registers, see `Modbus::read_input_registers`
+    pub fn read_input_registers(&mut self, addr: c_int, dest: &mut [u16]) -> ModbusResult
+    {
+        self.run(true, |ctx| ctx.read_input_registers(addr, dest))
+    }
+
+    /// Report server ID, see `Modbus::report_server_id`
+    pub fn report_server_id(&mut self) -> Result<ServerId, ModbusError>
+    {
+        self.run(true, |ctx| ctx.report_server_id())
+    }
+
+    /// Write a single bit, see `Modbus::write_bit`
+    pub fn write_bit(&mut self, coil_addr: c_int, status: c_int) -> ModbusResult
+    {
+        self.run(false, |ctx| ctx.write_bit(coil_addr, status))
+    }
+
+    /// Write many bits, see `Modbus::write_bits`
+    pub fn write_bits<B: Bit>(&mut self, addr: c_int, data: &[B]) -> ModbusResult
+    {
+        self.run(false, |ctx| ctx.write_bits(addr, data))
+    }
+
+    /// Write a single register, see `Modbus::write_register`
+    pub fn write_register(&mut self, reg_addr: c_int, value: c_int) -> ModbusResult
+    {
+        self.run(false, |ctx| ctx.write_register(reg_addr, value))
+    }
+
+    /// Write many registers, see `Modbus::write_registers`
+    pub fn write_registers(&mut self, addr: c_int, data: &[u16]) -> ModbusResult
+    {
+        self.run(false, |ctx| ctx.write_registers(addr, data))
+    }
+
+    /// Modify a single register using masks, see `Modbus::mask_write_register`
+    #[cfg(not(libmodbus_3_0))]
+    pub fn mask_write_register(&mut self, addr: c_int, and_mask: u16, or_mask: u16)
+        -> ModbusResult
+    {
+        self.run(false, |ctx| ctx.mask_write_register(addr, and_mask, or_mask))
+    }
+
+    /// Write and read many registers in a single transaction, see
+    /// `Modbus::write_and_read_registers`
+    pub fn write_and_read_registers(&mut self, write_addr: c_int, src: &[u16],
+                                    read_addr: c_int, dest: &mut [u16]) -> ModbusResult
+    {
+        self.run(false, |ctx| ctx.write_and_read_registers(write_addr, src, read_addr, dest))
+    }
+}
+
+/// Errors after which the connection must be opened again
+fn is_link_error(e: &ModbusError) -> bool
+{
+    match *e {
+        ModbusError::Io(ref e) => matches!(e.raw_os_error(),
+            Some(libc::ECONNRESET) | Some(libc::ECONNREFUSED) | Some(libc::ECONNABORTED) |
+            Some(libc::EPIPE) | Some(libc::ENOTCONN) | Some(libc::EBADF) |
+            Some(libc::ETIMEDOUT) | Some(libc::EHOSTUNREACH) | Some(libc::ENETUNREACH) |
+            Some(libc::EIO)),
+        _ => false,
+    }
+}
+
+impl Drop for ReconnectingModbus {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
@@ -0,0 +1,232 @@
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use libc::c_int;
+
+use crate::{ModbusError, Parity, DataBits, StopBits};
+use crate::client::{io_error, ModbusClient};
+use crate::codec::{self, RTU_MAX_ADU_LENGTH};
+use crate::serial::{self, SerialPort};
+use crate::transport::{Adu, Transport};
+
+/// Modbus RTU written in Rust, over a serial port configured with termios
+///
+/// RTU frames carry no length: they are delimited by silent intervals on the line, computed from
+/// the baud rate and the character format as the specification requires. A frame ends when the
+/// line is silent for 3.5 character times (t3.5). A silence of more than 1.5 character times
+/// (t1.5) within a frame breaks it: the frame is discarded with `ModbusError::BadFrame`, as are
+/// frames too short or too long to be valid, while an invalid CRC is reported as
+/// `ModbusError::BadCrc`. Above 19200 bauds the fixed values of the specification, 750 µs and
+/// 1.75 ms, are used.
+///
+/// Each frame is sent after t3.5 of silence since the end of the previous one.
+///
+/// See `ModbusClient::new_rtu`.
+pub struct RtuTransport {
+    device: String,
+    baud: c_int,
+    parity: Parity,
+    data_bits: DataBits,
+    stop_bits: StopBits,
+    port: Option<SerialPort>,
+    char_time: Duration,
+    t1_5: Duration,
+    t3_5: Duration,
+    // t3.5 after the end of the last frame on the line
+    next_frame: Option<Instant>,
+}
+
+impl RtuTransport {
+    /// A transport over the serial port device, opened by `Transport::connect`, see
+    /// `Modbus::new_rtu`
+    pub fn new(device: &str, baud: c_int, parity: Parity,
+               data_bits: DataBits, stop_bits: StopBits) -> Result<RtuTransport, ModbusError>
+    {
+        if device.is_empty() || device.contains('\0') {
+            return Err(io_error(libc::EINVAL))
+        }
+        // Only the rates termios knows, rather than failing on connect
+        serial::speed(baud)?;
+
+        // Start bit, data bits, parity bit and stop bits
+        let bits = 1 + match data_bits {
+            DataBits::Five => 5,
+            DataBits::Six => 6,
+            DataBits::Seven => 7,
+            DataBits::Eight => 8,
+        } + if parity == Parity::None { 0 } else { 1 } + match stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+        let char_time = Duration::from_nanos(bits * 1_000_000_000 / baud as u64);
+        let (t1_5, t3_5) = if baud > 19200 {
+            (Duration::from_micros(750), Duration::from_micros(1750))
+        } else {
+            (char_time * 3 / 2, char_time * 7 / 2)
+        };
+
+        Ok(RtuTransport {
+            device: device.to_string(),
+            baud,
+            parity,
+            data_bits,
+            stop_bits,
+            port: None,
+            char_time,
+            t1_5,
+            t3_5,
+            next_frame: None,
+        })
+    }
+
+    /// The longest silence allowed between two characters of a frame, t1.5
+    pub fn inter_character_timeout(&self) -> Duration
+    {
+        self.t1_5
+    }
+
+    /// The silence delimiting frames, t3.5
+    pub fn inter_frame_delay(&self) -> Duration
+    {
+        self.t3_5
+    }
+
+    fn port(&mut self) -> Result<&mut SerialPort, ModbusError>
+    {
+        self.port.as_mut().ok_or_else(|| io_error(libc::EBADF))
+    }
+
+    /// Read the characters received within timeout, None if there are none
+    fn read_within(&mut self, buf: &mut [u8], timeout: Duration) -> Result<Option<usize>, ModbusError>
+    {
+        match self.port()?.read(buf, Some(timeout)) {
+            Ok(n) => Ok(Some(n)),
+            Err(ref e) if is_timeout(e) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+fn is_timeout(e: &io::Error) -> bool
+{
+    e.raw_os_error() == Some(libc::ETIMEDOUT)
+}
+
+impl Transport for RtuTransport {
+    fn connect(&mut self) -> Result<(), ModbusError>
+    {
+        let port = SerialPort::open(&self.device, self.baud, self.parity,
+                                    self.data_bits, self.stop_bits)?;
+        self.port = Some(port);
+        self.next_frame = None;
+        Ok(())
+    }
+
+    fn close(&mut self)
+    {
+        self.port = None;
+    }
+
+    fn header_length(&self) -> usize
+    {
+        1
+    }
+
+    fn frame_length(&self, adu: &Adu) -> usize
+    {
+        // The slave address, the PDU and the CRC
+        1 + adu.pdu.len() + 2
+    }
+
+    fn has_transaction_id(&self) -> bool
+    {
+        false
+    }
+
+    fn send(&mut self, adu: &Adu) -> Result<(), ModbusError>
+    {
+        if adu.pdu.is_empty() || adu.pdu.len() > codec::MAX_PDU_LENGTH {
+            return Err(io_error(libc::EINVAL))
+        }
+        let frame = codec::encode_rtu(adu.slave, &adu.pdu);
+
+        if let Some(next_frame) = self.next_frame {
+            let now = Instant::now();
+            if next_frame > now {
+                thread::sleep(next_frame - now);
+            }
+        }
+        let port = self.port()?;
+        port.write_frame(&frame)?;
+        // The port buffers the frame, which is on the line until it is all transmitted
+        self.next_frame = Some(Instant::now() + self.char_time * frame.len() as u32 + self.t3_5);
+        Ok(())
+    }
+
+    fn receive(&mut self, timeout: Option<Duration>) -> Result<Adu, ModbusError>
+    {
+        let mut adu = Vec::with_capacity(RTU_MAX_ADU_LENGTH);
+        let mut chunk = [0u8; RTU_MAX_ADU_LENGTH];
+
+        match self.port()?.read(&mut chunk, timeout) {
+            Ok(n) => adu.extend_from_slice(&chunk[..n]),
+            Err(ref e) if is_timeout(e) => return Err(io_error(libc::ETIMEDOUT)),
+            Err(e) => return Err(e.into()),
+        }
+
+        let mut broken = false;
+        loop {
+            // The characters of a frame follow each other within t1.5
+            if let Some(n) = self.read_within(&mut chunk, self.t1_5)? {
+                adu.extend_from_slice(&chunk[..n]);
+                adu.truncate(RTU_MAX_ADU_LENGTH + 1);
+                continue
+            }
+            // The frame ends unless a character is received before t3.5, which breaks it
+            match self.read_within(&mut chunk, self.t3_5 - self.t1_5)? {
+                Some(n) => {
+                    broken = true;
+                    adu.extend_from_slice(&chunk[..n]);
+                    adu.truncate(RTU_MAX_ADU_LENGTH + 1);
+                }
+                None => break,
+            }
+        }
+        // The line was just silent for t3.5
+        self.next_frame = None;
+
+        if broken || adu.len() < 4 || adu.len() > RTU_MAX_ADU_LENGTH {
+            return Err(ModbusError::BadFrame)
+        }
+        let frame = codec::decode_rtu(&adu)?;
+        Ok(Adu { slave: frame.slave, transaction_id: 0, pdu: frame.pdu.to_vec() })
+    }
+}
+
+impl ModbusClient<RtuTransport> {
+    /// Create a new Modbus RTU client, see `Modbus::new_rtu` and `RtuTransport`
+    ///
+    /// The device is opened by `ModbusClient::connect`, and the slave must be set before sending
+    /// requests.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use modbus::{ModbusClient, Parity, DataBits, StopBits};
+    ///
+    /// let mb = ModbusClient::new_rtu("/dev/ttyUSB0", 19200, Parity::Even,
+    ///                                DataBits::Eight, StopBits::One).unwrap();
+    /// mb.connect().unwrap();
+    /// mb.set_slave(1).unwrap();
+    ///
+    /// let mut dest = [0u16; 4];
+    /// mb.read_registers(0, &mut dest).unwrap();
+    /// ```
+    pub fn new_rtu(device: &str, baud: c_int, parity: Parity,
+                   data_bits: DataBits, stop_bits: StopBits)
+                   -> Result<ModbusClient<RtuTransport>, ModbusError>
+    {
+        Ok(ModbusClient::new(RtuTransport::new(device, baud, parity, data_bits, stop_bits)?))
+    }
+}
@@ -0,0 +1,191 @@
+//! Serial ports configured with termios, for the transports written in Rust
+
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::time::{Duration, Instant};
+
+use libc::{c_int, speed_t};
+
+use crate::{Parity, DataBits, StopBits};
+
+/// A serial port in raw mode, read with timeouts
+pub struct SerialPort {
+    file: File,
+}
+
+fn cvt_io(r: c_int) -> io::Result<c_int>
+{
+    if r == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(r)
+    }
+}
+
+/// The termios speed of a baud rate, `EINVAL` if the rate is not supported
+pub fn speed(baud: c_int) -> io::Result<speed_t>
+{
+    Ok(match baud {
+        1200 => libc::B1200,
+        2400 => libc::B2400,
+        4800 => libc::B4800,
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        230400 => libc::B230400,
+        _ => return Err(io::Error::from_raw_os_error(libc::EINVAL)),
+    })
+}
+
+impl SerialPort {
+    /// Open and configure a serial port
+    pub fn open(device: &str, baud: c_int, parity: Parity,
+                data_bits: DataBits, stop_bits: StopBits) -> io::Result<SerialPort>
+    {
+        let speed = speed(baud)?;
+        let path = CString::new(device).map_err(|_| io::Error::from_raw_os_error(libc::EINVAL))?;
+        let file = unsafe {
+            let fd = cvt_io(libc::open(path.as_ptr(),
+                                       libc::O_RDWR | libc::O_NOCTTY | libc::O_NONBLOCK |
+                                       libc::O_CLOEXEC))?;
+            File::from_raw_fd(fd)
+        };
+
+        unsafe {
+            let mut tios: libc::termios = mem::zeroed();
+            cvt_io(libc::tcgetattr(file.as_raw_fd(), &mut tios))?;
+            libc::cfmakeraw(&mut tios);
+            cvt_io(libc::cfsetispeed(&mut tios, speed))?;
+            cvt_io(libc::cfsetospeed(&mut tios, speed))?;
+
+            tios.c_cflag |= libc::CLOCAL | libc::CREAD;
+            tios.c_cflag &= !(libc::CSIZE | libc::PARENB | libc::PARODD | libc::CSTOPB);
+            tios.c_cflag |= match data_bits {
+                DataBits::Five => libc::CS5,
+                DataBits::Six => libc::CS6,
+                DataBits::Seven => libc::CS7,
+                DataBits::Eight => libc::CS8,
+            };
+            match parity {
+                Parity::None => {}
+                Parity::Even => tios.c_cflag |= libc::PARENB,
+                Parity::Odd => tios.c_cflag |= libc::PARENB | libc::PARODD,
+            }
+            if stop_bits == StopBits::Two {
+                tios.c_cflag |= libc::CSTOPB;
+            }
+            if parity != Parity::None {
+                tios.c_iflag |= libc::INPCK;
+            }
+            // Reads return immediately, timeouts are handled with poll
+            tios.c_cc[libc::VMIN] = 0;
+            tios.c_cc[libc::VTIME] = 0;
+            cvt_io(libc::tcsetattr(file.as_raw_fd(), libc::TCSANOW, &tios))?;
+        }
+
+        Ok(SerialPort { file })
+    }
+
+    /// Send a request frame
+    ///
+    /// What is left of a previous response, e.g. one received after its timeout, is discarded
+    /// first so that it cannot be taken for the response to this request.
+    pub fn write_frame(&mut self, frame: &[u8]) -> io::Result<()>
+    {
+        unsafe {
+            cvt_io(libc::tcflush(self.file.as_raw_fd(), libc::TCIFLUSH))?;
+        }
+        self.write_all(frame)
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()>
+    {
+        let mut written = 0;
+        while written < data.len() {
+            match self.file.write(&data[written..]) {
+                Ok(n) => written += n,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    self.wait(libc::POLLOUT, None)?;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the bytes available, waiting for at least one until the timeout, if any
+    ///
+    /// Returns `ETIMEDOUT` if nothing was received in time.
+    pub fn read(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> io::Result<usize>
+    {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut ready = false;
+        loop {
+            match self.file.read(buf) {
+                Ok(0) if ready => return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                                            "serial line hung up")),
+                Ok(0) => {}
+                Ok(n) => return Ok(n),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock ||
+                              e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+            let remaining = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(io::Error::from_raw_os_error(libc::ETIMEDOUT))
+                    }
+                    Some(deadline - now)
+                }
+                None => None,
+            };
+            ready = self.wait(libc::POLLIN, remaining)?;
+        }
+    }
+
+    /// Wait until the port is ready for the events, returns false on timeout
+    fn wait(&self, events: libc::c_short, timeout: Option<Duration>) -> io::Result<bool>
+    {
+        let mut fds = libc::pollfd { fd: self.file.as_raw_fd(), events, revents: 0 };
+        match poll(&mut fds, timeout) {
+            -1 => {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted { Ok(false) } else { Err(e) }
+            }
+            0 => Ok(false),
+            _ => Ok(true),
+        }
+    }
+}
+
+/// Poll with a timespec, the silences of RTU are shorter than a millisecond above 19200 bauds
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn poll(fds: &mut libc::pollfd, timeout: Option<Duration>) -> c_int
+{
+    let ts = timeout.map(|t| libc::timespec {
+        tv_sec: t.as_secs().min(libc::time_t::MAX as u64) as libc::time_t,
+        tv_nsec: t.subsec_nanos() as _,
+    });
+    let ts = ts.as_ref().map_or(std::ptr::null(), |ts| ts as *const libc::timespec);
+    unsafe { libc::ppoll(fds, 1, ts, std::ptr::null()) }
+}
+
+/// Poll at millisecond granularity where ppoll is missing: a silence of t1.5 within a frame may
+/// then only be detected after up to a millisecond more
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+fn poll(fds: &mut libc::pollfd, timeout: Option<Duration>) -> c_int
+{
+    let timeout = match timeout {
+        // Round up, poll would otherwise spin on sub-millisecond timeouts
+        Some(t) => t.as_nanos().div_ceil(1_000_000).min(c_int::MAX as u128) as c_int,
+        None => -1,
+    };
+    unsafe { libc::poll(fds, 1, timeout) }
+}
@@ -0,0 +1,348 @@
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use libc::c_int;
+
+use crate::{Modbus, ModbusMapping, ModbusError, ModbusResult, MAX_ADU_LENGTH};
+#[cfg(not(libmodbus_3_0))]
+use crate::RequestHandler;
+
+/// A Modbus TCP server answering the requests of its clients from a `ModbusMapping`
+///
+/// Clients can be served one at a time with `accept` and `serve`, or all together with
+/// `serve_clients`.
+///
+/// # Example
+///
+/// ```no_run
+/// use modbus::{ModbusServer, ModbusMapping};
+///
+/// let addr = "127.0.0.1:1502".parse().unwrap();
+/// let mut server = ModbusServer::listen(&addr, 1).unwrap();
+/// let mut mapping = ModbusMapping::new(500, 500, 500, 500);
+/// loop {
+///     server.accept().unwrap();
+///     server.serve(&mut mapping).unwrap();
+/// }
+/// ```
+pub struct ModbusServer {
+    ctx: Modbus,
+    pi: bool,
+    listener: TcpListener,
+    connected: bool,
+    max_connections: usize,
+    idle_timeout: Option<Duration>,
+    shutdown: Arc<Shutdown>,
+    wake: File,
+}
+
+struct Shutdown {
+    requested: AtomicBool,
+    wake: File,
+}
+
+/// Handle stopping a server running `ModbusServer::serve_clients` from another thread
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    shutdown: Arc<Shutdown>,
+}
+
+impl ShutdownHandle {
+    /// Ask the server to stop
+    ///
+    /// The server finishes the request it is answering, closes all client connections and
+    /// returns from `serve_clients`.
+    pub fn shutdown(&self)
+    {
+        self.shutdown.requested.store(true, Ordering::SeqCst);
+        let _ = (&self.shutdown.wake).write(&[1]);
+    }
+}
+
+struct Client {
+    stream: TcpStream,
+    last_activity: Instant,
+}
+
+impl ModbusServer {
+    /// Listen for Modbus TCP connections
+    ///
+    /// # Arguments
+    /// * `addr` - A TCP/IPv4 or TCP/IPv6 socket address to listen on. Port 0 picks any free
+    ///   port.
+    /// * `nb_connection` - Maximum number of pending connections
+    pub fn listen(addr: &SocketAddr, nb_connection: c_int) -> Result<ModbusServer, ModbusError>
+    {
+        let ctx = Modbus::new_tcp(addr);
+        let pi = addr.is_ipv6();
+        let socket = if pi {
+            ctx.tcp_pi_listen(nb_connection)?
+        } else {
+            ctx.tcp_listen(nb_connection)?
+        };
+        let listener = unsafe { TcpListener::from_raw_fd(socket) };
+
+        let mut fds = [0 as c_int; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+            return Err(io::Error::last_os_error().into())
+        }
+        let (wake, wake_write) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+        set_cloexec_nonblock(&wake)?;
+        set_cloexec_nonblock(&wake_write)?;
+
+        Ok(ModbusServer {
+            ctx,
+            pi,
+            listener,
+            connected: false,
+            max_connections: 32,
+            idle_timeout: None,
+            shutdown: Arc::new(Shutdown { requested: AtomicBool::new(false), wake: wake_write }),
+            wake,
+        })
+    }
+
+    /// Set the maximum number of clients `serve_clients` serves at once, 32 by default
+    ///
+    /// Clients connecting while the limit is reached are disconnected straight away.
+    pub fn set_max_connections(&mut self, max_connections: usize)
+    {
+        self.max_connections = max_connections;
+    }
+
+    /// Set how long `serve_clients` keeps a client connected without receiving a request from
+    /// it. None, the default, never disconnects idle clients.
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>)
+    {
+        self.idle_timeout = timeout;
+    }
+
+    /// Returns a handle which can stop `serve_clients` from another thread
+    pub fn shutdown_handle(&self) -> ShutdownHandle
+    {
+        ShutdownHandle { shutdown: self.shutdown.clone() }
+    }
+
+    /// Returns the address the server is listening on
+    pub fn local_addr(&self) -> io::Result<SocketAddr>
+    {
+        self.listener.local_addr()
+    }
+
+    /// Returns the context used to talk to the current client, e.g. to enable debug output or
+    /// to receive and reply by hand
+    pub fn modbus(&self) -> &Modbus
+    {
+        &self.ctx
+    }
+
+    /// Wait for a client to connect
+    ///
+    /// The connection to the previous client, if any, is closed.
+    pub fn accept(&mut self) -> Result<(), ModbusError>
+    {
+        self.close_client();
+        if self.pi {
+            self.ctx.tcp_pi_accept(self.listener.as_raw_fd())?;
+        } else {
+            self.ctx.tcp_accept(self.listener.as_raw_fd())?;
+        }
+        self.connected = true;
+        Ok(())
+    }
+
+    /// Answer the requests of the current client from mapping until it disconnects
+    pub fn serve(&mut self, mapping: &mut ModbusMapping) -> Result<(), ModbusError>
+    {
+        self.serve_with(|ctx, req| ctx.reply(req, mapping))
+    }
+
+    /// Answer the requests of the current client with handler until it disconnects
+    #[cfg(not(libmodbus_3_0))]
+    pub fn serve_handler<H: RequestHandler>(&mut self, handler: &mut H) -> Result<(), ModbusError>
+    {
+        self.serve_with(|ctx, req| ctx.reply_handler(req, handler))
+    }
+
+    fn serve_with<F>(&mut self, mut answer: F) -> Result<(), ModbusError>
+        where F: FnMut(&Modbus, &[u8]) -> ModbusResult
+    {
+        let mut query = [0u8; MAX_ADU_LENGTH];
+        loop {
+            match self.ctx.receive(&mut query) {
+                Ok(0) => continue,
+                Ok(len) => { answer(&self.ctx, &query[..len as usize])?; }
+                Err(ModbusError::Io(ref e)) if e.raw_os_error() == Some(libc::ECONNRESET) => {
+                    self.close_client();
+                    return Ok(())
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Accept clients and answer their requests from mapping, all clients being served
+    /// concurrently, until the server is stopped through a `ShutdownHandle`
+    ///
+    /// The sockets are multiplexed with `poll`; requests are answered one at a time in the
+    /// order they arrive, so every client sees the writes of the others. A client which sent
+    /// part of a request holds the others up until the rest arrives or the byte timeout of the
+    /// context expires, 500 ms by default: shorten it with `modbus().set_byte_timeout` when the
+    /// clients may be slow or misbehave.
+    ///
+    /// Once stopped, `serve_clients` can be called again.
+    pub fn serve_clients(&mut self, mapping: &mut ModbusMapping) -> Result<(), ModbusError>
+    {
+        self.serve_clients_with(|ctx, req| ctx.reply(req, mapping))
+    }
+
+    /// Accept clients and answer their requests with handler, like `serve_clients`
+    #[cfg(not(libmodbus_3_0))]
+    pub fn serve_clients_handler<H: RequestHandler>(&mut self, handler: &mut H)
+        -> Result<(), ModbusError>
+    {
+        self.serve_clients_with(|ctx, req| ctx.reply_handler(req, handler))
+    }
+
+    fn serve_clients_with<F>(&mut self, mut answer: F) -> Result<(), ModbusError>
+        where F: FnMut(&Modbus, &[u8]) -> ModbusResult
+    {
+        self.close_client();
+
+        let mut clients: Vec<Client> = Vec::new();
+        let mut query = [0u8; MAX_ADU_LENGTH];
+
+        let result = loop {
+            // Reset on the way out, so that the server can be run again
+            if self.shutdown.requested.swap(false, Ordering::SeqCst) {
+                self.drain_wake();
+                break Ok(())
+            }
+
+            let mut fds = Vec::with_capacity(clients.len() + 2);
+            fds.push(pollfd(self.wake.as_raw_fd()));
+            fds.push(pollfd(self.listener.as_raw_fd()));
+            for client in &clients {
+                fds.push(pollfd(client.stream.as_raw_fd()));
+            }
+
+            let timeout = match self.idle_timeout {
+                Some(idle) => clients.iter()
+                                     .map(|c| remaining_ms(c.last_activity + idle))
+                                     .min().unwrap_or(-1),
+                None => -1,
+            };
+
+            if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) } == -1 {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted {
+                    continue
+                }
+                break Err(e.into())
+            }
+
+            if fds[0].revents != 0 {
+                self.drain_wake();
+                continue
+            }
+
+            // Answer the clients, dropping the ones which disconnected or misbehaved
+            let mut closed = Vec::new();
+            for (i, fd) in fds[2..].iter().enumerate() {
+                if fd.revents == 0 {
+                    continue
+                }
+                let client = &mut clients[i];
+                client.last_activity = Instant::now();
+                self.ctx.set_socket(fd.fd)?;
+                let served = match self.ctx.receive(&mut query) {
+                    Ok(0) => Ok(0),
+                    Ok(len) => answer(&self.ctx, &query[..len as usize]),
+                    Err(e) => Err(e),
+                };
+                if served.is_err() {
+                    closed.push(i);
+                }
+            }
+            for i in closed.into_iter().rev() {
+                clients.remove(i);
+            }
+
+            if let Some(idle) = self.idle_timeout {
+                let now = Instant::now();
+                clients.retain(|c| c.last_activity + idle > now);
+            }
+
+            if fds[1].revents != 0 {
+                if let Ok((stream, _)) = self.listener.accept() {
+                    if clients.len() < self.max_connections {
+                        clients.push(Client { stream, last_activity: Instant::now() });
+                    }
+                }
+            }
+        };
+
+        // The client sockets are closed when dropped, do not leave the context pointing to one
+        let _ = self.ctx.set_socket(-1);
+        result
+    }
+
+    fn drain_wake(&mut self)
+    {
+        let mut buf = [0u8; 16];
+        while let Ok(n) = self.wake.read(&mut buf) {
+            if n == 0 {
+                break
+            }
+        }
+    }
+
+    fn close_client(&mut self)
+    {
+        if self.connected {
+            self.ctx.close();
+            self.connected = false;
+        }
+    }
+}
+
+impl Drop for ModbusServer {
+    fn drop(&mut self) {
+        self.close_client();
+    }
+}
+
+fn pollfd(fd: c_int) -> libc::pollfd
+{
+    libc::pollfd { fd, events: libc::POLLIN, revents: 0 }
+}
+
+fn remaining_ms(deadline: Instant) -> c_int
+{
+    let now = Instant::now();
+    if deadline <= now {
+        0
+    } else {
+        let left = deadline - now;
+        (left.as_secs() * 1000 + left.subsec_millis() as u64 + 1) as c_int
+    }
+}
+
+fn set_cloexec_nonblock(file: &File) -> io::Result<()>
+{
+    let fd = file.as_raw_fd();
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        if flags == -1 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) == -1 ||
+           libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) == -1 {
+            return Err(io::Error::last_os_error())
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use libc::c_int;
+
+use crate::{Modbus, ModbusError, ModbusResult, ServerId, Bit};
+
+/// A context which can be shared between threads
+///
+/// A libmodbus context owns a single connection, so `Modbus` is `Send` but not `Sync`: two
+/// threads sending requests at the same time would interleave their frames and read each
+/// other's responses. `SharedModbus` serializes the transactions through a mutex, each request
+/// holding the lock until its response has been received. Clones share the same context.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::thread;
+/// use modbus::{Modbus, SharedModbus};
+///
+/// let addr = "192.168.1.10:502".parse().unwrap();
+/// let mb = Modbus::new_tcp(&addr);
+/// mb.connect().unwrap();
+/// let mb = SharedModbus::new(mb);
+///
+/// let poller = {
+///     let mb = mb.clone();
+///     thread::spawn(move || {
+///         let mut dest = [0u16; 10];
+///         mb.read_registers(0, &mut dest).unwrap();
+///     })
+/// };
+/// mb.write_register(100, 1).unwrap();
+/// poller.join().unwrap();
+/// ```
+#[derive(Clone)]
+pub struct SharedModbus {
+    ctx: Arc<Mutex<Modbus>>,
+}
+
+impl SharedModbus {
+    /// Share a context
+    pub fn new(ctx: Modbus) -> SharedModbus
+    {
+        SharedModbus { ctx: Arc::new(Mutex::new(ctx)) }
+    }
+
+    /// Lock the context for several requests which must not be interleaved with other threads,
+    /// e.g. `send_raw_request` and `receive_confirmation`, or requests to a slave selected with
+    /// `Modbus::with_slave`
+    ///
+    /// A thread panicking while holding the lock leaves the context usable, so a poisoned lock
+    /// is not an error.
+    pub fn lock<'a>(&'a self) -> MutexGuard<'a, Modbus>
+    {
+        match self.ctx.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Read many bits, see `Modbus::read_bits`
+    pub fn read_bits<B: Bit>(&self, addr: c_int, dest: &mut [B]) -> ModbusResult
+    {
+        self.lock().read_bits(addr, dest)
+    }
+
+    /// Read many input bits, see `Modbus::read_input_bits`
+    pub fn read_input_bits<B: Bit>(&self, addr: c_int, dest: &mut [B]) -> ModbusResult
+    {
+        self.lock().read_input_bits(addr, dest)
+    }
+
+    /// Read many registers, see `Modbus::read_registers`
+    pub fn read_registers(&self, addr: c_int, dest: &mut [u16]) -> ModbusResult
+    {
+        self.lock().read_registers(addr, dest)
+    }
+
+    /// Read many input registers, see `Modbus::read_input_registers`
+    pub fn read_input_registers(&self, addr: c_int, dest: &mut [u16]) -> ModbusResult
+    {
+        self.lock().read_input_registers(addr, dest)
+    }
+
+    /// Report server ID, see `Modbus::report_server_id`
+    pub fn report_server_id(&self) -> Result<ServerId, ModbusError>
+    {
+        self.lock().report_server_id()
+    }
+
+    /// Write a single bit, see `Modbus::write_bit`
+    pub fn write_bit(&self, coil_addr: c_int, status: c_int) -> ModbusResult
+    {
+        self.lock().write_bit(coil_addr, status)
+    }
+
+    /// Write many bits, see `Modbus::write_bits`
+    pub fn write_bits<B: Bit>(&self, addr: c_int, data: &[B]) -> ModbusResult
+    {
+        self.lock().write_bits(addr, data)
+    }
+
+    /// Write a single register, see `Modbus::write_register`
+    pub fn write_register(&self, reg_addr: c_int, value: c_int) -> ModbusResult
+    {
+        self.lock().write_register(reg_addr, value)
+    }
+
+    /// Write many registers, see `Modbus::write_registers`
+    pub fn write_registers(&self, addr: c_int, data: &[u16]) -> ModbusResult
+    {
+        self.lock().write_registers(addr, data)
+    }
+
+    /// Modify a single register, see `Modbus::mask_write_register`
+    #[cfg(not(libmodbus_3_0))]
+    pub fn mask_write_register(&self, addr: c_int, and_mask: u16, or_mask: u16) -> ModbusResult
+    {
+        self.lock().mask_write_register(addr, and_mask, or_mask)
+    }
+
+    /// Write and read many registers in a single transaction, see
+    /// `Modbus::write_and_read_registers`
+    pub fn write_and_read_registers(&self, write_addr: c_int, src: &[u16],
+                                    read_addr: c_int, dest: &mut [u16]) -> ModbusResult
+    {
+        self.lock().write_and_read_registers(write_addr, src, read_addr, dest)
+    }
+}
@@ -0,0 +1,135 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+use crate::{ModbusError, MAX_ADU_LENGTH};
+use crate::client::io_error;
+use crate::codec;
+use crate::transport::{Adu, Transport};
+
+/// Modbus TCP written in Rust, over a `std::net::TcpStream`
+///
+/// See `ModbusClient::new_tcp`, and `TcpTransport::from_stream` for the connections accepted by a
+/// `TransportServer`.
+pub struct TcpTransport {
+    addr: SocketAddr,
+    stream: Option<TcpStream>,
+    buf: Vec<u8>,
+}
+
+impl TcpTransport {
+    /// A transport to the server at addr, connected by `Transport::connect`
+    pub fn new(addr: &SocketAddr) -> TcpTransport
+    {
+        TcpTransport {
+            addr: *addr,
+            stream: None,
+            buf: Vec::with_capacity(2 * MAX_ADU_LENGTH),
+        }
+    }
+
+    /// A transport over a connected stream, e.g. accepted by a server
+    pub fn from_stream(stream: TcpStream) -> io::Result<TcpTransport>
+    {
+        stream.set_nodelay(true)?;
+        Ok(TcpTransport {
+            addr: stream.peer_addr()?,
+            stream: Some(stream),
+            buf: Vec::with_capacity(2 * MAX_ADU_LENGTH),
+        })
+    }
+
+    fn stream(&mut self) -> Result<&mut TcpStream, ModbusError>
+    {
+        self.stream.as_mut().ok_or_else(|| io_error(libc::EBADF))
+    }
+}
+
+impl Transport for TcpTransport {
+    fn connect(&mut self) -> Result<(), ModbusError>
+    {
+        let stream = TcpStream::connect(self.addr)?;
+        stream.set_nodelay(true)?;
+        self.stream = Some(stream);
+        self.buf.clear();
+        Ok(())
+    }
+
+    fn close(&mut self)
+    {
+        self.stream = None;
+        self.buf.clear();
+    }
+
+    fn header_length(&self) -> usize
+    {
+        codec::MBAP_HEADER_LENGTH
+    }
+
+    fn encode_header(&self, adu: &Adu) -> Vec<u8>
+    {
+        let mut frame = codec::encode_tcp(adu.transaction_id, adu.slave, &adu.pdu);
+        frame.truncate(codec::MBAP_HEADER_LENGTH);
+        frame
+    }
+
+    fn has_transaction_id(&self) -> bool
+    {
+        true
+    }
+
+    fn send(&mut self, adu: &Adu) -> Result<(), ModbusError>
+    {
+        let frame = codec::encode_tcp(adu.transaction_id, adu.slave, &adu.pdu);
+        self.stream()?.write_all(&frame)?;
+        Ok(())
+    }
+
+    fn receive(&mut self, timeout: Option<Duration>) -> Result<Adu, ModbusError>
+    {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut chunk = [0u8; MAX_ADU_LENGTH];
+        loop {
+            match codec::tcp_frame_length(&self.buf) {
+                Ok(Some(len)) if self.buf.len() >= len => {
+                    let frame: Vec<u8> = self.buf.drain(..len).collect();
+                    let frame = codec::decode_tcp(&frame)?;
+                    return Ok(Adu {
+                        slave: frame.unit_id,
+                        transaction_id: frame.transaction_id,
+                        pdu: frame.pdu.to_vec(),
+                    })
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    // Lost track of the frames, nothing more can be read from this stream
+                    self.buf.clear();
+                    return Err(e)
+                }
+            }
+
+            let remaining = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(io_error(libc::ETIMEDOUT))
+                    }
+                    Some(deadline - now)
+                }
+                None => None,
+            };
+            let stream = self.stream()?;
+            stream.set_read_timeout(remaining)?;
+            match stream.read(&mut chunk) {
+                Ok(0) => return Err(io_error(libc::ECONNRESET)),
+                Ok(n) => self.buf.extend_from_slice(&chunk[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock ||
+                              e.kind() == io::ErrorKind::TimedOut => {
+                    return Err(io_error(libc::ETIMEDOUT))
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
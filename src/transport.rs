@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use crate::ModbusError;
+
+/// A frame exchanged over a transport, its PDU with the addressing of its header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Adu {
+    /// Slave address, or unit identifier of Modbus TCP
+    pub slave: u8,
+    /// Transaction identifier of Modbus TCP, always 0 on transports without one
+    pub transaction_id: u16,
+    /// The function code and data
+    pub pdu: Vec<u8>,
+}
+
+/// A link carrying Modbus frames, e.g. a TCP connection
+///
+/// A transport frames the ADUs it sends with the header and checksum of its protocol, and checks
+/// and strips them from the frames it receives. `ModbusClient` implements the Modbus functions
+/// over any transport and `TransportServer` answers requests received over any transport, so a
+/// link libmodbus does not know, e.g. a tunnel, only needs to move frames.
+///
+/// The transports of the crate are `TcpTransport`, `UdpTransport`, `RtuTransport`,
+/// `AsciiTransport` and, for tests, the in-memory `ChannelTransport`.
+pub trait Transport {
+    /// Open the link
+    fn connect(&mut self) -> Result<(), ModbusError>;
+
+    /// Close the link
+    fn close(&mut self);
+
+    /// Length of the header before the PDU of a frame, e.g. 7 for the MBAP header of Modbus TCP
+    fn header_length(&self) -> usize;
+
+    /// The header of adu, `header_length` bytes, as it is framed on the link
+    ///
+    /// `ModbusClient::receive_confirmation` returns it before the PDU. The default is the slave
+    /// address, the header of serial lines.
+    fn encode_header(&self, adu: &Adu) -> Vec<u8>
+    {
+        vec![adu.slave]
+    }
+
+    /// Length of the frame of adu on the link, with its header and checksum
+    ///
+    /// `ModbusClient::send_raw_request` returns it, as libmodbus does. The default is the header
+    /// followed by the PDU, without a checksum.
+    fn frame_length(&self, adu: &Adu) -> usize
+    {
+        self.header_length() + adu.pdu.len()
+    }
+
+    /// Whether frames carry a transaction identifier
+    ///
+    /// Otherwise the frames received have a transaction identifier of 0, and the first frame
+    /// received after a request is its response.
+    fn has_transaction_id(&self) -> bool;
+
+    /// Send a frame
+    fn send(&mut self, adu: &Adu) -> Result<(), ModbusError>;
+
+    /// Receive a frame, waiting for its start at most timeout, or forever with None
+    ///
+    /// Fails with `ETIMEDOUT` if no frame starts in time.
+    fn receive(&mut self, timeout: Option<Duration>) -> Result<Adu, ModbusError>;
+}
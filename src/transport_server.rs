@@ -0,0 +1,124 @@
+use std::time::Duration;
+
+use libc::c_int;
+
+use crate::{ModbusError, ModbusResult, RequestHandler, MODBUS_BROADCAST_ADDRESS, MODBUS_TCP_SLAVE};
+use crate::client::io_error;
+use crate::handler::respond;
+use crate::transport::{Adu, Transport};
+
+/// A Modbus server written in Rust, answering the requests received over any `Transport` with a
+/// `RequestHandler`
+///
+/// It is the counterpart of `ModbusClient`, and does not need libmodbus. Over transports with a
+/// transaction identifier, e.g. a TCP connection, every unit identifier is answered unless a
+/// slave is set. On serial lines only the requests to the slave set are answered, and broadcast
+/// requests are handled without a response.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::net::TcpListener;
+/// use modbus::{TransportServer, TcpTransport, RequestHandler, Exception};
+///
+/// struct Registers([u16; 16]);
+///
+/// impl RequestHandler for Registers {
+///     fn read_holding_registers(&mut self, addr: u16, dest: &mut [u16]) -> Result<(), Exception>
+///     {
+///         let addr = addr as usize;
+///         let values = self.0.get(addr..addr + dest.len()).ok_or(Exception::IllegalDataAddress)?;
+///         dest.copy_from_slice(values);
+///         Ok(())
+///     }
+/// }
+///
+/// let listener = TcpListener::bind("127.0.0.1:1502").unwrap();
+/// let mut registers = Registers([0; 16]);
+/// for stream in listener.incoming() {
+///     let mut server = TransportServer::new(TcpTransport::from_stream(stream.unwrap()).unwrap());
+///     server.serve(&mut registers).unwrap();
+/// }
+/// ```
+pub struct TransportServer<T: Transport> {
+    transport: T,
+    slave: Option<u8>,
+}
+
+impl<T: Transport> TransportServer<T> {
+    /// Create a server answering the requests received over transport, which must be connected
+    pub fn new(transport: T) -> TransportServer<T>
+    {
+        TransportServer {
+            transport,
+            slave: None,
+        }
+    }
+
+    /// Returns the transport, e.g. to change its settings
+    pub fn transport(&mut self) -> &mut T
+    {
+        &mut self.transport
+    }
+
+    /// Set the slave address of the server, between 1 and 247, or `MODBUS_TCP_SLAVE` (255)
+    ///
+    /// Requests to other slaves are then ignored.
+    pub fn set_slave(&mut self, slave: c_int) -> ModbusResult
+    {
+        if slave < 1 || (slave > 247 && slave != MODBUS_TCP_SLAVE) {
+            return Err(io_error(libc::EINVAL))
+        }
+        self.slave = Some(slave as u8);
+        Ok(0)
+    }
+
+    /// Get the slave address of the server, None if it answers every slave address
+    pub fn slave(&self) -> Option<c_int>
+    {
+        self.slave.map(|slave| slave as c_int)
+    }
+
+    /// Receive a request, waiting for it at most timeout or forever with None, and answer it with
+    /// handler
+    ///
+    /// Fails with `ETIMEDOUT` if no request is received in time.
+    pub fn serve_request<H: RequestHandler + ?Sized>(&mut self, handler: &mut H,
+                                                     timeout: Option<Duration>)
+        -> Result<(), ModbusError>
+    {
+        let req = self.transport.receive(timeout)?;
+        // Nobody answers a broadcast on a serial line
+        let broadcast = !self.transport.has_transaction_id() &&
+                        req.slave as c_int == MODBUS_BROADCAST_ADDRESS;
+        match self.slave {
+            Some(slave) if req.slave != slave && !broadcast => return Ok(()),
+            _ => {}
+        }
+
+        let pdu = respond(&req.pdu, handler);
+        if broadcast {
+            return Ok(())
+        }
+        self.transport.send(&Adu { slave: req.slave, transaction_id: req.transaction_id, pdu })
+    }
+
+    /// Answer the requests with handler until the client disconnects
+    ///
+    /// On serial lines, frames which are malformed or fail their checksum are skipped. Other
+    /// transports lose track of the frames, so these errors are returned.
+    pub fn serve<H: RequestHandler + ?Sized>(&mut self, handler: &mut H) -> Result<(), ModbusError>
+    {
+        loop {
+            match self.serve_request(handler, None) {
+                Ok(()) => {}
+                Err(ModbusError::BadData) | Err(ModbusError::BadCrc) | Err(ModbusError::BadFrame)
+                    if !self.transport.has_transaction_id() => {}
+                Err(ModbusError::Io(ref e)) if e.raw_os_error() == Some(libc::ECONNRESET) => {
+                    return Ok(())
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
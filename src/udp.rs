@@ -0,0 +1,281 @@
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::{ModbusError, MAX_ADU_LENGTH, MODBUS_TCP_SLAVE};
+use crate::client::{io_error, ModbusClient};
+use crate::codec;
+use crate::transport::{Adu, Transport};
+
+/// Modbus UDP written in Rust: MBAP frames, one per datagram
+///
+/// libmodbus does not implement UDP. A client transport, created by `UdpTransport::new`, sends
+/// its requests to one server and only receives the datagrams of that server. As datagrams may
+/// be lost, a read request is sent again every retry interval while its response is awaited, up
+/// to the number of retries; `ModbusClient` matches the responses by transaction identifier, so
+/// duplicate responses to a retried request are skipped. Since a retried write may be executed
+/// twice by the server, writes are only retried once enabled by `set_retry_writes`.
+///
+/// A server transport, created by `UdpTransport::bind`, receives requests from any client and
+/// sends each response to the client of the last request, see `TransportServer`.
+///
+/// Datagrams which do not hold exactly one valid frame are dropped.
+pub struct UdpTransport {
+    addr: SocketAddr,
+    server: bool,
+    socket: Option<UdpSocket>,
+    peer: Option<SocketAddr>,
+    retries: u32,
+    retry_interval: Duration,
+    retry_writes: bool,
+    // The last request of a client, sent again at retry_at while retries_left is not 0, until
+    // a response with its transaction identifier arrives
+    request: Option<Vec<u8>>,
+    transaction_id: u16,
+    retries_left: u32,
+    retry_at: Instant,
+}
+
+impl UdpTransport {
+    /// A client transport to the server at addr, its socket opened by `Transport::connect`
+    ///
+    /// Requests are retried twice, every 150 ms.
+    pub fn new(addr: &SocketAddr) -> UdpTransport
+    {
+        UdpTransport {
+            addr: *addr,
+            server: false,
+            socket: None,
+            peer: None,
+            retries: 2,
+            retry_interval: Duration::from_millis(150),
+            retry_writes: false,
+            request: None,
+            transaction_id: 0,
+            retries_left: 0,
+            retry_at: Instant::now(),
+        }
+    }
+
+    /// A server transport receiving the requests sent to addr
+    pub fn bind(addr: &SocketAddr) -> io::Result<UdpTransport>
+    {
+        let mut transport = UdpTransport::new(addr);
+        transport.server = true;
+        transport.socket = Some(UdpSocket::bind(addr)?);
+        Ok(transport)
+    }
+
+    /// The local address of the socket, e.g. to find the port a server bound to port 0 listens on
+    pub fn local_addr(&self) -> Result<SocketAddr, ModbusError>
+    {
+        let socket = self.socket.as_ref().ok_or_else(|| io_error(libc::EBADF))?;
+        Ok(socket.local_addr()?)
+    }
+
+    /// The number of times a request is sent again when its response does not arrive
+    pub fn retries(&self) -> u32
+    {
+        self.retries
+    }
+
+    /// Set the number of times a request is sent again, 2 by default, 0 to disable retries
+    pub fn set_retries(&mut self, retries: u32)
+    {
+        self.retries = retries;
+    }
+
+    /// The time waited for a response before the request is sent again
+    pub fn retry_interval(&self) -> Duration
+    {
+        self.retry_interval
+    }
+
+    /// Set the time waited for a response before the request is sent again, 150 ms by default
+    ///
+    /// The interval must not be zero. Requests are never retried after the response timeout of
+    /// the client.
+    pub fn set_retry_interval(&mut self, interval: Duration) -> Result<(), ModbusError>
+    {
+        if interval == Duration::from_secs(0) {
+            return Err(io_error(libc::EINVAL))
+        }
+        self.retry_interval = interval;
+        Ok(())
+    }
+
+    /// Whether the requests which write are retried too
+    pub fn retry_writes(&self) -> bool
+    {
+        self.retry_writes
+    }
+
+    /// Retry the requests which write, false by default
+    ///
+    /// Only enable it when the writes are idempotent, or when writing twice is harmless: the
+    /// server executes a write again when its response rather than the request was lost.
+    pub fn set_retry_writes(&mut self, retry_writes: bool)
+    {
+        self.retry_writes = retry_writes;
+    }
+
+    fn socket(&self) -> Result<&UdpSocket, ModbusError>
+    {
+        self.socket.as_ref().ok_or_else(|| io_error(libc::EBADF))
+    }
+}
+
+impl Transport for UdpTransport {
+    /// Open the socket of a client, or bind a server closed by `Transport::close` again
+    fn connect(&mut self) -> Result<(), ModbusError>
+    {
+        let socket = if self.server {
+            UdpSocket::bind(self.addr)?
+        } else {
+            let any = match self.addr {
+                SocketAddr::V4(_) => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+                SocketAddr::V6(_) => SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0),
+            };
+            let socket = UdpSocket::bind(any)?;
+            socket.connect(self.addr)?;
+            socket
+        };
+        self.socket = Some(socket);
+        self.peer = None;
+        self.request = None;
+        Ok(())
+    }
+
+    fn close(&mut self)
+    {
+        self.socket = None;
+        self.peer = None;
+        self.request = None;
+    }
+
+    fn header_length(&self) -> usize
+    {
+        codec::MBAP_HEADER_LENGTH
+    }
+
+    fn encode_header(&self, adu: &Adu) -> Vec<u8>
+    {
+        let mut frame = codec::encode_tcp(adu.transaction_id, adu.slave, &adu.pdu);
+        frame.truncate(codec::MBAP_HEADER_LENGTH);
+        frame
+    }
+
+    fn has_transaction_id(&self) -> bool
+    {
+        true
+    }
+
+    fn send(&mut self, adu: &Adu) -> Result<(), ModbusError>
+    {
+        let datagram = codec::encode_tcp(adu.transaction_id, adu.slave, &adu.pdu);
+        if self.server {
+            let peer = self.peer.ok_or_else(|| io_error(libc::EDESTADDRREQ))?;
+            self.socket()?.send_to(&datagram, peer)?;
+        } else {
+            self.socket()?.send(&datagram)?;
+            self.request = Some(datagram);
+            self.transaction_id = adu.transaction_id;
+            self.retries_left = if self.retry_writes || is_read(&adu.pdu) { self.retries } else { 0 };
+            self.retry_at = Instant::now() + self.retry_interval;
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self, timeout: Option<Duration>) -> Result<Adu, ModbusError>
+    {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut datagram = [0u8; 2 * MAX_ADU_LENGTH];
+        loop {
+            let now = Instant::now();
+            let mut wait = match deadline {
+                Some(deadline) if now >= deadline => {
+                    // The client gave up, a later receive must not send the request again
+                    self.request = None;
+                    return Err(io_error(libc::ETIMEDOUT))
+                }
+                Some(deadline) => Some(deadline - now),
+                None => None,
+            };
+            if self.request.is_some() && self.retries_left > 0 {
+                if now >= self.retry_at {
+                    // The request or its response was lost
+                    if let Some(ref request) = self.request {
+                        self.socket()?.send(request)?;
+                    }
+                    self.retries_left -= 1;
+                    self.retry_at = now + self.retry_interval;
+                    continue
+                }
+                let retry = self.retry_at - now;
+                wait = Some(wait.map_or(retry, |wait| wait.min(retry)));
+            }
+
+            let socket = self.socket()?;
+            socket.set_read_timeout(wait)?;
+            let (n, from) = match socket.recv_from(&mut datagram) {
+                Ok(received) => received,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock ||
+                              e.kind() == io::ErrorKind::TimedOut ||
+                              e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let frame = match codec::decode_tcp(&datagram[..n]) {
+                Ok(frame) => frame,
+                Err(_) => continue,
+            };
+            if self.server {
+                self.peer = Some(from);
+            } else if frame.transaction_id == self.transaction_id {
+                self.request = None;
+            }
+            return Ok(Adu {
+                slave: frame.unit_id,
+                transaction_id: frame.transaction_id,
+                pdu: frame.pdu.to_vec(),
+            })
+        }
+    }
+}
+
+/// Whether the request only reads, so that executing it twice is harmless
+fn is_read(pdu: &[u8]) -> bool
+{
+    match pdu.first() {
+        Some(&function) => (0x01..=0x04).contains(&function) || function == 0x11 || function == 0x2B,
+        None => false,
+    }
+}
+
+impl ModbusClient<UdpTransport> {
+    /// Create a new Modbus UDP client to the server at addr, see `UdpTransport`
+    ///
+    /// Requests are sent to the unit identifier `MODBUS_TCP_SLAVE` (255) by default.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use modbus::ModbusClient;
+    ///
+    /// let addr = "192.168.1.10:502".parse().unwrap();
+    /// let mb = ModbusClient::new_udp(&addr);
+    /// mb.connect().unwrap();
+    /// mb.transport().set_retries(4);
+    /// mb.transport().set_retry_interval(Duration::from_millis(200)).unwrap();
+    /// mb.set_response_timeout(Duration::from_secs(1)).unwrap();
+    ///
+    /// let mut dest = [0u16; 4];
+    /// mb.read_registers(0, &mut dest).unwrap();
+    /// ```
+    pub fn new_udp(addr: &SocketAddr) -> ModbusClient<UdpTransport>
+    {
+        let client = ModbusClient::new(UdpTransport::new(addr));
+        client.set_slave(MODBUS_TCP_SLAVE).unwrap();
+        client
+    }
+}
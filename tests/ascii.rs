@@ -0,0 +1,272 @@
+extern crate modbus;
+
+mod common;
+
+use std::io::{self, Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use modbus::{ModbusAscii, AsciiTransport, ModbusError, Exception, Parity, DataBits, StopBits,
+             Adu, Transport, MODBUS_BROADCAST_ADDRESS};
+use modbus::codec::{self, AsciiFrame};
+use common::Pty;
+
+fn connected_ascii(pty: &Pty) -> ModbusAscii
+{
+    let mb = ModbusAscii::new_ascii(&pty.slave_name, 9600, Parity::Even,
+                                    DataBits::Seven, StopBits::One).unwrap();
+    mb.connect().unwrap();
+    mb.set_slave(1).unwrap();
+    mb
+}
+
+/// Read a request from the master side of the pty, up to CR LF
+fn read_frame<R: Read>(master: &mut R) -> Vec<u8>
+{
+    let mut frame = Vec::new();
+    let mut byte = [0u8; 1];
+    while !frame.ends_with(b"\r\n") {
+        master.read_exact(&mut byte).unwrap();
+        frame.push(byte[0]);
+    }
+    frame
+}
+
+fn is_timeout(r: Result<i32, ModbusError>) -> bool
+{
+    match r {
+        Err(ModbusError::Io(ref e)) => e.kind() == io::ErrorKind::TimedOut,
+        _ => false,
+    }
+}
+
+#[test]
+fn test_ascii_framing() {
+    // Read 2 holding registers at 0 of slave 1, and its response
+    assert_eq!(codec::lrc(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x02]), 0xFA);
+    assert_eq!(codec::encode_ascii(1, &[0x03, 0x00, 0x00, 0x00, 0x02]),
+               b":010300000002FA\r\n".to_vec());
+    assert_eq!(codec::decode_ascii(b":01030400010002F5\r\n").unwrap(),
+               AsciiFrame { slave: 1, pdu: vec![0x03, 0x04, 0x00, 0x01, 0x00, 0x02] });
+
+    // Lower case hexadecimal is accepted
+    assert_eq!(codec::decode_ascii(b":110600010003e5\r\n").unwrap(),
+               AsciiFrame { slave: 0x11, pdu: vec![0x06, 0x00, 0x01, 0x00, 0x03] });
+
+    match codec::decode_ascii(b":010300000002FB\r\n") {
+        Err(ModbusError::BadCrc) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    let malformed: &[&[u8]] = &[
+        b"010300000002FA\r\n",     // No colon
+        b":010300000002FA\n",      // No CR
+        b":010300000002FA",        // No terminator
+        b":01030000002FA\r\n",     // Odd number of digits
+        b":01030000000GF9\r\n",    // Not hexadecimal
+        b":01FF\r\n",              // Too short
+    ];
+    for adu in malformed {
+        match codec::decode_ascii(adu) {
+            Err(ModbusError::BadData) => {}
+            r => panic!("unexpected result {:?} for {:?}", r, adu),
+        }
+    }
+
+    let pdu = [0x10u8; 253];
+    let adu = codec::encode_ascii(0xF7, &pdu);
+    assert_eq!(adu.len(), codec::ASCII_MAX_ADU_LENGTH);
+    assert_eq!(codec::decode_ascii(&adu).unwrap().pdu, pdu.to_vec());
+}
+
+#[test]
+fn test_new_ascii_rejects_invalid_settings() {
+    assert!(ModbusAscii::new_ascii("/dev/ttyS0", 0, Parity::None, DataBits::Seven, StopBits::One).is_err());
+    assert!(ModbusAscii::new_ascii("/dev/tty\0S0", 9600, Parity::None, DataBits::Seven, StopBits::One).is_err());
+
+    let mb = ModbusAscii::new_ascii("/dev/ttyS0", 9600, Parity::None, DataBits::Seven, StopBits::One).unwrap();
+    assert_eq!(mb.slave(), None);
+    assert!(mb.set_slave(248).is_err());
+    mb.set_slave(247).unwrap();
+    assert_eq!(mb.slave(), Some(247));
+    assert!(mb.set_response_timeout(Duration::from_secs(0)).is_err());
+    assert_eq!(mb.get_byte_timeout().unwrap(), Some(Duration::from_secs(1)));
+
+    // Not connected
+    let mut dest = [0u16; 1];
+    assert!(mb.read_registers(0, &mut dest).is_err());
+}
+
+#[test]
+fn test_ascii_pdu_too_long_pty() {
+    let pty = Pty::open();
+    let mut transport = AsciiTransport::new(&pty.slave_name, 9600, Parity::Even,
+                                            DataBits::Seven, StopBits::One).unwrap();
+    transport.connect().unwrap();
+
+    let adu = Adu { slave: 1, transaction_id: 0, pdu: vec![0x10; codec::MAX_PDU_LENGTH + 1] };
+    match transport.send(&adu) {
+        Err(ModbusError::TooManyData) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    transport.close();
+}
+
+#[test]
+fn test_ascii_read_registers_pty() {
+    let pty = Pty::open();
+    let mb = connected_ascii(&pty);
+
+    let mut master = pty.master.try_clone().unwrap();
+    let device = thread::spawn(move || {
+        assert_eq!(read_frame(&mut master), b":010300000002FA\r\n".to_vec());
+        // Noise before the frame, then the frame in pieces
+        master.write_all(b"\0\xff").unwrap();
+        for piece in &[&b":0103"[..], b"040001", b"0002F5\r", b"\n"] {
+            master.write_all(piece).unwrap();
+            thread::sleep(Duration::from_millis(20));
+        }
+    });
+
+    let mut dest = [0u16; 2];
+    assert_eq!(mb.read_registers(0, &mut dest).unwrap(), 2);
+    assert_eq!(dest, [1, 2]);
+
+    device.join().unwrap();
+    mb.close();
+}
+
+#[test]
+fn test_ascii_operations_pty() {
+    let pty = Pty::open();
+    let mb = connected_ascii(&pty);
+
+    let mut master = pty.master.try_clone().unwrap();
+    let device = thread::spawn(move || {
+        // Write single register, echoed
+        let req = read_frame(&mut master);
+        assert_eq!(req, codec::encode_ascii(1, &[0x06, 0x00, 0x01, 0x00, 0x03]));
+        master.write_all(&req).unwrap();
+
+        // Write multiple coils
+        let req = read_frame(&mut master);
+        assert_eq!(req, codec::encode_ascii(1, &[0x0F, 0x00, 0x13, 0x00, 0x0A, 2, 0xCD, 0x01]));
+        master.write_all(&codec::encode_ascii(1, &[0x0F, 0x00, 0x13, 0x00, 0x0A])).unwrap();
+
+        // Read coils
+        read_frame(&mut master);
+        master.write_all(&codec::encode_ascii(1, &[0x01, 1, 0x05])).unwrap();
+
+        // Exception
+        read_frame(&mut master);
+        master.write_all(b":0183027A\r\n").unwrap();
+
+        // Report server ID
+        assert_eq!(read_frame(&mut master), b":0111EE\r\n".to_vec());
+        master.write_all(&codec::encode_ascii(1, &[0x11, 3, 0x42, 0xFF, 0x07])).unwrap();
+    });
+
+    assert_eq!(mb.write_register(1, 3).unwrap(), 1);
+    assert_eq!(mb.write_bits(0x13, &[1u8, 0, 1, 1, 0, 0, 1, 1, 1, 0]).unwrap(), 10);
+
+    let mut bits = [false; 3];
+    assert_eq!(mb.read_bits(0, &mut bits).unwrap(), 3);
+    assert_eq!(bits, [true, false, true]);
+
+    let mut dest = [0u16; 1];
+    match mb.read_registers(1000, &mut dest) {
+        Err(ModbusError::Exception(Exception::IllegalDataAddress)) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+
+    let id = mb.report_server_id().unwrap();
+    assert_eq!((id.server_id, id.running, id.data), (0x42, true, vec![0x07]));
+
+    device.join().unwrap();
+    mb.close();
+}
+
+#[test]
+fn test_ascii_invalid_responses_pty() {
+    let pty = Pty::open();
+    let mb = connected_ascii(&pty);
+
+    let mut master = pty.master.try_clone().unwrap();
+    let device = thread::spawn(move || {
+        // Invalid LRC
+        read_frame(&mut master);
+        master.write_all(b":01030400010002F6\r\n").unwrap();
+        // Another slave
+        read_frame(&mut master);
+        master.write_all(&codec::encode_ascii(2, &[0x03, 0x04, 0x00, 0x01, 0x00, 0x02])).unwrap();
+        // A truncated frame restarted by a colon
+        read_frame(&mut master);
+        master.write_all(b":0103040001:01030400010002F5\r\n").unwrap();
+    });
+
+    let mut dest = [0u16; 2];
+    match mb.read_registers(0, &mut dest) {
+        Err(ModbusError::BadCrc) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    match mb.read_registers(0, &mut dest) {
+        Err(ModbusError::BadSlave) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    assert_eq!(mb.read_registers(0, &mut dest).unwrap(), 2);
+    assert_eq!(dest, [1, 2]);
+
+    device.join().unwrap();
+    mb.close();
+}
+
+#[test]
+fn test_ascii_timeouts_pty() {
+    let pty = Pty::open();
+    let mb = connected_ascii(&pty);
+    mb.set_response_timeout(Duration::from_millis(100)).unwrap();
+    mb.set_byte_timeout(Some(Duration::from_millis(50))).unwrap();
+
+    let mut master = pty.master.try_clone().unwrap();
+    let device = thread::spawn(move || {
+        // No response
+        read_frame(&mut master);
+        // The response stalls between two characters
+        read_frame(&mut master);
+        master.write_all(b":0103").unwrap();
+        thread::sleep(Duration::from_millis(200));
+        master.write_all(b"0400010002F5\r\n").unwrap();
+    });
+
+    let mut dest = [0u16; 2];
+    let start = Instant::now();
+    assert!(is_timeout(mb.read_registers(0, &mut dest)));
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    let start = Instant::now();
+    assert!(is_timeout(mb.read_registers(0, &mut dest)));
+    assert!(start.elapsed() < Duration::from_millis(200));
+
+    device.join().unwrap();
+    mb.close();
+}
+
+#[test]
+fn test_ascii_broadcast_pty() {
+    let pty = Pty::open();
+    let mb = connected_ascii(&pty);
+    mb.set_slave(MODBUS_BROADCAST_ADDRESS).unwrap();
+
+    // No slave replies to a broadcast, so do not wait for the response timeout
+    let start = Instant::now();
+    assert_eq!(mb.write_register(5, 0xabcd).unwrap(), 1);
+    assert!(start.elapsed() < mb.get_response_timeout().unwrap());
+
+    let mut master = pty.master.try_clone().unwrap();
+    assert_eq!(read_frame(&mut master), codec::encode_ascii(0, &[0x06, 0x00, 0x05, 0xab, 0xcd]));
+
+    // Nothing can be read from a broadcast
+    let mut dest = [0u16; 1];
+    assert!(mb.read_registers(0, &mut dest).is_err());
+
+    mb.close();
+}
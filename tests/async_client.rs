@@ -0,0 +1,211 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+#[cfg(feature = "libmodbus")]
+use modbus::ModbusMapping;
+use modbus::{AsyncModbus, ModbusError, Exception};
+use modbus::codec::{self, Request, Response};
+#[cfg(feature = "libmodbus")]
+use common::spawn_server;
+
+#[cfg(feature = "libmodbus")]
+#[tokio::test]
+async fn test_async_client() {
+    let (addr, server) = spawn_server("127.0.0.1:0", |server| {
+        let mut mapping = ModbusMapping::new(100, 100, 100, 100);
+        for (i, r) in mapping.input_registers_mut().iter_mut().enumerate() {
+            *r = i as u16 * 10;
+        }
+        server.serve(&mut mapping).unwrap();
+        mapping
+    });
+    let mb = AsyncModbus::connect(&addr).await.unwrap();
+
+    assert_eq!(mb.write_registers(10, &[1, 2, 3]).await.unwrap(), 3);
+    assert_eq!(mb.write_register(13, 4).await.unwrap(), 1);
+    let mut regs = [0u16; 4];
+    assert_eq!(mb.read_registers(10, &mut regs).await.unwrap(), 4);
+    assert_eq!(regs, [1, 2, 3, 4]);
+
+    mb.write_bits(0, &[true, false, true]).await.unwrap();
+    mb.write_bit(3, 1).await.unwrap();
+    let mut bits = [false; 4];
+    assert_eq!(mb.read_bits(0, &mut bits).await.unwrap(), 4);
+    assert_eq!(bits, [true, false, true, true]);
+
+    mb.mask_write_register(10, 0x00F0, 0x0005).await.unwrap();
+    let mut read = [0u16; 2];
+    assert_eq!(mb.write_and_read_registers(20, &[7, 8], 9, &mut read).await.unwrap(), 2);
+    assert_eq!(read, [0, 0x0005]);
+
+    let mut inputs = [0u8; 2];
+    mb.read_input_bits(0, &mut inputs).await.unwrap();
+    assert_eq!(inputs, [0, 0]);
+
+    // Pipelined requests
+    let (mut a, mut b, mut c) = ([0u16; 3], [0u16; 2], [0u16; 1]);
+    let (ra, rb, rc) = tokio::join!(mb.read_input_registers(0, &mut a),
+                                    mb.read_input_registers(50, &mut b),
+                                    mb.read_registers(20, &mut c));
+    assert_eq!((ra.unwrap(), rb.unwrap(), rc.unwrap()), (3, 2, 1));
+    assert_eq!(a, [0, 10, 20]);
+    assert_eq!(b, [500, 510]);
+    assert_eq!(c, [7]);
+
+    let mut beyond = [0u16; 1];
+    match mb.read_registers(100, &mut beyond).await {
+        Err(ModbusError::Exception(Exception::IllegalDataAddress)) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+
+    drop(mb);
+    let mapping = server.join().unwrap();
+    assert_eq!(&mapping.registers()[20..22], &[7, 8]);
+}
+
+/// A server which reads count requests before answering them in reverse order, then closes the
+/// connection
+async fn reversing_server(count: usize) -> SocketAddr
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = Vec::new();
+        let mut frames = Vec::new();
+        let mut chunk = [0u8; 260];
+        while frames.len() < count {
+            match codec::tcp_frame_length(&buf).unwrap() {
+                Some(len) if buf.len() >= len => {
+                    frames.push(buf.drain(..len).collect::<Vec<u8>>());
+                    continue
+                }
+                _ => {}
+            }
+            let n = stream.read(&mut chunk).await.unwrap();
+            buf.extend_from_slice(&chunk[..n]);
+        }
+
+        for adu in frames.iter().rev() {
+            let frame = codec::decode_tcp(adu).unwrap();
+            let rsp = match Request::decode(frame.pdu).unwrap() {
+                Request::ReadHoldingRegisters { addr, nb } => {
+                    Response::ReadHoldingRegisters((addr..addr + nb).collect())
+                }
+                req => Response::Exception { function: req.function_code(),
+                                             exception: Exception::IllegalFunction },
+            };
+            let rsp = codec::encode_tcp(frame.transaction_id, frame.unit_id,
+                                        &rsp.encode().unwrap());
+            stream.write_all(&rsp).await.unwrap();
+        }
+    });
+
+    addr
+}
+
+#[tokio::test]
+async fn test_responses_matched_by_transaction_id() {
+    let addr = reversing_server(3).await;
+    let mb = AsyncModbus::connect(&addr).await.unwrap();
+
+    let (mut a, mut b) = ([0u16; 2], [0u16; 3]);
+    let (ra, rb, rc) = tokio::join!(mb.read_registers(100, &mut a),
+                                    mb.read_registers(200, &mut b),
+                                    mb.write_register(0, 1));
+    ra.unwrap();
+    rb.unwrap();
+    assert_eq!(a, [100, 101]);
+    assert_eq!(b, [200, 201, 202]);
+    match rc {
+        Err(ModbusError::Exception(Exception::IllegalFunction)) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+
+    // The server closed the connection
+    let mut c = [0u16; 1];
+    match mb.read_registers(0, &mut c).await {
+        Err(ModbusError::Io(_)) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
+#[tokio::test]
+async fn test_response_timeout() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        // Accept and never answer
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut sink = Vec::new();
+        let _ = stream.read_to_end(&mut sink).await;
+    });
+
+    let mb = AsyncModbus::connect(&addr).await.unwrap();
+    mb.set_response_timeout(Duration::from_millis(50)).unwrap();
+    assert_eq!(mb.get_response_timeout().unwrap(), Duration::from_millis(50));
+    mb.set_slave(1).unwrap();
+    assert_eq!(mb.slave(), 1);
+
+    let mut regs = [0u16; 1];
+    match mb.read_registers(0, &mut regs).await {
+        Err(ModbusError::Io(ref e)) if e.kind() == io::ErrorKind::TimedOut => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
+#[tokio::test]
+async fn test_cancelled_request() {
+    let addr = reversing_server(2).await;
+    let mb = AsyncModbus::connect(&addr).await.unwrap();
+
+    // Dropped while waiting for its response, the request was still sent whole
+    let mut a = [0u16; 2];
+    let cancelled = tokio::time::timeout(Duration::from_secs(0), mb.read_registers(100, &mut a));
+    assert!(cancelled.await.is_err());
+
+    let mut b = [0u16; 3];
+    assert_eq!(mb.read_registers(200, &mut b).await.unwrap(), 3);
+    assert_eq!(b, [200, 201, 202]);
+}
+
+#[tokio::test]
+async fn test_broadcast_not_answered() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+        // Receive the frame and never answer
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut frame = [0u8; 12];
+        stream.read_exact(&mut frame).await.unwrap();
+        frame
+    });
+
+    let mb = AsyncModbus::connect(&addr).await.unwrap();
+    mb.set_response_timeout(Duration::from_secs(10)).unwrap();
+    mb.set_slave(0).unwrap();
+
+    // Returns without waiting for the response timeout
+    let sent = tokio::time::timeout(Duration::from_secs(5), mb.write_register(3, 0x1234));
+    assert_eq!(sent.await.unwrap().unwrap(), 1);
+    let frame = server.await.unwrap();
+    let frame = codec::decode_tcp(&frame).unwrap();
+    assert_eq!(frame.unit_id, 0);
+    assert_eq!(Request::decode(frame.pdu).unwrap(),
+               Request::WriteSingleRegister { addr: 3, value: 0x1234 });
+
+    let mut regs = [0u16; 1];
+    match mb.read_registers(0, &mut regs).await {
+        Err(ModbusError::Io(ref e)) if e.kind() == io::ErrorKind::InvalidInput => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+}
@@ -0,0 +1,55 @@
+extern crate modbus;
+
+use modbus::Coils;
+use modbus::bits::*;
+
+#[test]
+fn test_pack_unpack() {
+    let bits = [1u8, 0, 1, 1, 0, 0, 1, 1, 1, 0];
+    assert_eq!(pack_bits(&bits), vec![0xcd, 0x01]);
+    assert_eq!(unpack_bits::<u8>(&[0xcd, 0x01], 10), bits.to_vec());
+
+    let bools = unpack_bits::<bool>(&[0xcd, 0x01], 10);
+    assert_eq!(bools, bits.iter().map(|&b| b != 0).collect::<Vec<bool>>());
+    assert_eq!(pack_bits(&bools), vec![0xcd, 0x01]);
+    assert!(pack_bits::<bool>(&[]).is_empty());
+}
+
+#[test]
+#[cfg(feature = "libmodbus")]
+fn test_libmodbus_helpers() {
+    let mut dest = [0u8; 12];
+    set_bits_from_byte(&mut dest, 2, 0xcd);
+    assert_eq!(dest, [0, 0, 1, 0, 1, 1, 0, 0, 1, 1, 0, 0]);
+    assert_eq!(get_byte_from_bits(&dest, 2, 8), 0xcd);
+    assert_eq!(get_byte_from_bits(&dest, 2, 3), 0x05);
+
+    let mut dest = [0u8; 10];
+    set_bits_from_bytes(&mut dest, 0, 10, &[0xcd, 0x01]);
+    assert_eq!(dest, [1, 0, 1, 1, 0, 0, 1, 1, 1, 0]);
+}
+
+#[test]
+#[should_panic]
+#[cfg(feature = "libmodbus")]
+fn test_set_bits_from_byte_overflow() {
+    let mut dest = [0u8; 7];
+    set_bits_from_byte(&mut dest, 0, 0xff);
+}
+
+#[test]
+fn test_coils() {
+    let mut coils = Coils::new(10);
+    assert_eq!(coils.len(), 10);
+    assert!(coils.iter().all(|&c| !c));
+
+    coils[0] = true;
+    coils[2] = true;
+    assert_eq!(coils.to_packed(), vec![0x05, 0x00]);
+    assert_eq!(coils.to_bits(), vec![1, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(Coils::from_bits(&coils.to_bits()), coils);
+    assert_eq!(Coils::from_packed(&[0x05, 0x00], 10), coils);
+
+    let bools: Vec<bool> = coils.clone().into();
+    assert_eq!(Coils::from(bools), coils);
+}
@@ -0,0 +1,366 @@
+extern crate modbus;
+extern crate libc;
+
+#[cfg(feature = "libmodbus")]
+use std::fs::File;
+#[cfg(feature = "libmodbus")]
+use std::io::{Read, Write};
+#[cfg(feature = "libmodbus")]
+use std::net::{SocketAddr, TcpListener};
+#[cfg(feature = "libmodbus")]
+use std::os::unix::io::FromRawFd;
+#[cfg(feature = "libmodbus")]
+use std::ptr;
+#[cfg(feature = "libmodbus")]
+use std::sync::mpsc;
+#[cfg(feature = "libmodbus")]
+use std::thread;
+
+#[cfg(feature = "libmodbus")]
+use modbus::Modbus;
+use modbus::{ModbusError, Exception};
+use modbus::codec::{self, Request, Response, DeviceIdentification};
+
+#[test]
+fn test_request_encoding() {
+    // Examples of the Modbus application protocol specification
+    let cases: Vec<(Request, Vec<u8>)> = vec![
+        (Request::ReadCoils { addr: 0x13, nb: 0x13 }, vec![0x01, 0x00, 0x13, 0x00, 0x13]),
+        (Request::ReadDiscreteInputs { addr: 0xC4, nb: 0x16 }, vec![0x02, 0x00, 0xC4, 0x00, 0x16]),
+        (Request::ReadHoldingRegisters { addr: 0x6B, nb: 3 }, vec![0x03, 0x00, 0x6B, 0x00, 0x03]),
+        (Request::ReadInputRegisters { addr: 0x08, nb: 1 }, vec![0x04, 0x00, 0x08, 0x00, 0x01]),
+        (Request::WriteSingleCoil { addr: 0xAC, value: true }, vec![0x05, 0x00, 0xAC, 0xFF, 0x00]),
+        (Request::WriteSingleRegister { addr: 1, value: 3 }, vec![0x06, 0x00, 0x01, 0x00, 0x03]),
+        (Request::WriteMultipleCoils {
+            addr: 0x13,
+            values: vec![true, false, true, true, false, false, true, true, true, false],
+        }, vec![0x0F, 0x00, 0x13, 0x00, 0x0A, 0x02, 0xCD, 0x01]),
+        (Request::WriteMultipleRegisters { addr: 1, values: vec![0x000A, 0x0102] },
+         vec![0x10, 0x00, 0x01, 0x00, 0x02, 0x04, 0x00, 0x0A, 0x01, 0x02]),
+        (Request::MaskWriteRegister { addr: 4, and_mask: 0xF2, or_mask: 0x25 },
+         vec![0x16, 0x00, 0x04, 0x00, 0xF2, 0x00, 0x25]),
+        (Request::ReadWriteMultipleRegisters {
+            read_addr: 3, read_nb: 6, write_addr: 0x0E, values: vec![0x00FF, 0x00FF, 0x00FF],
+        }, vec![0x17, 0x00, 0x03, 0x00, 0x06, 0x00, 0x0E, 0x00, 0x03, 0x06,
+                0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF]),
+        (Request::ReadDeviceIdentification { read_code: 1, object_id: 0 },
+         vec![0x2B, 0x0E, 0x01, 0x00]),
+    ];
+
+    for (req, pdu) in cases {
+        assert_eq!(req.encode().unwrap(), pdu);
+        assert_eq!(Request::decode(&pdu).unwrap(), req);
+        assert_eq!(codec::request_length(&pdu).unwrap(), Some(pdu.len()));
+    }
+}
+
+#[test]
+fn test_response_encoding() {
+    let cases: Vec<(Response, Vec<u8>)> = vec![
+        (Response::ReadCoils(codec_bits(&[0xCD, 0x6B, 0x05])),
+         vec![0x01, 0x03, 0xCD, 0x6B, 0x05]),
+        (Response::ReadHoldingRegisters(vec![0x022B, 0x0000, 0x0064]),
+         vec![0x03, 0x06, 0x02, 0x2B, 0x00, 0x00, 0x00, 0x64]),
+        (Response::WriteSingleCoil { addr: 0xAC, value: false },
+         vec![0x05, 0x00, 0xAC, 0x00, 0x00]),
+        (Response::WriteMultipleCoils { addr: 0x13, nb: 0x0A }, vec![0x0F, 0x00, 0x13, 0x00, 0x0A]),
+        (Response::WriteMultipleRegisters { addr: 1, nb: 2 }, vec![0x10, 0x00, 0x01, 0x00, 0x02]),
+        (Response::MaskWriteRegister { addr: 4, and_mask: 0xF2, or_mask: 0x25 },
+         vec![0x16, 0x00, 0x04, 0x00, 0xF2, 0x00, 0x25]),
+        (Response::ReadWriteMultipleRegisters(vec![0x00FE, 0x0ACD]),
+         vec![0x17, 0x04, 0x00, 0xFE, 0x0A, 0xCD]),
+        (Response::ReadDeviceIdentification(DeviceIdentification {
+            read_code: 1,
+            conformity_level: 0x01,
+            more_follows: false,
+            next_object_id: 0,
+            objects: vec![(0, b"ACME".to_vec()), (1, b"PLC".to_vec())],
+        }), vec![0x2B, 0x0E, 0x01, 0x01, 0x00, 0x00, 0x02,
+                 0x00, 0x04, b'A', b'C', b'M', b'E', 0x01, 0x03, b'P', b'L', b'C']),
+        (Response::Exception { function: 0x03, exception: Exception::IllegalDataAddress },
+         vec![0x83, 0x02]),
+    ];
+
+    for (rsp, pdu) in cases {
+        assert_eq!(rsp.encode().unwrap(), pdu);
+        assert_eq!(Response::decode(&pdu).unwrap(), rsp);
+        assert_eq!(codec::response_length(&pdu).unwrap(), Some(pdu.len()));
+    }
+}
+
+fn codec_bits(bytes: &[u8]) -> Vec<bool>
+{
+    modbus::bits::unpack_bits(bytes, bytes.len() * 8)
+}
+
+#[test]
+fn test_malformed_pdus() {
+    // Truncated, byte count not matching the quantity, invalid coil value
+    let pdus: [&[u8]; 5] = [
+        &[],
+        &[0x03, 0x00, 0x01, 0x00],
+        &[0x10, 0x00, 0x01, 0x00, 0x02, 0x02, 0x00, 0x01],
+        &[0x05, 0x00, 0x01, 0x12, 0x34],
+        &[0x42],
+    ];
+    for pdu in &pdus {
+        match Request::decode(pdu) {
+            Err(ModbusError::BadData) => {}
+            r => panic!("{:?} decoded to {:?}", pdu, r),
+        }
+    }
+    match Request::decode(&[0x03, 0x00, 0x00, 0x00, 126]) {
+        Err(ModbusError::TooManyData) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    match (Request::ReadCoils { addr: 0, nb: 2001 }).encode() {
+        Err(ModbusError::TooManyData) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    match Response::decode(&[0x83, 0x0C]) {
+        Err(ModbusError::BadException) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    match Response::decode(&[0x03, 0x03, 0x00, 0x01, 0x02]) {
+        Err(ModbusError::BadData) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    assert_eq!(codec::response_length(&[0x03]).unwrap(), None);
+    assert!(codec::response_length(&[0x42]).is_err());
+}
+
+#[test]
+fn test_tcp_framing() {
+    let adu = codec::encode_tcp(0x1234, 0xFF, &[0x03, 0x00, 0x00, 0x00, 0x01]);
+    assert_eq!(adu, [0x12, 0x34, 0x00, 0x00, 0x00, 0x06, 0xFF, 0x03, 0x00, 0x00, 0x00, 0x01]);
+    assert_eq!(codec::tcp_frame_length(&adu[..5]).unwrap(), None);
+    assert_eq!(codec::tcp_frame_length(&adu[..6]).unwrap(), Some(adu.len()));
+
+    let frame = codec::decode_tcp(&adu).unwrap();
+    assert_eq!(frame.transaction_id, 0x1234);
+    assert_eq!(frame.unit_id, 0xFF);
+    assert_eq!(frame.pdu, &adu[7..]);
+
+    // Truncated, then a protocol identifier other than Modbus
+    assert!(codec::decode_tcp(&adu[..adu.len() - 1]).is_err());
+    let mut other = adu.clone();
+    other[3] = 1;
+    assert!(codec::decode_tcp(&other).is_err());
+}
+
+#[test]
+fn test_rtu_framing() {
+    let adu = codec::encode_rtu(1, &[0x03, 0x00, 0x00, 0x00, 0x01]);
+    assert_eq!(adu, [0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0A]);
+
+    let frame = codec::decode_rtu(&adu).unwrap();
+    assert_eq!(frame.slave, 1);
+    assert_eq!(frame.pdu, &adu[1..6]);
+
+    let mut corrupted = adu.clone();
+    corrupted[3] ^= 0x10;
+    match codec::decode_rtu(&corrupted) {
+        Err(ModbusError::BadCrc) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    match codec::decode_rtu(&adu[..3]) {
+        Err(ModbusError::BadData) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
+/// A Modbus TCP server written with the codec only, sending every request it decodes to reqs
+#[cfg(feature = "libmodbus")]
+fn spawn_codec_server(reqs: mpsc::Sender<Request>) -> SocketAddr
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 260];
+        loop {
+            if let Some(len) = codec::tcp_frame_length(&buf).unwrap() {
+                if buf.len() >= len {
+                    let adu: Vec<u8> = buf.drain(..len).collect();
+                    let frame = codec::decode_tcp(&adu).unwrap();
+                    let req = Request::decode(frame.pdu).unwrap();
+                    let rsp = respond(&req).encode().unwrap();
+                    reqs.send(req).unwrap();
+                    stream.write_all(&codec::encode_tcp(frame.transaction_id, frame.unit_id, &rsp))
+                          .unwrap();
+                    continue
+                }
+            }
+            match stream.read(&mut chunk).unwrap() {
+                0 => return,
+                n => buf.extend_from_slice(&chunk[..n]),
+            }
+        }
+    });
+
+    addr
+}
+
+#[cfg(feature = "libmodbus")]
+fn respond(req: &Request) -> Response
+{
+    match *req {
+        Request::ReadCoils { nb, .. } => Response::ReadCoils((0..nb).map(|i| i % 3 == 0).collect()),
+        Request::ReadHoldingRegisters { addr, nb } => {
+            Response::ReadHoldingRegisters((0..nb).map(|i| addr + i).collect())
+        }
+        Request::WriteSingleRegister { addr, value } => {
+            Response::WriteSingleRegister { addr, value }
+        }
+        Request::WriteMultipleCoils { addr, ref values } => {
+            Response::WriteMultipleCoils { addr, nb: values.len() as u16 }
+        }
+        Request::WriteMultipleRegisters { addr, ref values } => {
+            Response::WriteMultipleRegisters { addr, nb: values.len() as u16 }
+        }
+        Request::MaskWriteRegister { addr, and_mask, or_mask } => {
+            Response::MaskWriteRegister { addr, and_mask, or_mask }
+        }
+        Request::ReadWriteMultipleRegisters { read_nb, ref values, .. } => {
+            Response::ReadWriteMultipleRegisters(values.iter().cloned().cycle()
+                                                       .take(read_nb as usize).collect())
+        }
+        _ => Response::Exception { function: req.function_code(),
+                                   exception: Exception::IllegalFunction },
+    }
+}
+
+#[cfg(feature = "libmodbus")]
+#[test]
+fn test_libmodbus_client_against_codec() {
+    let (tx, rx) = mpsc::channel();
+    let addr = spawn_codec_server(tx);
+
+    let mb = Modbus::new_tcp(&addr);
+    mb.connect().unwrap();
+
+    let mut regs = [0u16; 3];
+    mb.read_registers(10, &mut regs).unwrap();
+    assert_eq!(regs, [10, 11, 12]);
+    assert_eq!(rx.recv().unwrap(), Request::ReadHoldingRegisters { addr: 10, nb: 3 });
+
+    let mut coils = [false; 10];
+    mb.read_bits(0, &mut coils).unwrap();
+    assert_eq!(coils, [true, false, false, true, false, false, true, false, false, true]);
+    assert_eq!(rx.recv().unwrap(), Request::ReadCoils { addr: 0, nb: 10 });
+
+    mb.write_register(5, 0xBEEF).unwrap();
+    assert_eq!(rx.recv().unwrap(), Request::WriteSingleRegister { addr: 5, value: 0xBEEF });
+
+    mb.write_bits(3, &[true, true, false]).unwrap();
+    assert_eq!(rx.recv().unwrap(),
+               Request::WriteMultipleCoils { addr: 3, values: vec![true, true, false] });
+
+    mb.write_registers(7, &[1, 2]).unwrap();
+    assert_eq!(rx.recv().unwrap(), Request::WriteMultipleRegisters { addr: 7, values: vec![1, 2] });
+
+    #[cfg(not(libmodbus_3_0))]
+    {
+        mb.mask_write_register(4, 0xF2, 0x25).unwrap();
+        assert_eq!(rx.recv().unwrap(),
+                   Request::MaskWriteRegister { addr: 4, and_mask: 0xF2, or_mask: 0x25 });
+    }
+
+    let mut read = [0u16; 4];
+    mb.write_and_read_registers(1, &[8, 9], 20, &mut read).unwrap();
+    assert_eq!(read, [8, 9, 8, 9]);
+    assert_eq!(rx.recv().unwrap(), Request::ReadWriteMultipleRegisters {
+        read_addr: 20, read_nb: 4, write_addr: 1, values: vec![8, 9],
+    });
+
+    let mut inputs = [0u16; 1];
+    match mb.read_input_registers(0, &mut inputs) {
+        Err(ModbusError::Exception(Exception::IllegalFunction)) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    assert_eq!(rx.recv().unwrap(), Request::ReadInputRegisters { addr: 0, nb: 1 });
+}
+
+/// Run f with the standard output, where libmodbus prints its debug output, sent to a pipe
+#[cfg(feature = "libmodbus")]
+fn capture_stdout<F: FnOnce()>(f: F) -> String
+{
+    unsafe {
+        let mut fds = [0; 2];
+        assert_eq!(libc::pipe(fds.as_mut_ptr()), 0);
+        let saved = libc::dup(1);
+        libc::fflush(ptr::null_mut());
+        libc::dup2(fds[1], 1);
+        libc::close(fds[1]);
+
+        f();
+
+        libc::fflush(ptr::null_mut());
+        libc::dup2(saved, 1);
+        libc::close(saved);
+        let mut out = String::new();
+        File::from_raw_fd(fds[0]).read_to_string(&mut out).unwrap();
+        out
+    }
+}
+
+/// Parse the frames of libmodbus debug output, "[XX]" bytes for the ones sent and "<XX>" for
+/// the ones received
+#[cfg(feature = "libmodbus")]
+fn debug_frames(out: &str, open: char) -> Vec<Vec<u8>>
+{
+    out.lines()
+       .filter(|line| line.starts_with(open))
+       .map(|line| {
+           line.split(['[', ']', '<', '>'])
+               .filter(|hex| !hex.is_empty())
+               .map(|hex| u8::from_str_radix(hex, 16).unwrap())
+               .collect()
+       })
+       .collect()
+}
+
+#[cfg(feature = "libmodbus")]
+#[test]
+fn test_codec_round_trips_debug_output() {
+    let (tx, _rx) = mpsc::channel();
+    let addr = spawn_codec_server(tx);
+
+    let mb = Modbus::new_tcp(&addr);
+    mb.connect().unwrap();
+    let out = capture_stdout(|| {
+        mb.set_debug(true);
+        let mut regs = [0u16; 2];
+        mb.read_registers(0x6B, &mut regs).unwrap();
+        mb.write_bits(0x13, &[true, false, true, true, false, false, true, true, true, false])
+          .unwrap();
+        mb.set_debug(false);
+    });
+
+    let sent = debug_frames(&out, '[');
+    let received = debug_frames(&out, '<');
+    assert_eq!(sent.len(), 2);
+    assert_eq!(received.len(), 2);
+
+    let expected = [
+        (Request::ReadHoldingRegisters { addr: 0x6B, nb: 2 },
+         Response::ReadHoldingRegisters(vec![0x6B, 0x6C])),
+        (Request::WriteMultipleCoils {
+            addr: 0x13,
+            values: vec![true, false, true, true, false, false, true, true, true, false],
+        }, Response::WriteMultipleCoils { addr: 0x13, nb: 10 }),
+    ];
+    for (i, (req, rsp)) in expected.iter().enumerate() {
+        let frame = codec::decode_tcp(&sent[i]).unwrap();
+        assert_eq!(&Request::decode(frame.pdu).unwrap(), req);
+        assert_eq!(codec::encode_tcp(frame.transaction_id, frame.unit_id, &req.encode().unwrap()),
+                   sent[i]);
+
+        let frame = codec::decode_tcp(&received[i]).unwrap();
+        assert_eq!(&Response::decode(frame.pdu).unwrap(), rsp);
+        assert_eq!(codec::encode_tcp(frame.transaction_id, frame.unit_id, &rsp.encode().unwrap()),
+                   received[i]);
+    }
+}
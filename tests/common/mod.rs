@@ -0,0 +1,69 @@
+#![allow(dead_code)]
+
+extern crate libc;
+
+use std::ffi::CStr;
+use std::fs::File;
+#[cfg(feature = "libmodbus")]
+use std::net::SocketAddr;
+use std::os::unix::io::FromRawFd;
+#[cfg(feature = "libmodbus")]
+use std::thread;
+
+use modbus::codec;
+#[cfg(feature = "libmodbus")]
+use modbus::ModbusServer;
+
+/// A pseudo-terminal pair. The slave side is opened by name, like a serial port, while the test
+/// plays the remote device on the master side.
+pub struct Pty {
+    pub master: File,
+    pub slave_name: String,
+}
+
+impl Pty {
+    pub fn open() -> Pty
+    {
+        unsafe {
+            let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(fd >= 0, "posix_openpt failed");
+            assert_eq!(libc::grantpt(fd), 0);
+            assert_eq!(libc::unlockpt(fd), 0);
+
+            let mut buf = [0 as libc::c_char; 128];
+            assert_eq!(libc::ptsname_r(fd, buf.as_mut_ptr(), buf.len()), 0);
+            let slave_name = CStr::from_ptr(buf.as_ptr()).to_string_lossy().into_owned();
+
+            Pty { master: File::from_raw_fd(fd), slave_name }
+        }
+    }
+}
+
+/// Append the CRC to a slave address and PDU
+pub fn rtu_frame(body: &[u8]) -> Vec<u8>
+{
+    let crc = codec::crc16(body);
+    let mut frame = body.to_vec();
+    frame.push(crc as u8);
+    frame.push((crc >> 8) as u8);
+    frame
+}
+
+/// Listen on addr, port 0 picking a free port, and run serve on a thread once a client connected
+///
+/// Returns the address to connect to and the thread, which returns what serve returns.
+#[cfg(feature = "libmodbus")]
+pub fn spawn_server<T, F>(addr: &str, serve: F) -> (SocketAddr, thread::JoinHandle<T>)
+    where T: Send + 'static, F: FnOnce(&mut ModbusServer) -> T + Send + 'static
+{
+    let addr = addr.parse().unwrap();
+    let mut server = ModbusServer::listen(&addr, 1).unwrap();
+    let addr = server.local_addr().unwrap();
+
+    let handle = thread::spawn(move || {
+        server.accept().unwrap();
+        serve(&mut server)
+    });
+
+    (addr, handle)
+}
@@ -0,0 +1,107 @@
+#![cfg(feature = "libmodbus")]
+
+extern crate modbus;
+extern crate modbus_sys;
+
+use modbus::conversions::*;
+
+const ORDERS: [WordOrder; 4] = [WordOrder::Abcd, WordOrder::Dcba, WordOrder::Badc, WordOrder::Cdab];
+
+#[cfg(not(libmodbus_3_0))]
+const FLOATS: [f32; 6] = [0.0, 1.5, -2.25, 123_456.79, 1e-30, f32::MAX];
+
+// The float functions of libmodbus appeared in 3.1
+#[cfg(not(libmodbus_3_0))]
+fn libmodbus_get_float(src: &[u16], order: WordOrder) -> f32
+{
+    unsafe {
+        match order {
+            WordOrder::Abcd => modbus_sys::modbus_get_float_abcd(src.as_ptr()),
+            WordOrder::Dcba => modbus_sys::modbus_get_float_dcba(src.as_ptr()),
+            WordOrder::Badc => modbus_sys::modbus_get_float_badc(src.as_ptr()),
+            WordOrder::Cdab => modbus_sys::modbus_get_float_cdab(src.as_ptr()),
+        }
+    }
+}
+
+#[cfg(not(libmodbus_3_0))]
+fn libmodbus_set_float(f: f32, dest: &mut [u16], order: WordOrder)
+{
+    unsafe {
+        match order {
+            WordOrder::Abcd => modbus_sys::modbus_set_float_abcd(f, dest.as_mut_ptr()),
+            WordOrder::Dcba => modbus_sys::modbus_set_float_dcba(f, dest.as_mut_ptr()),
+            WordOrder::Badc => modbus_sys::modbus_set_float_badc(f, dest.as_mut_ptr()),
+            WordOrder::Cdab => modbus_sys::modbus_set_float_cdab(f, dest.as_mut_ptr()),
+        }
+    }
+}
+
+#[test]
+#[cfg(not(libmodbus_3_0))]
+fn test_f32_matches_libmodbus() {
+    for &order in &ORDERS {
+        for &f in &FLOATS {
+            let mut ours = [0u16; 2];
+            let mut theirs = [0u16; 2];
+            set_f32(f, &mut ours, order);
+            libmodbus_set_float(f, &mut theirs, order);
+            assert_eq!(ours, theirs, "set {} {:?}", f, order);
+            assert_eq!(get_f32(&theirs, order), libmodbus_get_float(&theirs, order));
+            assert_eq!(get_f32(&ours, order), f);
+        }
+    }
+}
+
+#[test]
+fn test_32_bit_orders() {
+    let value = 0x11223344u32;
+    let expected = [
+        (WordOrder::Abcd, [0x1122, 0x3344]),
+        (WordOrder::Dcba, [0x4433, 0x2211]),
+        (WordOrder::Badc, [0x2211, 0x4433]),
+        (WordOrder::Cdab, [0x3344, 0x1122]),
+    ];
+    for &(order, regs) in &expected {
+        let mut dest = [0u16; 2];
+        set_u32(value, &mut dest, order);
+        assert_eq!(dest, regs, "{:?}", order);
+        assert_eq!(get_u32(&regs, order), value);
+    }
+
+    let mut dest = [0u16; 2];
+    set_i32(-2, &mut dest, WordOrder::Cdab);
+    assert_eq!(dest, [0xfffe, 0xffff]);
+    assert_eq!(get_i32(&dest, WordOrder::Cdab), -2);
+}
+
+#[test]
+fn test_64_bit_orders() {
+    let value = 0x1122334455667788u64;
+    let expected = [
+        (WordOrder::Abcd, [0x1122, 0x3344, 0x5566, 0x7788]),
+        (WordOrder::Dcba, [0x8877, 0x6655, 0x4433, 0x2211]),
+        (WordOrder::Badc, [0x2211, 0x4433, 0x6655, 0x8877]),
+        (WordOrder::Cdab, [0x7788, 0x5566, 0x3344, 0x1122]),
+    ];
+    for &(order, regs) in &expected {
+        let mut dest = [0u16; 4];
+        set_u64(value, &mut dest, order);
+        assert_eq!(dest, regs, "{:?}", order);
+        assert_eq!(get_u64(&regs, order), value);
+    }
+
+    for &order in &ORDERS {
+        let mut dest = [0u16; 4];
+        set_i64(-1234567890123, &mut dest, order);
+        assert_eq!(get_i64(&dest, order), -1234567890123);
+        set_f64(-0.1, &mut dest, order);
+        assert_eq!(get_f64(&dest, order), -0.1);
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_short_slice() {
+    get_f64(&[0u16; 3], WordOrder::Abcd);
+}
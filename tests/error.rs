@@ -0,0 +1,56 @@
+#![cfg(feature = "libmodbus")]
+
+extern crate modbus;
+extern crate modbus_sys;
+extern crate libc;
+
+use std::error::Error;
+use std::io;
+
+use modbus::{ModbusError, Exception};
+
+#[test]
+fn test_decode_exceptions() {
+    match ModbusError::from_errno(modbus_sys::EMBXILADD) {
+        ModbusError::Exception(Exception::IllegalDataAddress) => (),
+        e => panic!("unexpected {:?}", e),
+    }
+    match ModbusError::from_errno(modbus_sys::EMBXSBUSY) {
+        ModbusError::Exception(Exception::ServerBusy) => (),
+        e => panic!("unexpected {:?}", e),
+    }
+    for code in 1..12 {
+        let e = Exception::from_code(code).unwrap();
+        assert_eq!(e.code(), code);
+        let err = ModbusError::Exception(e);
+        assert_eq!(err.errno(), Some(modbus_sys::MODBUS_ENOBASE + code as i32));
+    }
+    assert!(Exception::from_code(0).is_none());
+    assert!(Exception::from_code(12).is_none());
+}
+
+#[test]
+fn test_decode_native_errors() {
+    match ModbusError::from_errno(modbus_sys::EMBBADCRC) {
+        ModbusError::BadCrc => (),
+        e => panic!("unexpected {:?}", e),
+    }
+    match ModbusError::from_errno(modbus_sys::EMBMDATA) {
+        ModbusError::TooManyData => (),
+        e => panic!("unexpected {:?}", e),
+    }
+    match ModbusError::from_errno(libc::ETIMEDOUT) {
+        ModbusError::Io(ref e) => assert_eq!(e.kind(), io::ErrorKind::TimedOut),
+        e => panic!("unexpected {:?}", e),
+    }
+}
+
+#[test]
+fn test_display() {
+    let e = ModbusError::from_errno(modbus_sys::EMBXILFUN);
+    assert_eq!(e.to_string(), "Illegal function");
+    assert!(e.source().is_none());
+
+    let e = ModbusError::from_errno(libc::ECONNRESET);
+    assert!(e.source().is_some());
+}
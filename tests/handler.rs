@@ -0,0 +1,103 @@
+#![cfg(all(feature = "libmodbus", not(libmodbus_3_0)))]
+
+extern crate modbus;
+
+mod common;
+
+use modbus::{Modbus, ModbusError, Exception, RequestHandler};
+use common::spawn_server;
+
+#[derive(Default)]
+struct Device {
+    writes: Vec<(u16, u16)>,
+}
+
+impl RequestHandler for Device {
+    fn read_holding_registers(&mut self, addr: u16, dest: &mut [u16]) -> Result<(), Exception>
+    {
+        for (i, r) in dest.iter_mut().enumerate() {
+            *r = (addr + i as u16) * 2;
+        }
+        Ok(())
+    }
+
+    fn read_input_registers(&mut self, _addr: u16, _dest: &mut [u16]) -> Result<(), Exception>
+    {
+        Err(Exception::ServerBusy)
+    }
+
+    fn write_registers(&mut self, addr: u16, values: &[u16]) -> Result<(), Exception>
+    {
+        if addr < 10 {
+            return Err(Exception::IllegalDataAddress)
+        }
+        for (i, &v) in values.iter().enumerate() {
+            self.writes.push((addr + i as u16, v));
+        }
+        Ok(())
+    }
+}
+
+fn expect_exception(r: modbus::ModbusResult, exception: Exception)
+{
+    match r {
+        Err(ModbusError::Exception(e)) => assert_eq!(e, exception),
+        r => panic!("expected {:?}, got {:?}", exception, r),
+    }
+}
+
+#[test]
+fn test_handler() {
+    let (addr, server) = spawn_server("127.0.0.1:0", |server| {
+        let mut device = Device::default();
+        server.serve_handler(&mut device).unwrap();
+        device
+    });
+    let mb = Modbus::new_tcp(&addr);
+    mb.connect().unwrap();
+
+    let mut dest = [0u16; 3];
+    mb.read_registers(100, &mut dest).unwrap();
+    assert_eq!(dest, [200, 202, 204]);
+
+    mb.write_register(10, 7).unwrap();
+    mb.write_registers(20, &[8, 9]).unwrap();
+    expect_exception(mb.write_register(5, 1), Exception::IllegalDataAddress);
+
+    // Default implementation writes then reads
+    mb.write_and_read_registers(30, &[1], 40, &mut dest).unwrap();
+    assert_eq!(dest, [80, 82, 84]);
+
+    expect_exception(mb.read_input_registers(0, &mut dest), Exception::ServerBusy);
+
+    let mut bits = [0u8; 4];
+    expect_exception(mb.read_bits(0, &mut bits), Exception::IllegalFunction);
+
+    // Checked before the handler is called
+    let mut too_many = vec![0u16; 200];
+    assert!(mb.read_registers(0, &mut too_many).is_err());
+
+    mb.close();
+    let device = server.join().unwrap();
+    assert_eq!(device.writes, vec![(10, 7), (20, 8), (21, 9), (30, 1)]);
+}
+
+#[test]
+fn test_handler_write_and_read_overlapping() {
+    let (addr, server) = spawn_server("127.0.0.1:0", |server| {
+        let mut device = Device::default();
+        server.serve_handler(&mut device).unwrap();
+        device
+    });
+    let mb = Modbus::new_tcp(&addr);
+    mb.connect().unwrap();
+
+    // The values read are the ones of the handler, not the ones written over the same range
+    let mut dest = [0u16; 3];
+    mb.write_and_read_registers(40, &[1, 1], 40, &mut dest).unwrap();
+    assert_eq!(dest, [80, 82, 84]);
+
+    mb.close();
+    let device = server.join().unwrap();
+    assert_eq!(device.writes, vec![(40, 1), (41, 1)]);
+}
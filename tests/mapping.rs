@@ -0,0 +1,58 @@
+#![cfg(feature = "libmodbus")]
+
+extern crate modbus;
+
+use modbus::ModbusMapping;
+
+#[test]
+fn test_mapping_tables() {
+    let mut mapping = ModbusMapping::new(10, 20, 30, 40);
+
+    assert_eq!(mapping.bits().len(), 10);
+    assert_eq!(mapping.input_bits().len(), 20);
+    assert_eq!(mapping.registers().len(), 30);
+    assert_eq!(mapping.input_registers().len(), 40);
+    assert!(mapping.registers().iter().all(|&r| r == 0));
+
+    mapping.registers_mut()[3] = 0x1234;
+    mapping.input_bits_mut()[19] = 1;
+    assert_eq!(mapping.register(3), Some(0x1234));
+    assert_eq!(mapping.input_bit(19), Some(1));
+    assert_eq!(mapping.input_bit(20), None);
+    assert_eq!(mapping.start_registers(), 0);
+}
+
+#[test]
+#[cfg(not(libmodbus_3_0))]
+fn test_mapping_start_address() {
+    let mut mapping = ModbusMapping::new_start_address(100, 8, 200, 8, 1000, 10, 3000, 10);
+
+    assert_eq!(mapping.start_bits(), 100);
+    assert_eq!(mapping.start_input_bits(), 200);
+    assert_eq!(mapping.start_registers(), 1000);
+    assert_eq!(mapping.start_input_registers(), 3000);
+
+    *mapping.register_mut(1005).unwrap() = 42;
+    assert_eq!(mapping.registers()[5], 42);
+    assert_eq!(mapping.register(1005), Some(42));
+    assert_eq!(mapping.register(999), None);
+    assert_eq!(mapping.register(1010), None);
+    assert!(mapping.register_mut(5).is_none());
+
+    *mapping.bit_mut(107).unwrap() = 1;
+    assert_eq!(mapping.bits()[7], 1);
+    assert!(mapping.bit_mut(108).is_none());
+
+    *mapping.input_register_mut(3000).unwrap() = 7;
+    assert_eq!(mapping.input_registers()[0], 7);
+}
+
+#[test]
+fn test_mapping_empty_tables() {
+    let mapping = ModbusMapping::new(0, 0, 4, 0);
+
+    assert!(mapping.bits().is_empty());
+    assert!(mapping.input_registers().is_empty());
+    assert_eq!(mapping.bit(0), None);
+    assert_eq!(mapping.register(0), Some(0));
+}
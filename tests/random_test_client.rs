@@ -0,0 +1,44 @@
+#![cfg(feature = "libmodbus")]
+
+extern crate modbus;
+
+use std::process::Command;
+use std::thread;
+
+use modbus::{ModbusServer, ModbusMapping};
+
+/// Run random_test_client with the backend against a server, it must report no failure
+fn run_random_test_client(backend: &str)
+{
+    let addr = "127.0.0.1:0".parse().unwrap();
+    let mut server = ModbusServer::listen(&addr, 1).unwrap();
+    let addr = server.local_addr().unwrap();
+    let shutdown = server.shutdown_handle();
+    let server = thread::spawn(move || {
+        let mut mapping = ModbusMapping::new(500, 500, 500, 500);
+        server.serve_clients(&mut mapping).unwrap();
+    });
+
+    let output = Command::new(env!("CARGO_BIN_EXE_random_test_client"))
+                     .arg(backend)
+                     .arg(addr.to_string())
+                     .output()
+                     .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains("SUCCESS"), "{}", stdout);
+    assert!(!stdout.contains("Error"), "{}", stdout);
+
+    shutdown.shutdown();
+    server.join().unwrap();
+}
+
+#[test]
+fn test_random_test_client_libmodbus() {
+    run_random_test_client("libmodbus");
+}
+
+#[test]
+fn test_random_test_client_rust() {
+    run_random_test_client("rust");
+}
@@ -0,0 +1,120 @@
+#![cfg(feature = "libmodbus")]
+
+extern crate modbus;
+
+mod common;
+
+use std::net::{SocketAddr, TcpListener};
+use std::thread;
+use std::time::Duration;
+
+use modbus::{Modbus, ModbusMapping, ModbusError, ErrorRecovery, ReconnectingModbus, MAX_ADU_LENGTH};
+use common::spawn_server;
+
+/// A server which answers one request, drops the client, then serves the next one until it
+/// disconnects
+fn spawn_dropping_server() -> (SocketAddr, thread::JoinHandle<ModbusMapping>)
+{
+    spawn_server("127.0.0.1:0", |server| {
+        let mut mapping = ModbusMapping::new(10, 10, 10, 10);
+        mapping.registers_mut()[0] = 42;
+
+        let mut query = [0u8; MAX_ADU_LENGTH];
+        let len = server.modbus().receive(&mut query).unwrap();
+        server.modbus().reply(&query[..len as usize], &mut mapping).unwrap();
+
+        server.accept().unwrap();
+        server.serve(&mut mapping).unwrap();
+        mapping
+    })
+}
+
+fn client(addr: &SocketAddr) -> ReconnectingModbus
+{
+    let mut mb = ReconnectingModbus::new(Modbus::new_tcp(addr));
+    mb.set_backoff(Duration::from_millis(10), Duration::from_millis(100));
+    mb
+}
+
+#[test]
+fn test_reconnect_replays_reads() {
+    let (addr, server) = spawn_dropping_server();
+
+    let mut mb = client(&addr);
+    let mut dest = [0u16; 1];
+    assert_eq!(mb.read_registers(0, &mut dest).unwrap(), 1);
+    assert_eq!(dest, [42]);
+
+    dest = [0];
+    assert_eq!(mb.read_registers(0, &mut dest).unwrap(), 1);
+    assert_eq!(dest, [42]);
+
+    drop(mb);
+    server.join().unwrap();
+}
+
+#[test]
+fn test_reconnect_does_not_replay_writes() {
+    let (addr, server) = spawn_dropping_server();
+
+    let mut mb = client(&addr);
+    assert_eq!(mb.write_register(1, 1).unwrap(), 1);
+    match mb.write_register(2, 2) {
+        Err(ModbusError::Io(_)) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    assert_eq!(mb.write_register(3, 3).unwrap(), 1);
+
+    drop(mb);
+    let mapping = server.join().unwrap();
+    assert_eq!(&mapping.registers()[..4], &[42, 1, 0, 3]);
+}
+
+#[test]
+fn test_reconnect_gives_up() {
+    // Nothing listens on a port just released
+    let addr = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap()
+    };
+
+    let mut mb = client(&addr);
+    mb.set_max_attempts(3);
+    let mut dest = [0u16; 1];
+    match mb.read_registers(0, &mut dest) {
+        Err(ModbusError::Io(_)) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
+#[test]
+fn test_reconnect_limits_replays() {
+    // Connections are accepted by the kernel, but no request is ever answered
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let mut mb = client(&addr);
+    mb.set_max_attempts(3);
+    mb.modbus().set_response_timeout(Duration::from_millis(50)).unwrap();
+    let mut dest = [0u16; 1];
+    match mb.read_registers(0, &mut dest) {
+        Err(ModbusError::Io(_)) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+
+    // One connection per attempt
+    listener.set_nonblocking(true).unwrap();
+    let mut connections = 0;
+    while listener.accept().is_ok() {
+        connections += 1;
+    }
+    assert_eq!(connections, 3);
+}
+
+#[test]
+fn test_set_error_recovery() {
+    let addr = "127.0.0.1:1502".parse().unwrap();
+    let mb = Modbus::new_tcp(&addr);
+    mb.set_error_recovery(ErrorRecovery::LINK | ErrorRecovery::PROTOCOL).unwrap();
+    mb.set_error_recovery(ErrorRecovery::empty()).unwrap();
+}
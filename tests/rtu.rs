@@ -0,0 +1,192 @@
+#![cfg(feature = "libmodbus")]
+
+extern crate modbus;
+
+mod common;
+
+use std::io::{Read, Write};
+use std::thread;
+#[cfg(not(libmodbus_3_0))]
+use std::time::{Duration, Instant};
+
+use modbus::{Modbus, Parity, DataBits, StopBits, SerialMode};
+#[cfg(not(libmodbus_3_0))]
+use modbus::{RtsMode, MODBUS_BROADCAST_ADDRESS};
+use common::{Pty, rtu_frame};
+
+fn connected_rtu(pty: &Pty) -> Modbus
+{
+    let mb = Modbus::new_rtu(&pty.slave_name, 19200, Parity::Even,
+                             DataBits::Eight, StopBits::One).unwrap();
+    mb.connect().unwrap();
+    mb
+}
+
+#[test]
+fn test_new_rtu_rejects_invalid_settings() {
+    assert!(Modbus::new_rtu("/dev/ttyS0", 0, Parity::None, DataBits::Eight, StopBits::One).is_err());
+    assert!(Modbus::new_rtu("/dev/tty\0S0", 9600, Parity::None, DataBits::Eight, StopBits::One).is_err());
+}
+
+#[test]
+fn test_rtu_connect_pty() {
+    let pty = Pty::open();
+    let mb = connected_rtu(&pty);
+
+    assert_eq!(mb.get_serial_mode().unwrap(), SerialMode::Rs232);
+    // A pseudo-terminal has no RS-485 driver behind it
+    assert!(mb.set_serial_mode(SerialMode::Rs485).is_err());
+    assert_eq!(mb.get_serial_mode().unwrap(), SerialMode::Rs232);
+
+    mb.close();
+}
+
+#[test]
+#[cfg(not(libmodbus_3_0))]
+fn test_rtu_rts() {
+    let pty = Pty::open();
+    let mb = connected_rtu(&pty);
+
+    assert_eq!(mb.get_rts().unwrap(), RtsMode::None);
+    mb.set_rts(RtsMode::Up).unwrap();
+    assert_eq!(mb.get_rts().unwrap(), RtsMode::Up);
+    mb.set_rts(RtsMode::Down).unwrap();
+    assert_eq!(mb.get_rts().unwrap(), RtsMode::Down);
+
+    mb.set_rts_delay(1500).unwrap();
+    assert_eq!(mb.get_rts_delay().unwrap(), 1500);
+    assert!(mb.set_rts_delay(-1).is_err());
+    assert_eq!(mb.get_rts_delay().unwrap(), 1500);
+
+    mb.close();
+}
+
+#[test]
+fn test_rtu_read_registers_pty() {
+    let pty = Pty::open();
+    let mb = connected_rtu(&pty);
+    mb.set_slave(1).unwrap();
+    #[cfg(not(libmodbus_3_0))]
+    assert_eq!(mb.slave(), Some(1));
+
+    let mut master = pty.master.try_clone().unwrap();
+    let device = thread::spawn(move || {
+        let mut req = [0u8; 8];
+        master.read_exact(&mut req).unwrap();
+        assert_eq!(&req[..], &rtu_frame(&[1, 0x03, 0x00, 0x10, 0x00, 0x02])[..]);
+        master.write_all(&rtu_frame(&[1, 0x03, 4, 0x12, 0x34, 0x56, 0x78])).unwrap();
+    });
+
+    let mut dest = [0u16; 2];
+    assert_eq!(mb.read_registers(0x10, &mut dest).unwrap(), 2);
+    assert_eq!(dest, [0x1234, 0x5678]);
+
+    device.join().unwrap();
+    mb.close();
+}
+
+#[test]
+#[cfg(not(libmodbus_3_0))]
+fn test_rtu_slave_range() {
+    let mb = Modbus::new_rtu("/dev/ttyS0", 9600, Parity::None, DataBits::Eight, StopBits::One).unwrap();
+    assert_eq!(mb.slave(), None);
+    mb.set_slave(247).unwrap();
+    assert!(mb.set_slave(248).is_err());
+    assert!(mb.set_slave(-1).is_err());
+    assert_eq!(mb.slave(), Some(247));
+}
+
+#[test]
+#[cfg(not(libmodbus_3_0))]
+fn test_rtu_broadcast() {
+    let pty = Pty::open();
+    let mb = connected_rtu(&pty);
+    mb.set_slave(MODBUS_BROADCAST_ADDRESS).unwrap();
+
+    // No slave replies to a broadcast, so do not wait for the response timeout
+    let start = Instant::now();
+    assert_eq!(mb.write_register(5, 0xabcd).unwrap(), 1);
+    assert_eq!(mb.write_bits(0x13, &[1, 0, 1, 1, 0, 0, 1, 1, 1, 0]).unwrap(), 10);
+    assert!(start.elapsed() < mb.get_response_timeout().unwrap());
+
+    let mut master = pty.master.try_clone().unwrap();
+    let mut req = [0u8; 8];
+    master.read_exact(&mut req).unwrap();
+    assert_eq!(&req[..], &rtu_frame(&[0, 0x06, 0x00, 0x05, 0xab, 0xcd])[..]);
+    let mut req = [0u8; 11];
+    master.read_exact(&mut req).unwrap();
+    assert_eq!(&req[..], &rtu_frame(&[0, 0x0f, 0x00, 0x13, 0x00, 0x0a, 2, 0xcd, 0x01])[..]);
+
+    // Nothing can be read from a broadcast
+    let mut dest = [0u16; 1];
+    assert!(mb.read_registers(0, &mut dest).is_err());
+
+    mb.close();
+}
+
+#[test]
+#[cfg(not(libmodbus_3_0))]
+fn test_rtu_with_slave() {
+    let pty = Pty::open();
+    let mb = connected_rtu(&pty);
+    mb.set_slave(1).unwrap();
+    mb.set_response_timeout(Duration::from_secs(1)).unwrap();
+
+    let mut master = pty.master.try_clone().unwrap();
+    let device = thread::spawn(move || {
+        for &unit in &[7u8, 1] {
+            let mut req = [0u8; 8];
+            master.read_exact(&mut req).unwrap();
+            assert_eq!(&req[..], &rtu_frame(&[unit, 0x06, 0x00, 0x01, 0x00, 0x02])[..]);
+            master.write_all(&req).unwrap();
+        }
+    });
+
+    {
+        let unit = mb.with_slave(7).unwrap();
+        assert_eq!(unit.slave(), Some(7));
+        unit.write_register(1, 2).unwrap();
+    }
+    assert_eq!(mb.slave(), Some(1));
+    mb.write_register(1, 2).unwrap();
+
+    device.join().unwrap();
+    mb.close();
+}
+
+#[test]
+#[cfg(not(libmodbus_3_0))]
+fn test_rtu_with_slave_unset() {
+    let pty = Pty::open();
+    let mb = connected_rtu(&pty);
+    assert_eq!(mb.slave(), None);
+
+    // No slave to restore
+    assert!(mb.with_slave(7).is_err());
+    assert_eq!(mb.slave(), None);
+
+    mb.close();
+}
+
+#[test]
+fn test_rtu_report_server_id() {
+    let pty = Pty::open();
+    let mb = connected_rtu(&pty);
+    mb.set_slave(1).unwrap();
+
+    let mut master = pty.master.try_clone().unwrap();
+    let device = thread::spawn(move || {
+        let mut req = [0u8; 4];
+        master.read_exact(&mut req).unwrap();
+        assert_eq!(&req[..], &rtu_frame(&[1, 0x11])[..]);
+        master.write_all(&rtu_frame(&[1, 0x11, 5, 0x42, 0x00, b'a', b'b', b'c'])).unwrap();
+    });
+
+    let id = mb.report_server_id().unwrap();
+    assert_eq!(id.server_id, 0x42);
+    assert!(!id.running);
+    assert_eq!(id.data, b"abc");
+
+    device.join().unwrap();
+    mb.close();
+}
@@ -0,0 +1,237 @@
+extern crate modbus;
+
+mod common;
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use modbus::{ModbusClient, RtuTransport, ModbusError, Exception, Parity, DataBits, StopBits,
+             MODBUS_BROADCAST_ADDRESS};
+use modbus::codec;
+use common::Pty;
+
+/// At 1200 bauds with 11 bits per character, t1.5 is 13.75 ms and t3.5 32 ms
+fn connected_rtu(pty: &Pty, baud: i32) -> ModbusClient<RtuTransport>
+{
+    let mb = ModbusClient::new_rtu(&pty.slave_name, baud, Parity::Even,
+                                   DataBits::Eight, StopBits::One).unwrap();
+    mb.connect().unwrap();
+    mb.set_slave(1).unwrap();
+    mb
+}
+
+/// Read a request of len bytes from the master side of the pty
+fn read_frame(master: &mut File, len: usize) -> Vec<u8>
+{
+    let mut frame = vec![0u8; len];
+    master.read_exact(&mut frame).unwrap();
+    frame
+}
+
+fn is_timeout(r: Result<i32, ModbusError>) -> bool
+{
+    match r {
+        Err(ModbusError::Io(ref e)) => e.kind() == io::ErrorKind::TimedOut,
+        _ => false,
+    }
+}
+
+#[test]
+fn test_rtu_timings() {
+    let transport = RtuTransport::new("/dev/ttyS0", 9600, Parity::Even,
+                                      DataBits::Eight, StopBits::One).unwrap();
+    // 11 bits of 104.17 µs
+    assert_eq!(transport.inter_character_timeout().as_micros(), 1718);
+    assert_eq!(transport.inter_frame_delay().as_micros(), 4010);
+
+    let transport = RtuTransport::new("/dev/ttyS0", 1200, Parity::None,
+                                      DataBits::Eight, StopBits::Two).unwrap();
+    assert_eq!(transport.inter_character_timeout().as_micros(), 13749);
+    assert_eq!(transport.inter_frame_delay().as_micros(), 32083);
+
+    // Fixed above 19200 bauds
+    let transport = RtuTransport::new("/dev/ttyS0", 115200, Parity::None,
+                                      DataBits::Eight, StopBits::One).unwrap();
+    assert_eq!(transport.inter_character_timeout(), Duration::from_micros(750));
+    assert_eq!(transport.inter_frame_delay(), Duration::from_micros(1750));
+
+    assert!(RtuTransport::new("/dev/ttyS0", 0, Parity::None, DataBits::Eight, StopBits::One).is_err());
+    // Not a termios rate
+    assert!(RtuTransport::new("/dev/ttyS0", 1234, Parity::None, DataBits::Eight, StopBits::One).is_err());
+    assert!(RtuTransport::new("", 9600, Parity::None, DataBits::Eight, StopBits::One).is_err());
+}
+
+#[test]
+fn test_rtu_fragmented_response_pty() {
+    let pty = Pty::open();
+    let mb = connected_rtu(&pty, 1200);
+
+    let mut master = pty.master.try_clone().unwrap();
+    let device = thread::spawn(move || {
+        assert_eq!(read_frame(&mut master, 8), codec::encode_rtu(1, &[0x03, 0x00, 0x00, 0x00, 0x02]));
+        // The response in pieces, well within t1.5
+        let rsp = codec::encode_rtu(1, &[0x03, 0x04, 0x00, 0x01, 0x00, 0x02]);
+        for piece in rsp.chunks(3) {
+            master.write_all(piece).unwrap();
+            thread::sleep(Duration::from_millis(2));
+        }
+    });
+
+    let mut dest = [0u16; 2];
+    assert_eq!(mb.read_registers(0, &mut dest).unwrap(), 2);
+    assert_eq!(dest, [1, 2]);
+
+    device.join().unwrap();
+    mb.close();
+}
+
+#[test]
+fn test_rtu_corrupted_frames_pty() {
+    let pty = Pty::open();
+    let mb = connected_rtu(&pty, 1200);
+
+    let mut master = pty.master.try_clone().unwrap();
+    let device = thread::spawn(move || {
+        let rsp = codec::encode_rtu(1, &[0x03, 0x04, 0x00, 0x01, 0x00, 0x02]);
+
+        // Invalid CRC
+        read_frame(&mut master, 8);
+        let mut corrupted = rsp.clone();
+        corrupted[4] ^= 0x10;
+        master.write_all(&corrupted).unwrap();
+
+        // A silence longer than t1.5 in the middle of the frame
+        read_frame(&mut master, 8);
+        master.write_all(&rsp[..4]).unwrap();
+        thread::sleep(Duration::from_millis(22));
+        master.write_all(&rsp[4..]).unwrap();
+
+        // Too short to be a frame
+        read_frame(&mut master, 8);
+        master.write_all(&[0x01, 0x03]).unwrap();
+
+        // The line recovered
+        read_frame(&mut master, 8);
+        master.write_all(&rsp).unwrap();
+    });
+
+    let mut dest = [0u16; 2];
+    match mb.read_registers(0, &mut dest) {
+        Err(ModbusError::BadCrc) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    for _ in 0..2 {
+        match mb.read_registers(0, &mut dest) {
+            Err(ModbusError::BadFrame) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+    assert_eq!(mb.read_registers(0, &mut dest).unwrap(), 2);
+    assert_eq!(dest, [1, 2]);
+
+    device.join().unwrap();
+    mb.close();
+}
+
+#[test]
+fn test_rtu_operations_pty() {
+    let pty = Pty::open();
+    let mb = connected_rtu(&pty, 9600);
+
+    let mut master = pty.master.try_clone().unwrap();
+    let device = thread::spawn(move || {
+        // Write single register, echoed
+        let req = read_frame(&mut master, 8);
+        assert_eq!(req, codec::encode_rtu(1, &[0x06, 0x00, 0x01, 0x00, 0x03]));
+        master.write_all(&req).unwrap();
+
+        // Write multiple coils
+        let req = read_frame(&mut master, 11);
+        assert_eq!(req, codec::encode_rtu(1, &[0x0F, 0x00, 0x13, 0x00, 0x0A, 2, 0xCD, 0x01]));
+        master.write_all(&codec::encode_rtu(1, &[0x0F, 0x00, 0x13, 0x00, 0x0A])).unwrap();
+
+        // Read coils
+        read_frame(&mut master, 8);
+        master.write_all(&codec::encode_rtu(1, &[0x01, 1, 0x05])).unwrap();
+
+        // Exception
+        read_frame(&mut master, 8);
+        master.write_all(&codec::encode_rtu(1, &[0x83, 0x02])).unwrap();
+
+        // Another slave
+        read_frame(&mut master, 8);
+        master.write_all(&codec::encode_rtu(2, &[0x03, 0x02, 0x00, 0x01])).unwrap();
+
+        // Report server ID
+        assert_eq!(read_frame(&mut master, 4), codec::encode_rtu(1, &[0x11]));
+        master.write_all(&codec::encode_rtu(1, &[0x11, 3, 0x42, 0xFF, 0x07])).unwrap();
+    });
+
+    assert_eq!(mb.get_header_length().unwrap(), 1);
+    assert_eq!(mb.write_register(1, 3).unwrap(), 1);
+    assert_eq!(mb.write_bits(0x13, &[1u8, 0, 1, 1, 0, 0, 1, 1, 1, 0]).unwrap(), 10);
+
+    let mut bits = [false; 3];
+    assert_eq!(mb.read_bits(0, &mut bits).unwrap(), 3);
+    assert_eq!(bits, [true, false, true]);
+
+    let mut dest = [0u16; 1];
+    match mb.read_registers(1000, &mut dest) {
+        Err(ModbusError::Exception(Exception::IllegalDataAddress)) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    match mb.read_registers(0, &mut dest) {
+        Err(ModbusError::BadSlave) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+
+    let id = mb.report_server_id().unwrap();
+    assert_eq!((id.server_id, id.running, id.data), (0x42, true, vec![0x07]));
+
+    device.join().unwrap();
+    mb.close();
+}
+
+#[test]
+fn test_rtu_timeout_pty() {
+    let pty = Pty::open();
+    let mb = connected_rtu(&pty, 9600);
+    mb.set_response_timeout(Duration::from_millis(100)).unwrap();
+
+    let mut dest = [0u16; 2];
+    let start = Instant::now();
+    assert!(is_timeout(mb.read_registers(0, &mut dest)));
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    mb.close();
+    assert!(mb.read_registers(0, &mut dest).is_err());
+}
+
+#[test]
+fn test_rtu_broadcast_pty() {
+    let pty = Pty::open();
+    let mb = connected_rtu(&pty, 1200);
+    mb.set_slave(MODBUS_BROADCAST_ADDRESS).unwrap();
+
+    // No slave replies to a broadcast, so do not wait for the response timeout
+    let start = Instant::now();
+    assert_eq!(mb.write_register(5, 0xabcd).unwrap(), 1);
+    assert!(start.elapsed() < mb.get_response_timeout().unwrap());
+
+    // The next frame waits for the 8 characters of the first one to be sent, 73 ms at 1200 bauds,
+    // then for t3.5
+    assert_eq!(mb.write_register(6, 0x1234).unwrap(), 1);
+    assert!(start.elapsed() >= Duration::from_millis(105));
+
+    let mut master = pty.master.try_clone().unwrap();
+    assert_eq!(read_frame(&mut master, 8), codec::encode_rtu(0, &[0x06, 0x00, 0x05, 0xab, 0xcd]));
+    assert_eq!(read_frame(&mut master, 8), codec::encode_rtu(0, &[0x06, 0x00, 0x06, 0x12, 0x34]));
+
+    // Nothing can be read from a broadcast
+    let mut dest = [0u16; 1];
+    assert!(mb.read_registers(0, &mut dest).is_err());
+
+    mb.close();
+}
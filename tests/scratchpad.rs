@@ -1,3 +1,5 @@
+#![cfg(feature = "libmodbus")]
+
 
 #[cfg(test)]
 pub mod tests {
@@ -0,0 +1,240 @@
+#![cfg(feature = "libmodbus")]
+
+extern crate modbus;
+
+mod common;
+
+use std::net::SocketAddr;
+use std::thread;
+use std::time::Duration;
+
+use modbus::{Modbus, ModbusServer, ModbusMapping, ModbusError, Exception, ShutdownHandle, Coils};
+use common::spawn_server;
+
+/// Serve a mapping to a single client, returned connected
+fn connect_server() -> (Modbus, thread::JoinHandle<ModbusMapping>)
+{
+    let (addr, server) = spawn_server("127.0.0.1:0", |server| {
+        let mut mapping = ModbusMapping::new(100, 100, 100, 100);
+        server.serve(&mut mapping).unwrap();
+        mapping
+    });
+
+    let mb = Modbus::new_tcp(&addr);
+    mb.connect().unwrap();
+    (mb, server)
+}
+
+#[test]
+fn test_server_reply() {
+    let (mb, server) = connect_server();
+
+    let src = [1u16, 2, 3, 0xffff];
+    let mut dest = [0u16; 4];
+    assert_eq!(mb.write_registers(10, &src).unwrap(), 4);
+    assert_eq!(mb.read_registers(10, &mut dest).unwrap(), 4);
+    assert_eq!(src, dest);
+
+    let bits = [1u8, 0, 1];
+    let mut bits_dest = [0u8; 3];
+    mb.write_bits(0, &bits).unwrap();
+    mb.read_bits(0, &mut bits_dest).unwrap();
+    assert_eq!(bits, bits_dest);
+
+    let mut dest = [0u16; 2];
+    match mb.read_registers(99, &mut dest) {
+        Err(ModbusError::Exception(Exception::IllegalDataAddress)) => (),
+        r => panic!("unexpected {:?}", r),
+    }
+
+    mb.close();
+    let mapping = server.join().unwrap();
+    assert_eq!(&mapping.registers()[10..14], &src);
+    assert_eq!(&mapping.bits()[0..3], &bits);
+}
+
+#[test]
+fn test_server_read_input_bits() {
+    let (mb, server) = connect_server();
+
+    let mut inputs = [1u8; 5];
+    assert_eq!(mb.read_input_bits(0, &mut inputs).unwrap(), 5);
+    assert_eq!(inputs, [0; 5]);
+
+    mb.close();
+    server.join().unwrap();
+}
+
+#[cfg(not(libmodbus_3_0))]
+#[test]
+fn test_server_mask_write_register() {
+    let (mb, server) = connect_server();
+
+    mb.write_register(50, 0x00f0).unwrap();
+    mb.mask_write_register(50, 0x00ff, 0x1234).unwrap();
+
+    mb.close();
+    let mapping = server.join().unwrap();
+    assert_eq!(mapping.register(50), Some(0x12f0));
+}
+
+#[test]
+fn test_server_report_server_id() {
+    let (mb, server) = connect_server();
+
+    let id = mb.report_server_id().unwrap();
+    assert!(id.running);
+    assert!(id.data.starts_with(b"LMB"));
+
+    mb.close();
+    server.join().unwrap();
+}
+
+#[test]
+fn test_server_raw_request() {
+    let (mb, server) = connect_server();
+
+    mb.write_registers(10, &[1, 2]).unwrap();
+
+    // Raw read of registers 10 and 11 then an unknown function code
+    assert_eq!(mb.send_raw_request(&[0xff, 0x03, 0x00, 0x0a, 0x00, 0x02]).unwrap(), 12);
+    let rsp = mb.receive_confirmation().unwrap();
+    let offset = mb.get_header_length().unwrap() as usize;
+    assert_eq!(&rsp[offset..], &[0x03, 4, 0, 1, 0, 2]);
+    mb.send_raw_request(&[0xff, 0x41, 0x01]).unwrap();
+    let rsp = mb.receive_confirmation().unwrap();
+    assert_eq!(&rsp[offset..], &[0xc1, 0x01]);
+
+    assert!(mb.send_raw_request(&[0xff]).is_err());
+    assert!(mb.send_raw_request(&[0u8; 255]).is_err());
+
+    mb.close();
+    server.join().unwrap();
+}
+
+#[test]
+fn test_server_coils() {
+    let (mb, server) = connect_server();
+
+    mb.write_bits(0, &[1u8, 0, 1]).unwrap();
+    let mut coils = Coils::new(3);
+    mb.read_bits(0, &mut coils).unwrap();
+    assert_eq!(&coils[..], &[true, false, true]);
+    mb.write_bits(3, &[true, true]).unwrap();
+
+    mb.close();
+    let mapping = server.join().unwrap();
+    assert_eq!(&mapping.bits()[0..5], &[1, 0, 1, 1, 1]);
+}
+
+fn spawn_multi_server<F>(configure: F) -> (SocketAddr, ShutdownHandle, thread::JoinHandle<ModbusMapping>)
+    where F: FnOnce(&mut ModbusServer)
+{
+    let addr = "127.0.0.1:0".parse().unwrap();
+    let mut server = ModbusServer::listen(&addr, 5).unwrap();
+    configure(&mut server);
+    let addr = server.local_addr().unwrap();
+    let shutdown = server.shutdown_handle();
+
+    let handle = thread::spawn(move || {
+        let mut mapping = ModbusMapping::new(100, 100, 100, 100);
+        server.serve_clients(&mut mapping).unwrap();
+        mapping
+    });
+
+    (addr, shutdown, handle)
+}
+
+#[test]
+fn test_serve_clients_shared_mapping() {
+    let (addr, shutdown, server) = spawn_multi_server(|_| ());
+
+    let scada = Modbus::new_tcp(&addr);
+    let historian = Modbus::new_tcp(&addr);
+    scada.connect().unwrap();
+    historian.connect().unwrap();
+
+    let mut dest = [0u16; 2];
+    scada.write_registers(0, &[11, 22]).unwrap();
+    historian.read_registers(0, &mut dest).unwrap();
+    assert_eq!(dest, [11, 22]);
+
+    historian.write_register(1, 33).unwrap();
+    scada.read_registers(0, &mut dest).unwrap();
+    assert_eq!(dest, [11, 33]);
+
+    shutdown.shutdown();
+    let mapping = server.join().unwrap();
+    assert_eq!(&mapping.registers()[0..2], &[11, 33]);
+
+    // The server closed both connections on its way out
+    assert!(scada.read_registers(0, &mut dest).is_err());
+    assert!(historian.read_registers(0, &mut dest).is_err());
+}
+
+#[test]
+fn test_serve_clients_again_after_shutdown() {
+    let addr = "127.0.0.1:0".parse().unwrap();
+    let mut server = ModbusServer::listen(&addr, 5).unwrap();
+    let addr = server.local_addr().unwrap();
+    let shutdown = server.shutdown_handle();
+    let server = thread::spawn(move || {
+        let mut mapping = ModbusMapping::new(100, 100, 100, 100);
+        server.serve_clients(&mut mapping).unwrap();
+        server.serve_clients(&mut mapping).unwrap();
+        mapping
+    });
+
+    let mb = Modbus::new_tcp(&addr);
+    mb.connect().unwrap();
+    mb.write_register(0, 1).unwrap();
+    shutdown.shutdown();
+    // Closed once the first run stopped
+    let mut dest = [0u16; 1];
+    assert!(mb.read_registers(0, &mut dest).is_err());
+    mb.close();
+
+    mb.connect().unwrap();
+    mb.write_register(1, 2).unwrap();
+    shutdown.shutdown();
+    let mapping = server.join().unwrap();
+    assert_eq!(&mapping.registers()[0..2], &[1, 2]);
+}
+
+#[test]
+fn test_serve_clients_max_connections() {
+    let (addr, shutdown, server) = spawn_multi_server(|s| s.set_max_connections(1));
+
+    let first = Modbus::new_tcp(&addr);
+    first.connect().unwrap();
+    first.write_register(0, 1).unwrap();
+
+    let second = Modbus::new_tcp(&addr);
+    second.connect().unwrap();
+    assert!(second.write_register(0, 2).is_err());
+
+    first.write_register(0, 3).unwrap();
+
+    shutdown.shutdown();
+    let mapping = server.join().unwrap();
+    assert_eq!(mapping.register(0), Some(3));
+}
+
+#[test]
+fn test_serve_clients_idle_timeout() {
+    let (addr, shutdown, server) =
+        spawn_multi_server(|s| s.set_idle_timeout(Some(Duration::from_millis(100))));
+
+    let mb = Modbus::new_tcp(&addr);
+    mb.connect().unwrap();
+    mb.write_register(0, 1).unwrap();
+    thread::sleep(Duration::from_millis(50));
+    mb.write_register(0, 2).unwrap();
+
+    thread::sleep(Duration::from_millis(300));
+    assert!(mb.write_register(0, 3).is_err());
+
+    shutdown.shutdown();
+    let mapping = server.join().unwrap();
+    assert_eq!(mapping.register(0), Some(2));
+}
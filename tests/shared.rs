@@ -0,0 +1,74 @@
+#![cfg(feature = "libmodbus")]
+
+extern crate modbus;
+
+mod common;
+
+use std::thread;
+
+use modbus::{Modbus, ModbusMapping, SharedModbus};
+#[cfg(not(libmodbus_3_0))]
+use modbus::MODBUS_TCP_SLAVE;
+use common::spawn_server;
+
+const THREADS: usize = 8;
+const ROUNDS: usize = 200;
+
+#[test]
+fn test_concurrent_callers_get_their_own_responses() {
+    let (addr, server) = spawn_server("127.0.0.1:0", |server| {
+        let mut mapping = ModbusMapping::new(0, 0, 100, 100);
+        for (i, r) in mapping.input_registers_mut().iter_mut().enumerate() {
+            *r = i as u16 * 7;
+        }
+        server.serve(&mut mapping).unwrap();
+        mapping
+    });
+    let mb = Modbus::new_tcp(&addr);
+    mb.connect().unwrap();
+    let mb = SharedModbus::new(mb);
+
+    let callers: Vec<_> = (0..THREADS).map(|t| {
+        let mb = mb.clone();
+        thread::spawn(move || {
+            // Each thread owns registers 10 * t .. 10 * t + 10 and reads input registers of
+            // a different length, so a response delivered to the wrong caller is detected
+            let addr = (10 * t) as i32;
+            let mut inputs = vec![0u16; t + 1];
+            for round in 0..ROUNDS {
+                let value = (t * ROUNDS + round) as u16;
+                assert_eq!(mb.write_registers(addr, &[value; 10]).unwrap(), 10);
+
+                let mut regs = [0u16; 10];
+                assert_eq!(mb.read_registers(addr, &mut regs).unwrap(), 10);
+                assert_eq!(regs, [value; 10]);
+
+                assert_eq!(mb.read_input_registers(addr, &mut inputs).unwrap(), t as i32 + 1);
+                for (i, &r) in inputs.iter().enumerate() {
+                    assert_eq!(r, (10 * t + i) as u16 * 7);
+                }
+            }
+        })
+    }).collect();
+
+    for caller in callers {
+        caller.join().unwrap();
+    }
+
+    // Requests spanning several calls on the locked context
+    {
+        let ctx = mb.lock();
+        #[cfg(not(libmodbus_3_0))]
+        let ctx = ctx.with_slave(MODBUS_TCP_SLAVE).unwrap();
+        assert_eq!(ctx.write_register(99, 1234).unwrap(), 1);
+    }
+
+    mb.lock().close();
+    drop(mb);
+    let mapping = server.join().unwrap();
+    for t in 0..THREADS {
+        let value = (t * ROUNDS + ROUNDS - 1) as u16;
+        assert_eq!(&mapping.registers()[10 * t..10 * t + 10], &[value; 10]);
+    }
+    assert_eq!(mapping.registers()[99], 1234);
+}
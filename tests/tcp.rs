@@ -0,0 +1,64 @@
+#![cfg(feature = "libmodbus")]
+
+extern crate modbus;
+
+mod common;
+
+use modbus::{Modbus, ModbusServer, ModbusMapping};
+use common::spawn_server;
+
+fn serve(server: &mut ModbusServer)
+{
+    let mut mapping = ModbusMapping::new(0, 0, 10, 0);
+    server.serve(&mut mapping).unwrap();
+}
+
+fn round_trip(mb: &Modbus)
+{
+    mb.connect().unwrap();
+    mb.write_registers(0, &[0xcafe, 0xbeef]).unwrap();
+    let mut dest = [0u16; 2];
+    mb.read_registers(0, &mut dest).unwrap();
+    assert_eq!(dest, [0xcafe, 0xbeef]);
+    mb.close();
+}
+
+#[test]
+fn test_tcp_ipv4_loopback() {
+    let (addr, server) = spawn_server("127.0.0.1:0", serve);
+    assert!(addr.is_ipv4());
+    round_trip(&Modbus::new_tcp(&addr));
+    server.join().unwrap();
+}
+
+#[test]
+fn test_tcp_ipv6_loopback() {
+    let (addr, server) = spawn_server("[::1]:0", serve);
+    assert!(addr.is_ipv6());
+    round_trip(&Modbus::new_tcp(&addr));
+    server.join().unwrap();
+}
+
+#[test]
+fn test_tcp_pi() {
+    let (addr, server) = spawn_server("[::1]:0", serve);
+    round_trip(&Modbus::new_tcp_pi("::1", &addr.port().to_string()).unwrap());
+    server.join().unwrap();
+
+    let (addr, server) = spawn_server("127.0.0.1:0", serve);
+    round_trip(&Modbus::new_tcp_pi("127.0.0.1", &addr.port().to_string()).unwrap());
+    server.join().unwrap();
+}
+
+#[test]
+fn test_tcp_pi_hostname() {
+    // localhost may resolve to ::1 first, libmodbus tries every address
+    let (addr, server) = spawn_server("127.0.0.1:0", serve);
+    round_trip(&Modbus::new_tcp_pi("localhost", &addr.port().to_string()).unwrap());
+    server.join().unwrap();
+}
+
+#[test]
+fn test_tcp_pi_invalid() {
+    assert!(Modbus::new_tcp_pi("local\0host", "502").is_err());
+}
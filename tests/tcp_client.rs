@@ -0,0 +1,294 @@
+extern crate modbus;
+
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use modbus::{ModbusClient, ModbusError, Exception, MODBUS_BROADCAST_ADDRESS, MODBUS_TCP_SLAVE};
+use modbus::codec::{self, Request, Response};
+
+/// Registers and coils of the server, 0 to 99
+struct Tables {
+    coils: Vec<bool>,
+    registers: Vec<u16>,
+}
+
+impl Tables {
+    fn respond(&mut self, req: &Request) -> Response
+    {
+        let exception = |exception| Response::Exception { function: req.function_code(),
+                                                          exception };
+        let in_range = |addr: u16, nb: usize| addr as usize + nb <= 100;
+        match *req {
+            Request::ReadCoils { addr, nb } if in_range(addr, nb as usize) => {
+                Response::ReadCoils(self.coils[addr as usize..(addr + nb) as usize].to_vec())
+            }
+            Request::ReadDiscreteInputs { addr, nb } if in_range(addr, nb as usize) => {
+                Response::ReadDiscreteInputs((addr..addr + nb).map(|i| i % 2 == 1).collect())
+            }
+            Request::ReadHoldingRegisters { addr, nb } if in_range(addr, nb as usize) => {
+                Response::ReadHoldingRegisters(self.registers[addr as usize..(addr + nb) as usize]
+                                                   .to_vec())
+            }
+            Request::ReadInputRegisters { addr, nb } if in_range(addr, nb as usize) => {
+                Response::ReadInputRegisters((addr..addr + nb).map(|i| 1000 + i).collect())
+            }
+            Request::WriteSingleCoil { addr, value } if in_range(addr, 1) => {
+                self.coils[addr as usize] = value;
+                Response::WriteSingleCoil { addr, value }
+            }
+            Request::WriteSingleRegister { addr, value } if in_range(addr, 1) => {
+                self.registers[addr as usize] = value;
+                Response::WriteSingleRegister { addr, value }
+            }
+            Request::WriteMultipleCoils { addr, ref values } if in_range(addr, values.len()) => {
+                self.coils[addr as usize..addr as usize + values.len()].copy_from_slice(values);
+                Response::WriteMultipleCoils { addr, nb: values.len() as u16 }
+            }
+            Request::WriteMultipleRegisters { addr, ref values } if in_range(addr, values.len()) => {
+                self.registers[addr as usize..addr as usize + values.len()].copy_from_slice(values);
+                Response::WriteMultipleRegisters { addr, nb: values.len() as u16 }
+            }
+            Request::MaskWriteRegister { addr, and_mask, or_mask } if in_range(addr, 1) => {
+                let reg = &mut self.registers[addr as usize];
+                *reg = (*reg & and_mask) | (or_mask & !and_mask);
+                Response::MaskWriteRegister { addr, and_mask, or_mask }
+            }
+            Request::ReadWriteMultipleRegisters { read_addr, read_nb, write_addr, ref values }
+                if in_range(read_addr, read_nb as usize) && in_range(write_addr, values.len()) => {
+                let write_addr = write_addr as usize;
+                self.registers[write_addr..write_addr + values.len()].copy_from_slice(values);
+                Response::ReadWriteMultipleRegisters(
+                    self.registers[read_addr as usize..(read_addr + read_nb) as usize].to_vec())
+            }
+            Request::ReadDeviceIdentification { .. } => exception(Exception::IllegalFunction),
+            _ => exception(Exception::IllegalDataAddress),
+        }
+    }
+}
+
+/// Read the next Modbus TCP frame, None at the end of the stream
+fn read_frame(stream: &mut TcpStream, buf: &mut Vec<u8>) -> Option<Vec<u8>>
+{
+    let mut chunk = [0u8; 260];
+    loop {
+        if let Some(len) = codec::tcp_frame_length(buf).unwrap() {
+            if buf.len() >= len {
+                return Some(buf.drain(..len).collect())
+            }
+        }
+        match stream.read(&mut chunk).unwrap() {
+            0 => return None,
+            n => buf.extend_from_slice(&chunk[..n]),
+        }
+    }
+}
+
+/// A server answering with the tables, and Report Server ID
+fn spawn_server() -> SocketAddr
+{
+    spawn_scripted(|mut stream| {
+        let mut tables = Tables { coils: vec![false; 100], registers: vec![0; 100] };
+        let mut buf = Vec::new();
+        while let Some(adu) = read_frame(&mut stream, &mut buf) {
+            let frame = codec::decode_tcp(&adu).unwrap();
+            if frame.unit_id == MODBUS_BROADCAST_ADDRESS as u8 {
+                tables.respond(&Request::decode(frame.pdu).unwrap());
+                continue
+            }
+            let rsp = if frame.pdu == [0x11] {
+                vec![0x11, 3, 0x42, 0xFF, 0x07]
+            } else {
+                tables.respond(&Request::decode(frame.pdu).unwrap()).encode().unwrap()
+            };
+            stream.write_all(&codec::encode_tcp(frame.transaction_id, frame.unit_id, &rsp))
+                  .unwrap();
+        }
+    })
+}
+
+/// A server handling its only connection with script
+fn spawn_scripted<F>(script: F) -> SocketAddr
+    where F: FnOnce(TcpStream) + Send + 'static
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        script(stream);
+    });
+    addr
+}
+
+fn is_timeout<T: ::std::fmt::Debug>(r: Result<T, ModbusError>) -> bool
+{
+    match r {
+        Err(ModbusError::Io(ref e)) => e.kind() == io::ErrorKind::TimedOut,
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
+#[test]
+fn test_tcp_client_operations() {
+    let mb = ModbusClient::new_tcp(&spawn_server());
+    mb.connect().unwrap();
+    assert_eq!(mb.slave(), Some(MODBUS_TCP_SLAVE));
+    assert_eq!(mb.get_header_length().unwrap(), 7);
+
+    assert_eq!(mb.write_bit(3, 1).unwrap(), 1);
+    assert_eq!(mb.write_bits(10, &[true, false, true]).unwrap(), 3);
+    let mut bits = [0u8; 4];
+    assert_eq!(mb.read_bits(9, &mut bits).unwrap(), 4);
+    assert_eq!(bits, [0, 1, 0, 1]);
+    let mut bits = [false; 3];
+    assert_eq!(mb.read_bits(2, &mut bits).unwrap(), 3);
+    assert_eq!(bits, [false, true, false]);
+    assert_eq!(mb.read_input_bits(0, &mut bits).unwrap(), 3);
+    assert_eq!(bits, [false, true, false]);
+
+    assert_eq!(mb.write_register(0, 0x1234).unwrap(), 1);
+    assert_eq!(mb.write_registers(1, &[5, 6, 7]).unwrap(), 3);
+    assert_eq!(mb.mask_write_register(0, 0xFF00, 0x0056).unwrap(), 1);
+    let mut regs = [0u16; 4];
+    assert_eq!(mb.read_registers(0, &mut regs).unwrap(), 4);
+    assert_eq!(regs, [0x1256, 5, 6, 7]);
+    assert_eq!(mb.read_input_registers(2, &mut regs).unwrap(), 4);
+    assert_eq!(regs, [1002, 1003, 1004, 1005]);
+
+    let mut regs = [0u16; 3];
+    assert_eq!(mb.write_and_read_registers(2, &[8, 9], 1, &mut regs).unwrap(), 3);
+    assert_eq!(regs, [5, 8, 9]);
+
+    let id = mb.report_server_id().unwrap();
+    assert_eq!((id.server_id, id.running, id.data), (0x42, true, vec![0x07]));
+
+    match mb.read_registers(99, &mut regs) {
+        Err(ModbusError::Exception(Exception::IllegalDataAddress)) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+
+    // Raw requests are framed by the client, the length of the MBAP header is counted as
+    // libmodbus does
+    assert_eq!(mb.send_raw_request(&[0xFF, 0x06, 0x00, 0x05, 0x00, 0x2A]).unwrap(), 12);
+    let rsp = mb.receive_confirmation().unwrap();
+    let offset = mb.get_header_length().unwrap() as usize;
+    assert_eq!(&rsp[2..offset], &[0x00, 0x00, 0x00, 0x06, 0xFF]);
+    assert_eq!(&rsp[offset..], &[0x06, 0x00, 0x05, 0x00, 0x2A]);
+
+    mb.close();
+}
+
+#[test]
+fn test_tcp_client_broadcast() {
+    let mb = ModbusClient::new_tcp(&spawn_server());
+    mb.connect().unwrap();
+
+    mb.set_slave(MODBUS_BROADCAST_ADDRESS).unwrap();
+    assert_eq!(mb.write_register(4, 0xabcd).unwrap(), 1);
+    let mut dest = [0u16; 1];
+    assert!(mb.read_registers(4, &mut dest).is_err());
+
+    mb.set_slave(MODBUS_TCP_SLAVE).unwrap();
+    assert_eq!(mb.read_registers(4, &mut dest).unwrap(), 1);
+    assert_eq!(dest, [0xabcd]);
+
+    mb.close();
+}
+
+#[test]
+fn test_tcp_client_skips_stale_responses() {
+    let addr = spawn_scripted(|mut stream| {
+        let mut buf = Vec::new();
+        let adu = read_frame(&mut stream, &mut buf).unwrap();
+        let frame = codec::decode_tcp(&adu).unwrap();
+        assert_eq!(frame.pdu, [0x03, 0x00, 0x00, 0x00, 0x01]);
+        let tid = frame.transaction_id;
+        // A response to an earlier request, then the response split in two
+        stream.write_all(&codec::encode_tcp(tid.wrapping_sub(1), 0xFF, &[0x03, 2, 0xde, 0xad]))
+              .unwrap();
+        let rsp = codec::encode_tcp(tid, 0xFF, &[0x03, 2, 0x12, 0x34]);
+        stream.write_all(&rsp[..5]).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        stream.write_all(&rsp[5..]).unwrap();
+        read_frame(&mut stream, &mut buf);
+    });
+
+    let mb = ModbusClient::new_tcp(&addr);
+    mb.connect().unwrap();
+    let mut dest = [0u16; 1];
+    assert_eq!(mb.read_registers(0, &mut dest).unwrap(), 1);
+    assert_eq!(dest, [0x1234]);
+    mb.close();
+}
+
+#[test]
+fn test_tcp_client_invalid_responses() {
+    let addr = spawn_scripted(|mut stream| {
+        let mut buf = Vec::new();
+        // Another unit
+        let adu = read_frame(&mut stream, &mut buf).unwrap();
+        let tid = codec::decode_tcp(&adu).unwrap().transaction_id;
+        stream.write_all(&codec::encode_tcp(tid, 0x01, &[0x03, 2, 0, 1])).unwrap();
+        // Another function
+        let adu = read_frame(&mut stream, &mut buf).unwrap();
+        let tid = codec::decode_tcp(&adu).unwrap().transaction_id;
+        stream.write_all(&codec::encode_tcp(tid, 0xFF, &[0x04, 2, 0, 1])).unwrap();
+        // No response, then the connection is closed
+        read_frame(&mut stream, &mut buf).unwrap();
+        read_frame(&mut stream, &mut buf).unwrap();
+    });
+
+    let mb = ModbusClient::new_tcp(&addr);
+    mb.connect().unwrap();
+    mb.set_response_timeout(Duration::from_millis(100)).unwrap();
+    let mut dest = [0u16; 1];
+    match mb.read_registers(0, &mut dest) {
+        Err(ModbusError::BadSlave) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    assert!(mb.read_registers(0, &mut dest).is_err());
+
+    let start = Instant::now();
+    assert!(is_timeout(mb.read_registers(0, &mut dest)));
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    assert!(mb.read_registers(0, &mut dest).is_err());
+    mb.close();
+}
+
+#[test]
+fn test_tcp_client_write_echo_mismatch() {
+    let addr = spawn_scripted(|mut stream| {
+        let mut buf = Vec::new();
+        // Another address, another quantity, another value
+        for rsp in &[[0x06, 0x00, 0x06, 0x00, 0x03], [0x10, 0x00, 0x05, 0x00, 0x01],
+                     [0x06, 0x00, 0x05, 0x00, 0x04]] {
+            let adu = read_frame(&mut stream, &mut buf).unwrap();
+            let tid = codec::decode_tcp(&adu).unwrap().transaction_id;
+            stream.write_all(&codec::encode_tcp(tid, 0xFF, rsp)).unwrap();
+        }
+    });
+
+    let mb = ModbusClient::new_tcp(&addr);
+    mb.connect().unwrap();
+    let results = [mb.write_register(5, 3), mb.write_registers(5, &[1, 2]),
+                   mb.write_register(5, 3)];
+    for r in results {
+        match r {
+            Err(ModbusError::BadData) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+    mb.close();
+}
+
+#[test]
+fn test_tcp_client_not_connected() {
+    let addr = "127.0.0.1:1502".parse().unwrap();
+    let mb = ModbusClient::new_tcp(&addr);
+    assert!(mb.set_slave(248).is_err());
+    assert!(mb.set_response_timeout(Duration::from_secs(0)).is_err());
+    let mut dest = [0u16; 1];
+    assert!(mb.read_registers(0, &mut dest).is_err());
+}
@@ -0,0 +1,72 @@
+#![cfg(feature = "libmodbus")]
+
+extern crate modbus;
+
+use std::net::TcpListener;
+use std::time::{Duration, Instant};
+
+use modbus::{Modbus, ModbusError};
+
+fn context() -> Modbus
+{
+    let addr = "127.0.0.1:1502".parse().unwrap();
+    Modbus::new_tcp(&addr)
+}
+
+#[test]
+fn test_response_timeout() {
+    let mb = context();
+
+    mb.set_response_timeout(Duration::from_millis(100)).unwrap();
+    assert_eq!(mb.get_response_timeout().unwrap(), Duration::from_millis(100));
+
+    mb.set_response_timeout(Duration::new(5, 250_000_999)).unwrap();
+    assert_eq!(mb.get_response_timeout().unwrap(), Duration::new(5, 250_000_000));
+
+    assert!(mb.set_response_timeout(Duration::from_secs(0)).is_err());
+    assert!(mb.set_response_timeout(Duration::new(0, 999)).is_err());
+    assert!(mb.set_response_timeout(Duration::from_secs(1 << 40)).is_err());
+    assert_eq!(mb.get_response_timeout().unwrap(), Duration::new(5, 250_000_000));
+}
+
+#[test]
+fn test_byte_timeout() {
+    let mb = context();
+
+    mb.set_byte_timeout(Some(Duration::from_millis(20))).unwrap();
+    assert_eq!(mb.get_byte_timeout().unwrap(), Some(Duration::from_millis(20)));
+
+    #[cfg(not(libmodbus_3_0))]
+    {
+        mb.set_byte_timeout(None).unwrap();
+        assert_eq!(mb.get_byte_timeout().unwrap(), None);
+    }
+    // libmodbus 3.0 cannot disable the byte timeout
+    #[cfg(libmodbus_3_0)]
+    assert!(mb.set_byte_timeout(None).is_err());
+
+    let previous = mb.get_byte_timeout().unwrap();
+    assert!(mb.set_byte_timeout(Some(Duration::from_secs(0))).is_err());
+    assert_eq!(mb.get_byte_timeout().unwrap(), previous);
+}
+
+#[test]
+fn test_response_timeout_expires() {
+    // Accepts connections but never answers
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let mb = Modbus::new_tcp(&addr);
+    mb.set_response_timeout(Duration::from_millis(100)).unwrap();
+    mb.connect().unwrap();
+
+    let start = Instant::now();
+    let mut dest = [0u16; 1];
+    match mb.read_registers(0, &mut dest) {
+        Err(ModbusError::Io(ref e)) if e.kind() == std::io::ErrorKind::TimedOut => (),
+        r => panic!("unexpected {:?}", r),
+    }
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    mb.close();
+}
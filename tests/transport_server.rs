@@ -0,0 +1,241 @@
+extern crate modbus;
+
+mod common;
+
+use std::io::{self, Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use modbus::{Adu, ChannelTransport, ModbusClient, ModbusError, TcpTransport, Transport,
+             TransportServer, RtuTransport, RequestHandler, Exception, Parity, DataBits, StopBits};
+use modbus::codec;
+use common::Pty;
+
+/// Coils and holding registers 0 to 31
+struct Tables {
+    coils: [u8; 32],
+    registers: [u16; 32],
+}
+
+impl Tables {
+    fn new() -> Tables
+    {
+        Tables { coils: [0; 32], registers: [0; 32] }
+    }
+}
+
+fn range(addr: u16, nb: usize) -> Result<::std::ops::Range<usize>, Exception>
+{
+    if addr as usize + nb > 32 {
+        return Err(Exception::IllegalDataAddress)
+    }
+    Ok(addr as usize..addr as usize + nb)
+}
+
+impl RequestHandler for Tables {
+    fn read_coils(&mut self, addr: u16, dest: &mut [u8]) -> Result<(), Exception>
+    {
+        dest.copy_from_slice(&self.coils[range(addr, dest.len())?]);
+        Ok(())
+    }
+
+    fn read_holding_registers(&mut self, addr: u16, dest: &mut [u16]) -> Result<(), Exception>
+    {
+        dest.copy_from_slice(&self.registers[range(addr, dest.len())?]);
+        Ok(())
+    }
+
+    fn write_coils(&mut self, addr: u16, values: &[u8]) -> Result<(), Exception>
+    {
+        self.coils[range(addr, values.len())?].copy_from_slice(values);
+        Ok(())
+    }
+
+    fn write_registers(&mut self, addr: u16, values: &[u16]) -> Result<(), Exception>
+    {
+        self.registers[range(addr, values.len())?].copy_from_slice(values);
+        Ok(())
+    }
+}
+
+/// A custom link: any transport, recording the function codes of the frames it sends
+struct Recorder<T: Transport> {
+    inner: T,
+    sent: Arc<Mutex<Vec<u8>>>,
+}
+
+impl<T: Transport> Transport for Recorder<T> {
+    fn connect(&mut self) -> Result<(), ModbusError> { self.inner.connect() }
+    fn close(&mut self) { self.inner.close() }
+    fn header_length(&self) -> usize { self.inner.header_length() }
+    fn has_transaction_id(&self) -> bool { self.inner.has_transaction_id() }
+
+    fn send(&mut self, adu: &Adu) -> Result<(), ModbusError>
+    {
+        self.sent.lock().unwrap().push(adu.pdu[0]);
+        self.inner.send(adu)
+    }
+
+    fn receive(&mut self, timeout: Option<Duration>) -> Result<Adu, ModbusError>
+    {
+        self.inner.receive(timeout)
+    }
+}
+
+fn expect_exception<T>(r: Result<T, ModbusError>, exception: Exception)
+    where T: ::std::fmt::Debug
+{
+    match r {
+        Err(ModbusError::Exception(e)) if e == exception => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
+#[test]
+fn test_channel_client_and_server() {
+    let (client, server) = ChannelTransport::pair();
+    let server = thread::spawn(move || {
+        let mut tables = Tables::new();
+        TransportServer::new(server).serve(&mut tables).map(|()| tables.registers)
+    });
+
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let mb = ModbusClient::new(Recorder { inner: client, sent: sent.clone() });
+    mb.connect().unwrap();
+    mb.set_slave(1).unwrap();
+    assert_eq!(mb.get_header_length().unwrap(), 1);
+
+    assert_eq!(mb.write_bit(2, 1).unwrap(), 1);
+    assert_eq!(mb.write_bits(4, &[true, true, false, true]).unwrap(), 4);
+    let mut bits = [false; 8];
+    assert_eq!(mb.read_bits(0, &mut bits).unwrap(), 8);
+    assert_eq!(bits, [false, false, true, false, true, true, false, true]);
+
+    assert_eq!(mb.write_register(0, 0x1234).unwrap(), 1);
+    assert_eq!(mb.write_registers(1, &[1, 2, 3]).unwrap(), 3);
+    assert_eq!(mb.mask_write_register(0, 0x00FF, 0xAB00).unwrap(), 1);
+    let mut regs = [0u16; 3];
+    assert_eq!(mb.write_and_read_registers(3, &[4, 5], 2, &mut regs).unwrap(), 3);
+    assert_eq!(regs, [2, 4, 5]);
+    let mut regs = [0u16; 2];
+    assert_eq!(mb.read_registers(0, &mut regs).unwrap(), 2);
+    assert_eq!(regs, [0xAB34, 1]);
+
+    // Exceptions of the handler and of the functions it does not implement
+    expect_exception(mb.read_registers(31, &mut regs), Exception::IllegalDataAddress);
+    expect_exception(mb.read_input_registers(0, &mut regs), Exception::IllegalFunction);
+    expect_exception(mb.report_server_id(), Exception::IllegalFunction);
+
+    // Invalid requests
+    mb.send_raw_request(&[1, 0x03, 0x00, 0x00]).unwrap();
+    assert_eq!(mb.receive_confirmation().unwrap(), vec![1, 0x83, 3]);
+    mb.send_raw_request(&[1, 0x03, 0xFF, 0xFF, 0x00, 0x02]).unwrap();
+    assert_eq!(mb.receive_confirmation().unwrap(), vec![1, 0x83, 2]);
+    mb.send_raw_request(&[1, 0x41, 0x00]).unwrap();
+    assert_eq!(mb.receive_confirmation().unwrap(), vec![1, 0xC1, 1]);
+
+    assert_eq!(*sent.lock().unwrap(),
+               vec![0x05, 0x0F, 0x01, 0x06, 0x10, 0x16, 0x17, 0x03, 0x03, 0x04, 0x11,
+                    0x03, 0x03, 0x41]);
+
+    // The server returns once the client is gone
+    mb.close();
+    let registers = server.join().unwrap().unwrap();
+    assert_eq!(&registers[..5], &[0xAB34, 1, 2, 4, 5]);
+}
+
+#[test]
+fn test_channel_server_slave() {
+    let (client, server) = ChannelTransport::pair();
+    let server = thread::spawn(move || {
+        let mut server = TransportServer::new(server);
+        assert!(server.set_slave(0).is_err());
+        server.set_slave(7).unwrap();
+        assert_eq!(server.slave(), Some(7));
+        server.serve(&mut Tables::new())
+    });
+
+    let mb = ModbusClient::new(client);
+    mb.set_response_timeout(Duration::from_millis(50)).unwrap();
+    let mut dest = [0u16; 1];
+
+    // Requests to another slave are not answered
+    mb.set_slave(6).unwrap();
+    match mb.read_registers(0, &mut dest) {
+        Err(ModbusError::Io(ref e)) if e.kind() == io::ErrorKind::TimedOut => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    mb.set_slave(7).unwrap();
+    assert_eq!(mb.read_registers(0, &mut dest).unwrap(), 1);
+
+    mb.close();
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn test_tcp_transport_server() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let mut tables = Tables::new();
+        for _ in 0..2 {
+            let (stream, _) = listener.accept().unwrap();
+            let mut server = TransportServer::new(TcpTransport::from_stream(stream).unwrap());
+            server.serve(&mut tables).unwrap();
+        }
+        tables.registers
+    });
+
+    // The tables outlive the connections
+    for i in 0..2 {
+        let mb = ModbusClient::new_tcp(&addr);
+        mb.connect().unwrap();
+        let mut dest = [0u16; 1];
+        assert_eq!(mb.read_registers(10, &mut dest).unwrap(), 1);
+        assert_eq!(dest, [i]);
+        assert_eq!(mb.write_register(10, i as i32 + 1).unwrap(), 1);
+        mb.close();
+    }
+    assert_eq!(server.join().unwrap()[10], 2);
+}
+
+#[test]
+fn test_rtu_transport_server_pty() {
+    let pty = Pty::open();
+    let mut transport = RtuTransport::new(&pty.slave_name, 9600, Parity::Even,
+                                          DataBits::Eight, StopBits::One).unwrap();
+    transport.connect().unwrap();
+    let mut server = TransportServer::new(transport);
+    server.set_slave(1).unwrap();
+
+    let mut master = pty.master.try_clone().unwrap();
+    let client = thread::spawn(move || {
+        // A corrupted frame, a broadcast and a request to another slave are not answered
+        let mut corrupted = codec::encode_rtu(1, &[0x06, 0x00, 0x01, 0x00, 0x07]);
+        corrupted[3] ^= 0x01;
+        master.write_all(&corrupted).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        master.write_all(&codec::encode_rtu(0, &[0x06, 0x00, 0x01, 0x00, 0x2A])).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        master.write_all(&codec::encode_rtu(2, &[0x03, 0x00, 0x01, 0x00, 0x01])).unwrap();
+        thread::sleep(Duration::from_millis(20));
+
+        master.write_all(&codec::encode_rtu(1, &[0x03, 0x00, 0x01, 0x00, 0x01])).unwrap();
+        let mut rsp = [0u8; 7];
+        master.read_exact(&mut rsp).unwrap();
+        assert_eq!(rsp.to_vec(), codec::encode_rtu(1, &[0x03, 2, 0x00, 0x2A]));
+    });
+
+    let mut tables = Tables::new();
+    match server.serve_request(&mut tables, Some(Duration::from_secs(1))) {
+        Err(ModbusError::BadCrc) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    for _ in 0..3 {
+        server.serve_request(&mut tables, Some(Duration::from_secs(1))).unwrap();
+    }
+    client.join().unwrap();
+    assert_eq!(tables.registers[1], 0x2A);
+}
@@ -0,0 +1,230 @@
+extern crate modbus;
+
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use modbus::{ModbusClient, ModbusError, RequestHandler, Exception, Transport, TransportServer,
+             UdpTransport, MODBUS_TCP_SLAVE};
+use modbus::codec::{self, Request};
+
+/// Holding registers 0 to 15
+struct Registers([u16; 16]);
+
+impl RequestHandler for Registers {
+    fn read_holding_registers(&mut self, addr: u16, dest: &mut [u16]) -> Result<(), Exception>
+    {
+        let addr = addr as usize;
+        let values = self.0.get(addr..addr + dest.len()).ok_or(Exception::IllegalDataAddress)?;
+        dest.copy_from_slice(values);
+        Ok(())
+    }
+
+    fn write_registers(&mut self, addr: u16, values: &[u16]) -> Result<(), Exception>
+    {
+        let addr = addr as usize;
+        let dest = self.0.get_mut(addr..addr + values.len()).ok_or(Exception::IllegalDataAddress)?;
+        dest.copy_from_slice(values);
+        Ok(())
+    }
+}
+
+/// A scripted server: the socket, with the address of the client
+fn scripted_server() -> (UdpSocket, SocketAddr)
+{
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let addr = socket.local_addr().unwrap();
+    (socket, addr)
+}
+
+/// Receive a request, returning its transaction identifier and the client address
+fn recv_request(socket: &UdpSocket) -> (u16, SocketAddr)
+{
+    let mut buf = [0u8; 260];
+    let (n, from) = socket.recv_from(&mut buf).unwrap();
+    let frame = codec::decode_tcp(&buf[..n]).unwrap();
+    assert_eq!(Request::decode(frame.pdu).unwrap(), Request::ReadHoldingRegisters { addr: 0, nb: 1 });
+    (frame.transaction_id, from)
+}
+
+fn reply(socket: &UdpSocket, transaction_id: u16, value: u16, to: SocketAddr)
+{
+    let rsp = [0x03, 2, (value >> 8) as u8, value as u8];
+    socket.send_to(&codec::encode_tcp(transaction_id, 0xFF, &rsp), to).unwrap();
+}
+
+fn client(addr: &SocketAddr) -> ModbusClient<UdpTransport>
+{
+    let mb = ModbusClient::new_udp(addr);
+    mb.connect().unwrap();
+    mb.transport().set_retry_interval(Duration::from_millis(50)).unwrap();
+    mb
+}
+
+#[test]
+fn test_udp_client_and_server() {
+    let transport = UdpTransport::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = transport.local_addr().unwrap();
+    assert_eq!(transport.header_length(), 7);
+    let server = thread::spawn(move || {
+        let mut server = TransportServer::new(transport);
+        let mut registers = Registers([0; 16]);
+        for _ in 0..4 {
+            server.serve_request(&mut registers, None).unwrap();
+        }
+        registers.0
+    });
+
+    let mb = ModbusClient::new_udp(&addr);
+    assert_eq!(mb.slave(), Some(MODBUS_TCP_SLAVE));
+    mb.connect().unwrap();
+
+    assert_eq!(mb.write_registers(2, &[7, 8, 9]).unwrap(), 3);
+    let mut dest = [0u16; 4];
+    assert_eq!(mb.read_registers(1, &mut dest).unwrap(), 4);
+    assert_eq!(dest, [0, 7, 8, 9]);
+    assert_eq!(mb.write_register(4, 10).unwrap(), 1);
+    match mb.read_registers(14, &mut dest) {
+        Err(ModbusError::Exception(Exception::IllegalDataAddress)) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+
+    assert_eq!(&server.join().unwrap()[..5], &[0, 0, 7, 8, 10]);
+    mb.close();
+}
+
+#[test]
+fn test_udp_lost_request_is_retried() {
+    let (socket, addr) = scripted_server();
+    let server = thread::spawn(move || {
+        // The first datagram is lost, the retry is answered
+        let (tid, _) = recv_request(&socket);
+        let (retry_tid, from) = recv_request(&socket);
+        assert_eq!(retry_tid, tid);
+        reply(&socket, tid, 0x1234, from);
+    });
+
+    let mb = client(&addr);
+    let mut dest = [0u16; 1];
+    assert_eq!(mb.read_registers(0, &mut dest).unwrap(), 1);
+    assert_eq!(dest, [0x1234]);
+
+    server.join().unwrap();
+    mb.close();
+}
+
+#[test]
+fn test_udp_duplicate_and_invalid_responses_are_skipped() {
+    let (socket, addr) = scripted_server();
+    let server = thread::spawn(move || {
+        // The response to the first datagram is late, both datagrams are answered
+        let (tid, _) = recv_request(&socket);
+        let (_, from) = recv_request(&socket);
+        reply(&socket, tid, 1, from);
+        reply(&socket, tid, 1, from);
+
+        // The next request gets the duplicate first, then garbage, then its response
+        let (next_tid, from) = recv_request(&socket);
+        assert_eq!(next_tid, tid.wrapping_add(1));
+        socket.send_to(&[0x00, 0x01, 0x02], from).unwrap();
+        reply(&socket, next_tid, 2, from);
+    });
+
+    let mb = client(&addr);
+    // A second retry would be taken for the next request
+    mb.transport().set_retries(1);
+    let mut dest = [0u16; 1];
+    assert_eq!(mb.read_registers(0, &mut dest).unwrap(), 1);
+    assert_eq!(dest, [1]);
+    assert_eq!(mb.read_registers(0, &mut dest).unwrap(), 1);
+    assert_eq!(dest, [2]);
+
+    server.join().unwrap();
+    mb.close();
+}
+
+#[test]
+fn test_udp_retries_exhausted() {
+    let (socket, addr) = scripted_server();
+    let mb = client(&addr);
+    mb.transport().set_retries(3);
+    mb.set_response_timeout(Duration::from_millis(400)).unwrap();
+
+    let mut dest = [0u16; 1];
+    let start = Instant::now();
+    match mb.read_registers(0, &mut dest) {
+        Err(ModbusError::Io(ref e)) if e.kind() == io::ErrorKind::TimedOut => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    assert!(start.elapsed() >= Duration::from_millis(400));
+
+    // The request and its 3 retries, all with the same transaction identifier
+    let (tid, _) = recv_request(&socket);
+    for _ in 0..3 {
+        assert_eq!(recv_request(&socket).0, tid);
+    }
+    socket.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+    assert!(socket.recv_from(&mut [0u8; 260]).is_err());
+
+    assert!(mb.transport().set_retry_interval(Duration::from_secs(0)).is_err());
+    mb.close();
+}
+
+#[test]
+fn test_udp_no_retry_after_response() {
+    let (socket, addr) = scripted_server();
+    let mb = client(&addr);
+    mb.set_response_timeout(Duration::from_millis(200)).unwrap();
+    let server = thread::spawn(move || {
+        let (tid, from) = recv_request(&socket);
+        reply(&socket, tid, 3, from);
+        socket
+    });
+
+    let mut dest = [0u16; 1];
+    assert_eq!(mb.read_registers(0, &mut dest).unwrap(), 1);
+    assert_eq!(dest, [3]);
+    let socket = server.join().unwrap();
+
+    // Waiting again for a response does not send the answered request again
+    assert!(mb.receive_confirmation().is_err());
+    socket.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+    assert!(socket.recv_from(&mut [0u8; 260]).is_err());
+
+    mb.close();
+}
+
+#[test]
+fn test_udp_writes_retried_once_enabled() {
+    let (socket, addr) = scripted_server();
+    let mb = client(&addr);
+    mb.set_response_timeout(Duration::from_millis(200)).unwrap();
+    assert!(!mb.transport().retry_writes());
+
+    // Lost, and not retried
+    assert!(mb.write_register(1, 5).is_err());
+    let mut buf = [0u8; 260];
+    let n = socket.recv(&mut buf).unwrap();
+    let frame = codec::decode_tcp(&buf[..n]).unwrap();
+    assert_eq!(Request::decode(frame.pdu).unwrap(), Request::WriteSingleRegister { addr: 1, value: 5 });
+    socket.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+    assert!(socket.recv_from(&mut buf).is_err());
+
+    mb.transport().set_retry_writes(true);
+    socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let server = thread::spawn(move || {
+        let mut buf = [0u8; 260];
+        let (n, _) = socket.recv_from(&mut buf).unwrap();
+        let tid = codec::decode_tcp(&buf[..n]).unwrap().transaction_id;
+        let (n, from) = socket.recv_from(&mut buf).unwrap();
+        let frame = codec::decode_tcp(&buf[..n]).unwrap();
+        assert_eq!(frame.transaction_id, tid);
+        socket.send_to(&codec::encode_tcp(tid, 0xFF, frame.pdu), from).unwrap();
+    });
+    assert_eq!(mb.write_register(1, 5).unwrap(), 1);
+
+    server.join().unwrap();
+    mb.close();
+}